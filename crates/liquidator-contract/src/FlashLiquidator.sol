@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+interface IERC20 {
+    function balanceOf(address account) external view returns (uint256);
+}
+
+/// The MM protocol's ExchangeRouter; its Router pulls repay amounts from the liquidator.
+interface IExchangeRouter {
+    struct LiquidationParams {
+        address account;
+        uint256 positionId;
+    }
+
+    function router() external view returns (address);
+    function executeLiquidationBatch(LiquidationParams[] memory params) external;
+}
+
+/// Aave V3 style flashloan pool.
+interface IFlashLoanPool {
+    function flashLoan(
+        address receiverAddress,
+        address[] calldata assets,
+        uint256[] calldata amounts,
+        uint256[] calldata interestRateModes,
+        address onBehalfOf,
+        bytes calldata params,
+        uint16 referralCode
+    ) external;
+}
+
+/// Uniswap V2 style swap router.
+interface ISwapRouter {
+    function getAmountsIn(uint256 amountOut, address[] calldata path) external view returns (uint256[] memory amounts);
+    function swapTokensForExactTokens(
+        uint256 amountOut,
+        uint256 amountInMax,
+        address[] calldata path,
+        address to,
+        uint256 deadline
+    ) external returns (uint256[] memory amounts);
+    function swapExactTokensForTokens(
+        uint256 amountIn,
+        uint256 amountOutMin,
+        address[] calldata path,
+        address to,
+        uint256 deadline
+    ) external returns (uint256[] memory amounts);
+}
+
+/// Liquidates MM positions with borrowed repay amounts in a single tx: borrows
+/// `loans` from an Aave V3 style pool, liquidates through the ExchangeRouter,
+/// swaps seized collateral into whatever the loans plus premiums still lack,
+/// lets the pool pull its repayment and sends everything left to the caller.
+///
+/// The swaps take any price; the caller is protected by `minProfits`, the
+/// least of each token that must be left once the loans are repaid, checked
+/// after the fact so a sandwiched swap reverts the whole liquidation.
+///
+/// Holds no funds between calls, anyone may call it.
+contract FlashLiquidator {
+    struct LiquidationParams {
+        address account;
+        uint256 positionId;
+    }
+
+    struct Loan {
+        address token;
+        uint256 amount;
+    }
+
+    struct MinProfit {
+        address token;
+        uint256 amount;
+    }
+
+    error ReentrantCall();
+    error UnexpectedLender(address lender);
+    error UnexpectedInitiator(address initiator);
+    error InsufficientProfit(address token, uint256 balance, uint256 minimum);
+    error TokenCallFailed(address token);
+
+    // lender of the flashloan in progress, the only caller executeOperation accepts
+    address private activeLender;
+
+    /// Borrow `loans` from `lender`, liquidate `params` through
+    /// `exchangeRouter`, swap the seized collateral back into the loan tokens
+    /// through `swapRouter` and repay the loans plus fees, reverting unless the
+    /// sender is left with at least `minProfits` of each token. `tokens` are
+    /// the tokens the liquidations may seize, every base and meme token of
+    /// their pools; they must include the loan and profit tokens, and all of
+    /// them are swept to the sender.
+    function flashLiquidate(
+        address lender,
+        address exchangeRouter,
+        LiquidationParams[] calldata params,
+        Loan[] calldata loans,
+        address swapRouter,
+        address[] calldata tokens,
+        MinProfit[] calldata minProfits
+    ) external {
+        if (activeLender != address(0)) revert ReentrantCall();
+        activeLender = lender;
+
+        address[] memory assets = new address[](loans.length);
+        uint256[] memory amounts = new uint256[](loans.length);
+        // mode 0, the loans are repaid within this tx
+        uint256[] memory modes = new uint256[](loans.length);
+        for (uint256 i; i < loans.length; i++) {
+            assets[i] = loans[i].token;
+            amounts[i] = loans[i].amount;
+        }
+        IFlashLoanPool(lender).flashLoan(
+            address(this),
+            assets,
+            amounts,
+            modes,
+            address(this),
+            abi.encode(exchangeRouter, params, swapRouter, tokens),
+            0
+        );
+        activeLender = address(0);
+
+        for (uint256 i; i < minProfits.length; i++) {
+            uint256 balance = IERC20(minProfits[i].token).balanceOf(address(this));
+            if (balance < minProfits[i].amount) {
+                revert InsufficientProfit(minProfits[i].token, balance, minProfits[i].amount);
+            }
+        }
+        for (uint256 i; i < tokens.length; i++) {
+            uint256 balance = IERC20(tokens[i]).balanceOf(address(this));
+            if (balance > 0) {
+                _call(tokens[i], abi.encodeWithSignature("transfer(address,uint256)", msg.sender, balance));
+            }
+        }
+    }
+
+    /// Aave V3 flashloan callback, running the liquidation and the swaps.
+    function executeOperation(
+        address[] calldata assets,
+        uint256[] calldata amounts,
+        uint256[] calldata premiums,
+        address initiator,
+        bytes calldata data
+    ) external returns (bool) {
+        if (activeLender == address(0) || msg.sender != activeLender) revert UnexpectedLender(msg.sender);
+        if (initiator != address(this)) revert UnexpectedInitiator(initiator);
+        (address exchangeRouter, LiquidationParams[] memory params, address swapRouter, address[] memory tokens) =
+            abi.decode(data, (address, LiquidationParams[], address, address[]));
+
+        address router = IExchangeRouter(exchangeRouter).router();
+        for (uint256 i; i < assets.length; i++) {
+            _approve(assets[i], router, amounts[i]);
+        }
+        IExchangeRouter.LiquidationParams[] memory batch = new IExchangeRouter.LiquidationParams[](params.length);
+        for (uint256 i; i < params.length; i++) {
+            batch[i] = IExchangeRouter.LiquidationParams(params[i].account, params[i].positionId);
+        }
+        IExchangeRouter(exchangeRouter).executeLiquidationBatch(batch);
+        for (uint256 i; i < assets.length; i++) {
+            _approve(assets[i], router, 0);
+        }
+
+        uint256[] memory owed = new uint256[](assets.length);
+        for (uint256 i; i < assets.length; i++) {
+            owed[i] = amounts[i] + premiums[i];
+        }
+        for (uint256 i; i < assets.length; i++) {
+            _cover(swapRouter, i, assets, owed, tokens);
+            // the lender pulls the repayment once this returns
+            _approve(assets[i], msg.sender, owed[i]);
+        }
+        return true;
+    }
+
+    // swap other tokens into assets[index] until it covers owed[index], sparing
+    // what the other loans still owe; a shortfall left makes the lender's pull revert
+    function _cover(
+        address swapRouter,
+        uint256 index,
+        address[] calldata assets,
+        uint256[] memory owed,
+        address[] memory tokens
+    ) internal {
+        address token = assets[index];
+        for (uint256 j; j < tokens.length; j++) {
+            uint256 balance = IERC20(token).balanceOf(address(this));
+            if (balance >= owed[index]) return;
+            address source = tokens[j];
+            if (source == token) continue;
+            uint256 spare = IERC20(source).balanceOf(address(this));
+            for (uint256 k; k < assets.length; k++) {
+                if (assets[k] == source) {
+                    spare = spare > owed[k] ? spare - owed[k] : 0;
+                }
+            }
+            if (spare == 0) continue;
+
+            address[] memory path = new address[](2);
+            path[0] = source;
+            path[1] = token;
+            uint256 needed = owed[index] - balance;
+            uint256 amountIn = ISwapRouter(swapRouter).getAmountsIn(needed, path)[0];
+            _approve(source, swapRouter, spare);
+            if (amountIn <= spare) {
+                ISwapRouter(swapRouter).swapTokensForExactTokens(needed, amountIn, path, address(this), block.timestamp);
+            } else {
+                ISwapRouter(swapRouter).swapExactTokensForTokens(spare, 0, path, address(this), block.timestamp);
+            }
+            _approve(source, swapRouter, 0);
+        }
+    }
+
+    // approve that tolerates tokens returning nothing or requiring a reset to 0 first
+    function _approve(address token, address spender, uint256 amount) internal {
+        (bool success, bytes memory result) =
+            token.call(abi.encodeWithSignature("approve(address,uint256)", spender, amount));
+        if (!success || (result.length > 0 && !abi.decode(result, (bool)))) {
+            _call(token, abi.encodeWithSignature("approve(address,uint256)", spender, 0));
+            _call(token, abi.encodeWithSignature("approve(address,uint256)", spender, amount));
+        }
+    }
+
+    // call a token, accepting no return data or true
+    function _call(address token, bytes memory data) internal {
+        (bool success, bytes memory result) = token.call(data);
+        if (!success || (result.length > 0 && !abi.decode(result, (bool)))) revert TokenCallFailed(token);
+    }
+}
@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import {Test} from "forge-std/Test.sol";
+import {FlashLiquidator, IERC20, IExchangeRouter} from "../src/FlashLiquidator.sol";
+
+interface IERC20Transfer {
+    function transfer(address to, uint256 amount) external returns (bool);
+    function transferFrom(address from, address to, uint256 amount) external returns (bool);
+}
+
+/// Stands in for the MM ExchangeRouter: the liquidation pulls `debt` of the
+/// debt token from the liquidator through the approved Router, itself here,
+/// and pays out `collateral` of the collateral token.
+contract MockExchangeRouter {
+    address public debtToken;
+    uint256 public debt;
+    address public collateralToken;
+    uint256 public collateral;
+
+    constructor(address debtToken_, uint256 debt_, address collateralToken_, uint256 collateral_) {
+        debtToken = debtToken_;
+        debt = debt_;
+        collateralToken = collateralToken_;
+        collateral = collateral_;
+    }
+
+    function router() external view returns (address) {
+        return address(this);
+    }
+
+    function executeLiquidationBatch(IExchangeRouter.LiquidationParams[] memory) external {
+        IERC20Transfer(debtToken).transferFrom(msg.sender, address(this), debt);
+        IERC20Transfer(collateralToken).transfer(msg.sender, collateral);
+    }
+}
+
+/// Runs the helper against a real flashloan pool and swap router on a fork:
+///
+/// FORK_URL=<rpc> FLASHLOAN_POOL=<aave v3 pool> SWAP_ROUTER=<uniswap v2 router> \
+/// DEBT_TOKEN=<e.g. USDC> COLLATERAL_TOKEN=<e.g. WETH> forge test --match-contract FlashLiquidatorForkTest
+contract FlashLiquidatorForkTest is Test {
+    FlashLiquidator helper;
+    address pool;
+    address swapRouter;
+    address debtToken;
+    address collateralToken;
+    address liquidator = address(0xB0B);
+
+    function setUp() public {
+        vm.createSelectFork(vm.envString("FORK_URL"));
+        pool = vm.envAddress("FLASHLOAN_POOL");
+        swapRouter = vm.envAddress("SWAP_ROUTER");
+        debtToken = vm.envAddress("DEBT_TOKEN");
+        collateralToken = vm.envAddress("COLLATERAL_TOKEN");
+        helper = new FlashLiquidator();
+    }
+
+    // a liquidation repaying `debt` and seizing collateral worth `bonusBps` more at the DEX price
+    function exchange(uint256 debt, uint256 bonusBps) internal returns (MockExchangeRouter) {
+        address[] memory path = new address[](2);
+        path[0] = collateralToken;
+        path[1] = debtToken;
+        (bool ok, bytes memory result) =
+            swapRouter.staticcall(abi.encodeWithSignature("getAmountsIn(uint256,address[])", debt * (10_000 + bonusBps) / 10_000, path));
+        require(ok, "quote failed");
+        uint256 collateral = abi.decode(result, (uint256[]))[0];
+        MockExchangeRouter router = new MockExchangeRouter(debtToken, debt, collateralToken, collateral);
+        deal(collateralToken, address(router), collateral);
+        return router;
+    }
+
+    function liquidate(MockExchangeRouter router, uint256 debt, uint256 minProfit) internal {
+        FlashLiquidator.LiquidationParams[] memory params = new FlashLiquidator.LiquidationParams[](1);
+        params[0] = FlashLiquidator.LiquidationParams(address(0xA11CE), 1);
+        FlashLiquidator.Loan[] memory loans = new FlashLiquidator.Loan[](1);
+        loans[0] = FlashLiquidator.Loan(debtToken, debt);
+        address[] memory tokens = new address[](2);
+        tokens[0] = debtToken;
+        tokens[1] = collateralToken;
+        FlashLiquidator.MinProfit[] memory minProfits = new FlashLiquidator.MinProfit[](1);
+        minProfits[0] = FlashLiquidator.MinProfit(debtToken, minProfit);
+        vm.prank(liquidator);
+        helper.flashLiquidate(pool, address(router), params, loans, swapRouter, tokens, minProfits);
+    }
+
+    function unit() internal view returns (uint256) {
+        (, bytes memory result) = debtToken.staticcall(abi.encodeWithSignature("decimals()"));
+        return 10 ** abi.decode(result, (uint8));
+    }
+
+    function test_liquidatesRepaysAndPaysOutTheProfit() public {
+        uint256 debt = 1_000 * unit();
+        // 5% bonus, well above the premium and swap fee
+        MockExchangeRouter router = exchange(debt, 500);
+        liquidate(router, debt, debt / 100);
+
+        assertGt(IERC20(debtToken).balanceOf(liquidator), debt / 100);
+        assertEq(IERC20(debtToken).balanceOf(address(helper)), 0);
+        assertEq(IERC20(collateralToken).balanceOf(address(helper)), 0);
+    }
+
+    function test_revertsBelowTheMinimumProfit() public {
+        uint256 debt = 1_000 * unit();
+        MockExchangeRouter router = exchange(debt, 500);
+        // more than the whole bonus
+        vm.expectPartialRevert(FlashLiquidator.InsufficientProfit.selector);
+        liquidate(router, debt, debt / 10);
+    }
+
+    function test_revertsWhenTheCollateralCantRepay() public {
+        uint256 debt = 1_000 * unit();
+        // seized collateral short of the debt, the pool's pull fails
+        MockExchangeRouter router = exchange(debt, 0);
+        vm.prank(address(router));
+        IERC20Transfer(collateralToken).transfer(address(1), IERC20(collateralToken).balanceOf(address(router)) / 2);
+        vm.expectRevert();
+        liquidate(router, debt, 0);
+    }
+
+    function test_rejectsCallbacksOutsideAFlashloan() public {
+        address[] memory assets = new address[](0);
+        uint256[] memory amounts = new uint256[](0);
+        vm.expectRevert(abi.encodeWithSelector(FlashLiquidator.UnexpectedLender.selector, address(this)));
+        helper.executeOperation(assets, amounts, amounts, address(helper), "");
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing::{info, warn};
+use alloy::transports::http::reqwest::{Client, Url};
+
+/// Alerts queued for the webhook before new ones are dropped.
+const ALERT_QUEUE_CAPACITY: usize = 256;
+/// Alerts listed in one post, the rest of a burst is only counted.
+const MAX_ALERTS_PER_POST: usize = 10;
+/// Consecutive failed submissions that raise an alert.
+const FAILURE_STREAK_ALERT: u64 = 5;
+
+static ALERTS: OnceLock<mpsc::Sender<String>> = OnceLock::new();
+static DROPPED_ALERTS: AtomicU64 = AtomicU64::new(0);
+static FAILURE_STREAK: AtomicU64 = AtomicU64::new(0);
+
+/// Start posting alerts to `webhook`, at most one post per `min_interval`.
+/// Alerts raised in between are combined into the next post.
+///
+/// The post is JSON with the message in both `content` (Discord) and `text`
+/// (Slack, or Telegram's sendMessage with `chat_id` in the webhook's query).
+pub fn init(webhook: Url, min_interval: Duration) {
+    let (sender, mut receiver) = mpsc::channel::<String>(ALERT_QUEUE_CAPACITY);
+    if ALERTS.set(sender).is_err() {
+        warn!("alerts already initialized");
+        return;
+    }
+    info!("alerting to {} at most every {:?}", webhook.host_str().unwrap_or_default(), min_interval);
+    tokio::spawn(async move {
+        let client = Client::new();
+        while let Some(first) = receiver.recv().await {
+            let mut messages = vec![first];
+            while let Ok(message) = receiver.try_recv() {
+                messages.push(message);
+            }
+            let more = messages.len().saturating_sub(MAX_ALERTS_PER_POST) as u64 + DROPPED_ALERTS.swap(0, Ordering::Relaxed);
+            messages.truncate(MAX_ALERTS_PER_POST);
+            let mut text = messages.join("\n");
+            if more > 0 {
+                text.push_str(&format!("\n...and {} more alerts", more));
+            }
+            let body = serde_json::json!({ "content": text, "text": text });
+            let res = client
+                .post(webhook.clone())
+                .header("Content-Type", "application/json")
+                .body(body.to_string())
+                .send()
+                .await;
+            match res {
+                Ok(res) if !res.status().is_success() => warn!("alert webhook answered {}", res.status()),
+                Ok(_) => {}
+                Err(e) => warn!("alert webhook failed: {}", e),
+            }
+            tokio::time::sleep(min_interval).await;
+        }
+    });
+}
+
+/// Queue an alert without waiting, a no-op unless [init] was called.
+pub fn send(message: String) {
+    let Some(sender) = ALERTS.get() else {
+        return;
+    };
+    if sender.try_send(message).is_err() {
+        DROPPED_ALERTS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Count a failed submission, alerting once the streak reaches the threshold.
+pub fn submission_failed(error: &anyhow::Error) {
+    let streak = FAILURE_STREAK.fetch_add(1, Ordering::Relaxed) + 1;
+    if streak == FAILURE_STREAK_ALERT {
+        send(format!("{} liquidation submissions failed in a row, last error: {}", streak, error));
+    }
+}
+
+/// End a failed submission streak.
+pub fn submission_succeeded() {
+    FAILURE_STREAK.store(0, Ordering::Relaxed);
+}
@@ -0,0 +1,80 @@
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// How often a running backfill logs its progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Error messages providers answer eth_getLogs with when a range holds too
+/// many results or spans too many blocks.
+const RANGE_LIMIT_ERRORS: [&str; 7] = [
+    "too many results",
+    "returned more than",
+    "range is too large",
+    "range too large",
+    "exceed maximum block range",
+    "block range limit",
+    "response size exceeded",
+];
+
+/// Whether `error` is a provider refusing an eth_getLogs range as too large,
+/// which a smaller range gets past.
+pub fn is_range_limit_error(error: &impl std::fmt::Display) -> bool {
+    let message = error.to_string().to_lowercase();
+    RANGE_LIMIT_ERRORS.iter().any(|pattern| message.contains(pattern))
+}
+
+/// Splits a block range into eth_getLogs chunks of adaptive size: halved when
+/// the provider refuses a chunk as too large, doubled back towards the
+/// initial size after each chunk that succeeds.
+pub struct LogChunker {
+    max_size: u64,
+    size: u64,
+    from_block: u64,
+    to_block: u64,
+    last_progress: Instant,
+}
+
+impl LogChunker {
+    /// Chunk `from_block..=to_block`, starting with `chunk_size` blocks per call.
+    pub fn new(from_block: u64, to_block: u64, chunk_size: u64) -> Self {
+        let chunk_size = chunk_size.max(1);
+        Self {
+            max_size: chunk_size,
+            size: chunk_size,
+            from_block,
+            to_block,
+            last_progress: Instant::now(),
+        }
+    }
+
+    /// The next chunk to fetch, None once the range is done.
+    pub fn next_range(&self) -> Option<(u64, u64)> {
+        if self.from_block > self.to_block {
+            return None;
+        }
+        Some((self.from_block, std::cmp::min(self.from_block + self.size - 1, self.to_block)))
+    }
+
+    /// Move past the chunk ending at `end_block` and grow the chunk size,
+    /// logging progress every [PROGRESS_INTERVAL].
+    pub fn advance(&mut self, end_block: u64) {
+        self.from_block = end_block + 1;
+        self.size = std::cmp::min(self.size.saturating_mul(2), self.max_size);
+        if self.from_block <= self.to_block && self.last_progress.elapsed() >= PROGRESS_INTERVAL {
+            self.last_progress = Instant::now();
+            info!("log backfill at block {} of {}, {} blocks per call", self.from_block, self.to_block, self.size);
+        }
+    }
+
+    /// Halve the chunk size after the provider refused the current chunk as
+    /// too large. Returns false when the chunk is already a single block, so
+    /// retrying smaller can't help.
+    pub fn shrink(&mut self) -> bool {
+        if self.size == 1 {
+            return false;
+        }
+        self.size /= 2;
+        warn!("log range limited by provider, retrying with {} blocks per call", self.size);
+        true
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::Service;
+use tracing::info;
+use alloy::{
+    primitives::{Address, U256},
+    rpc::json_rpc::{Request, RequestPacket, Response, ResponsePacket, ResponsePayload, SerializedRequest},
+    transports::{TransportError, TransportErrorKind, TransportFut},
+};
+
+use crate::strategies::mm_strategy::UnderwaterPosition;
+
+/// Block tags a pinned request has replaced by the pinned block.
+const BLOCK_TAGS: [&str; 4] = ["latest", "pending", "safe", "finalized"];
+
+/// Position of the block parameter of the methods that take one.
+fn block_param_index(method: &str) -> Option<usize> {
+    match method {
+        "eth_getBlockByNumber" => Some(0),
+        "eth_call" | "eth_estimateGas" | "eth_getBalance" | "eth_getCode" | "eth_getTransactionCount" => Some(1),
+        "eth_getStorageAt" => Some(2),
+        _ => None,
+    }
+}
+
+/// Point a request reading the latest state at `block` instead.
+fn pin_request(request: SerializedRequest, block: u64) -> Result<SerializedRequest, TransportError> {
+    let Some(index) = block_param_index(request.method()) else {
+        return Ok(request);
+    };
+    let mut params: Vec<serde_json::Value> = match request.params() {
+        Some(params) => serde_json::from_str(params.get()).map_err(TransportErrorKind::custom)?,
+        None => Vec::new(),
+    };
+    let pinned = serde_json::Value::String(format!("0x{:x}", block));
+    match params.get_mut(index) {
+        Some(param) if param.as_str().map_or(false, |tag| BLOCK_TAGS.contains(&tag)) => *param = pinned,
+        None if params.len() == index => params.push(pinned),
+        // already reading a given block
+        _ => return Ok(request),
+    }
+    Request::new(request.method().to_string(), request.id().clone(), params)
+        .serialize()
+        .map_err(TransportErrorKind::custom)
+}
+
+/// A transport that makes `inner` look like a chain whose head is the pinned
+/// block: `eth_blockNumber` answers it, and reads of the latest state are
+/// made at it instead. Reading old blocks needs an archive node. Clones share
+/// the pinned block.
+#[derive(Clone)]
+pub struct PinnedBlock<S> {
+    inner: S,
+    block: Arc<AtomicU64>,
+}
+
+impl<S> PinnedBlock<S> {
+    pub fn new(inner: S, block: u64) -> Self {
+        Self { inner, block: Arc::new(AtomicU64::new(block)) }
+    }
+
+    /// Move the head to `block`.
+    pub fn pin(&self, block: u64) {
+        self.block.store(block, Ordering::Relaxed);
+    }
+}
+
+impl<S> Service<RequestPacket> for PinnedBlock<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError, Future = TransportFut<'static>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let block = self.block.load(Ordering::Relaxed);
+        let request = match request {
+            RequestPacket::Single(request) if request.method() == "eth_blockNumber" => {
+                let id = request.id().clone();
+                return Box::pin(async move {
+                    let number = serde_json::value::to_raw_value(&format!("0x{:x}", block)).map_err(TransportErrorKind::custom)?;
+                    Ok(ResponsePacket::Single(Response { id, payload: ResponsePayload::Success(number) }))
+                });
+            }
+            RequestPacket::Single(request) => pin_request(request, block).map(RequestPacket::Single),
+            RequestPacket::Batch(batch) => batch
+                .into_iter()
+                .map(|request| pin_request(request, block))
+                .collect::<Result<Vec<_>, _>>()
+                .map(RequestPacket::Batch),
+        };
+        match request {
+            Ok(request) => self.inner.call(request),
+            Err(e) => Box::pin(async move { Err(e) }),
+        }
+    }
+}
+
+/// Liquidations a backtest would have sent, written as CSV with one row per
+/// scanned block. A position underwater over several blocks is captured
+/// once, at the first block it could have been liquidated.
+pub struct BacktestReport {
+    file: BufWriter<File>,
+    captured: HashSet<(Address, U256)>,
+    profit: U256,
+}
+
+impl BacktestReport {
+    pub fn create(path: &str) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "block,underwater,captured,profit,cumulative_profit")?;
+        info!("writing backtest report to {}", path);
+        Ok(Self { file, captured: HashSet::new(), profit: U256::ZERO })
+    }
+
+    /// Add the positions that would have been liquidated at `block`, profits
+    /// in 18-decimal units of the accounting token.
+    pub fn record(&mut self, block: u64, underwaters: &[UnderwaterPosition]) -> Result<()> {
+        let mut captured = 0;
+        let mut profit = U256::ZERO;
+        for underwater in underwaters {
+            if self.captured.insert((underwater.account, underwater.position_id)) {
+                captured += 1;
+                profit = profit.saturating_add(underwater.common_profit);
+            }
+        }
+        self.profit = self.profit.saturating_add(profit);
+        writeln!(self.file, "{},{},{},{},{}", block, underwaters.len(), captured, profit, self.profit)?;
+        if captured > 0 {
+            info!("block {}: {} liquidations captured, profit {}", block, captured, profit);
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.file.flush()?;
+        info!(
+            "backtest captured {} liquidations, theoretical profit {}",
+            self.captured.len(), self.profit
+        );
+        Ok(())
+    }
+}
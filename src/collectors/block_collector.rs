@@ -0,0 +1,261 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::{debug, info, warn};
+use alloy::{
+    contract as alloy_contract,
+    network::Ethereum,
+    primitives::B256,
+    rpc::types::BlockTransactionsKind,
+};
+
+/// Delay before resubscribing after the block subscription drops.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Recent block hashes kept to detect reorgs, deeper ones are reported from
+/// the oldest block still known.
+pub const REORG_DEPTH: usize = 64;
+
+/// A collector that subscribes to new block headers, and generates a stream
+/// of [events](NewBlock) which contain the block number, hash and timestamp.
+/// A header whose ancestry disagrees with the blocks already emitted is
+/// preceded by a [Reorg]. Blocks skipped between two headers, e.g. while the
+/// subscription reconnects, are fetched first, up to [REORG_DEPTH] of them, and
+/// only checked for reorgs: one [NewBlock] for the header that follows them
+/// covers them, so a gap doesn't set off a tick per missed block. A header
+/// already emitted, e.g. repeated after resubscribing, is ignored. The
+/// subscription needs a pubsub (ws or ipc) provider.
+pub struct BlockCollector<T, P> {
+    provider: Arc<P>,
+    _transport: ::core::marker::PhantomData<T>,
+}
+
+/// A new block event, containing the block number, hash and timestamp.
+#[derive(Debug, Clone)]
+pub struct NewBlock {
+    pub number: u64,
+    pub hash: B256,
+    pub timestamp: u64,
+}
+
+/// Blocks from `from_block` on were replaced by a reorg.
+#[derive(Debug, Clone)]
+pub struct Reorg {
+    pub from_block: u64,
+}
+
+/// An event of the [BlockCollector].
+#[derive(Debug, Clone)]
+pub enum BlockEvent {
+    NewBlock(NewBlock),
+    Reorg(Reorg),
+}
+
+impl<T, P> BlockCollector<T, P> {
+    pub fn new(provider: Arc<P>) -> Self {
+        Self { provider, _transport: ::core::marker::PhantomData }
+    }
+}
+
+/// Hashes of the last [REORG_DEPTH] blocks emitted, to tell a new block from a
+/// reorg.
+#[derive(Debug, Default)]
+struct RecentBlocks {
+    hashes: BTreeMap<u64, B256>,
+}
+
+impl RecentBlocks {
+    fn last(&self) -> Option<u64> {
+        self.hashes.keys().next_back().copied()
+    }
+
+    // whether the block was already emitted, e.g. a header repeated by a resubscription
+    fn contains(&self, number: u64, hash: B256) -> bool {
+        self.hashes.get(&number) == Some(&hash)
+    }
+
+    // the first block, up to `number`, of the chain ending in block `number` with
+    // parent `parent_hash` that isn't one already emitted, walking back through
+    // `parent_of`, the parent hash of a block hash or None if it can't be fetched
+    async fn fork_block<F, Fut>(&self, number: u64, mut parent_hash: B256, parent_of: F) -> u64
+    where
+        F: Fn(B256) -> Fut,
+        Fut: Future<Output = Option<B256>>,
+    {
+        let mut fork_block = number;
+        while let Some(known) = fork_block.checked_sub(1).and_then(|number| self.hashes.get(&number)) {
+            if *known == parent_hash {
+                break;
+            }
+            fork_block -= 1;
+            match parent_of(parent_hash).await {
+                Some(hash) => parent_hash = hash,
+                None => break,
+            }
+        }
+        fork_block
+    }
+
+    // record block `number` as the new head of the chain from `fork_block` on,
+    // returning the reorg if that replaces blocks already emitted
+    fn insert(&mut self, fork_block: u64, number: u64, hash: B256) -> Option<Reorg> {
+        let reorg = self.hashes.range(fork_block..).next().is_some().then_some(Reorg { from_block: fork_block });
+        self.hashes.split_off(&fork_block);
+        self.hashes.insert(number, hash);
+        while self.hashes.len() > REORG_DEPTH {
+            self.hashes.pop_first();
+        }
+        reorg
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [BlockCollector](BlockCollector).
+/// The subscription is renewed whenever it fails or its stream ends.
+#[async_trait]
+impl<T, P> Collector<BlockEvent> for BlockCollector<T, P>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum> + 'static,
+{
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, BlockEvent>> {
+        let stream = async_stream::stream! {
+            let mut recent = RecentBlocks::default();
+            let provider = &self.provider;
+            loop {
+                match self.provider.subscribe_blocks().await {
+                    Ok(subscription) => {
+                        info!("subscribed to new blocks");
+                        let mut headers = subscription.into_stream();
+                        while let Some(header) = headers.next().await {
+                            if recent.contains(header.number, header.hash) {
+                                debug!("block {} {:?} already seen", header.number, header.hash);
+                                continue;
+                            }
+                            let mut missed = Vec::new();
+                            if let Some(last) = recent.last() {
+                                if header.number > last + 1 {
+                                    let from_block = std::cmp::max(last + 1, header.number.saturating_sub(REORG_DEPTH as u64));
+                                    if from_block > last + 1 {
+                                        warn!("skipping missed blocks {} to {}, deeper than the reorg depth", last + 1, from_block - 1);
+                                    }
+                                    info!(
+                                        "backfilling {} missed blocks {} to {} before block {}",
+                                        header.number - from_block, from_block, header.number - 1, header.number
+                                    );
+                                    for number in from_block..header.number {
+                                        match self.provider.get_block_by_number(number.into(), BlockTransactionsKind::Hashes).await {
+                                            Ok(Some(block)) => missed.push(block.header),
+                                            Ok(None) => {
+                                                warn!("missed block {} not found, resuming at {}", number, header.number);
+                                                break;
+                                            }
+                                            Err(e) => {
+                                                warn!("failed to backfill missed block {}, resuming at {}: {}", number, header.number, e);
+                                                break;
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            let head = header.number;
+                            missed.push(header);
+
+                            for header in missed {
+                                // walk the new chain back until it meets a block we emitted
+                                let fork_block = recent
+                                    .fork_block(header.number, header.parent_hash, |hash| async move {
+                                        match provider.get_block_by_hash(hash, BlockTransactionsKind::Hashes).await {
+                                            Ok(block) => block.map(|block| block.header.parent_hash),
+                                            Err(e) => {
+                                                warn!("failed to walk back reorged block {:?}: {}", hash, e);
+                                                None
+                                            }
+                                        }
+                                    })
+                                    .await;
+                                if let Some(reorg) = recent.insert(fork_block, header.number, header.hash) {
+                                    warn!("reorg from block {} at new head {} {:?}", fork_block, header.number, header.hash);
+                                    yield BlockEvent::Reorg(reorg);
+                                }
+
+                                if header.number == head {
+                                    yield BlockEvent::NewBlock(NewBlock {
+                                        number: header.number,
+                                        hash: header.hash,
+                                        timestamp: header.timestamp,
+                                    });
+                                }
+                            }
+                        }
+                        warn!("block subscription dropped, resubscribing");
+                    }
+                    Err(e) => warn!("block subscription failed, retrying: {}", e),
+                }
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn hash(number: u64, fork: u8) -> B256 {
+        let mut hash = B256::with_last_byte(number as u8);
+        hash[0] = fork;
+        hash
+    }
+
+    // blocks 1 to 5 of the canonical chain, fork 0
+    fn emitted() -> RecentBlocks {
+        let mut recent = RecentBlocks::default();
+        for number in 1..=5 {
+            assert!(recent.insert(number, number, hash(number, 0)).is_none());
+        }
+        recent
+    }
+
+    #[tokio::test]
+    async fn walks_back_to_the_fork_block() {
+        let mut recent = emitted();
+        // fork 1 replaces blocks 4 and 5 and builds block 6 on them
+        let parents = HashMap::from([(hash(5, 1), hash(4, 1)), (hash(4, 1), hash(3, 0))]);
+        let parent_of = |hash: B256| {
+            let parent = parents.get(&hash).copied();
+            async move { parent }
+        };
+
+        let fork_block = recent.fork_block(6, hash(5, 1), parent_of).await;
+        assert_eq!(fork_block, 4);
+        let reorg = recent.insert(fork_block, 6, hash(6, 1)).unwrap();
+        assert_eq!(reorg.from_block, 4);
+        assert_eq!(recent.hashes.keys().copied().collect::<Vec<_>>(), vec![1, 2, 3, 6]);
+    }
+
+    #[tokio::test]
+    async fn a_block_on_the_head_is_no_reorg() {
+        let mut recent = emitted();
+        let fork_block = recent.fork_block(6, hash(5, 0), |_| async { None }).await;
+        assert_eq!(fork_block, 6);
+        assert!(recent.insert(fork_block, 6, hash(6, 0)).is_none());
+    }
+
+    #[tokio::test]
+    async fn a_replaced_head_is_a_reorg_and_a_repeated_one_is_not() {
+        let mut recent = emitted();
+        assert!(recent.contains(5, hash(5, 0)));
+        assert!(!recent.contains(5, hash(5, 1)));
+
+        let fork_block = recent.fork_block(5, hash(4, 0), |_| async { None }).await;
+        assert_eq!(recent.insert(fork_block, 5, hash(5, 1)).unwrap().from_block, 5);
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use alloy::primitives::Address;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+/// An operator command sent through the control port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Liquidate the permanently skipped account again, or every one of them
+    /// if none is given, with its attempts reset.
+    ClearSkippedAccounts(Option<Address>),
+}
+
+/// A collector that feeds operator commands to the strategies as events.
+pub struct ControlCollector {
+    commands: broadcast::Sender<ControlCommand>,
+}
+
+impl ControlCollector {
+    pub fn new(commands: broadcast::Sender<ControlCommand>) -> Self {
+        Self { commands }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [ControlCollector](ControlCollector).
+#[async_trait]
+impl Collector<ControlCommand> for ControlCollector {
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, ControlCommand>> {
+        let mut receiver = self.commands.subscribe();
+        let stream = async_stream::stream! {
+            loop {
+                match receiver.recv().await {
+                    Ok(command) => yield command,
+                    Err(RecvError::Lagged(skipped)) => warn!("dropped {} control commands", skipped),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::{info, warn};
+use alloy::{
+    contract as alloy_contract,
+    network::Ethereum,
+    rpc::types::{Filter, Log},
+};
+
+use crate::backfill::{is_range_limit_error, LogChunker};
+
+/// Delay before resubscribing after the log subscription drops or the backfill fails.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// A collector that replays the logs matching `filter` from `from_block` to
+/// the chain head with chunked eth_getLogs, then streams new ones from a log
+/// subscription. The subscription needs a pubsub (ws or ipc) provider.
+pub struct LogCollector<T, P> {
+    provider: Arc<P>,
+    filter: Filter,
+    from_block: u64,
+    chunk_size: u64,
+    _transport: ::core::marker::PhantomData<T>,
+}
+
+impl<T, P> LogCollector<T, P> {
+    /// `chunk_size` is the initial number of blocks per eth_getLogs call,
+    /// halved while the provider refuses a range as too large.
+    pub fn new(provider: Arc<P>, filter: Filter, from_block: u64, chunk_size: u64) -> Self {
+        Self {
+            provider,
+            filter,
+            from_block,
+            chunk_size: chunk_size.max(1),
+            _transport: ::core::marker::PhantomData,
+        }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [LogCollector](LogCollector).
+/// The subscription is opened before each backfill so no log falls between
+/// the two, logs at or before the last emitted (block, log index) are dropped.
+/// After a dropped subscription the backfill resumes from the last emitted block.
+#[async_trait]
+impl<T, P> Collector<Log> for LogCollector<T, P>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum> + 'static,
+{
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, Log>> {
+        let stream = async_stream::stream! {
+            let mut next_block = self.from_block;
+            let mut last_emitted: Option<(u64, u64)> = None;
+            'subscribe: loop {
+                let subscription = match self.provider.subscribe_logs(&self.filter).await {
+                    Ok(subscription) => subscription,
+                    Err(e) => {
+                        warn!("log subscription failed, retrying: {}", e);
+                        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                        continue;
+                    }
+                };
+
+                let head = match self.provider.get_block_number().await {
+                    Ok(head) => head,
+                    Err(e) => {
+                        warn!("failed to get head for log backfill, retrying: {}", e);
+                        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                        continue;
+                    }
+                };
+                if next_block <= head {
+                    info!("backfilling logs of {} blocks from block {} to {}", head - next_block + 1, next_block, head);
+                }
+                let mut chunker = LogChunker::new(next_block, head, self.chunk_size);
+                while let Some((start_block, end_block)) = chunker.next_range() {
+                    let filter = self.filter.clone().from_block(start_block).to_block(end_block);
+                    let logs = match self.provider.get_logs(&filter).await {
+                        Ok(logs) => logs,
+                        Err(e) if is_range_limit_error(&e) && chunker.shrink() => continue,
+                        Err(e) => {
+                            warn!("log backfill of blocks {}..={} failed, retrying: {}", start_block, end_block, e);
+                            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                            continue 'subscribe;
+                        }
+                    };
+                    for log in logs {
+                        if let Some(key) = log_key(&log) {
+                            if last_emitted.map_or(false, |last| key <= last) {
+                                continue;
+                            }
+                            last_emitted = Some(key);
+                        }
+                        yield log;
+                    }
+                    next_block = end_block + 1;
+                    chunker.advance(end_block);
+                }
+
+                info!("subscribed to logs");
+                let mut logs = subscription.into_stream();
+                while let Some(log) = logs.next().await {
+                    if log.removed {
+                        // let the replacing chain's logs through the dedup
+                        warn!("ignoring log removed by reorg at block {:?}", log.block_number);
+                        if let Some((block, log_index)) = log_key(&log) {
+                            let before = match log_index.checked_sub(1) {
+                                Some(log_index) => (block, log_index),
+                                None => (block.saturating_sub(1), u64::MAX),
+                            };
+                            last_emitted = last_emitted.map(|last| std::cmp::min(last, before));
+                            next_block = std::cmp::min(next_block, block);
+                        }
+                        continue;
+                    }
+                    if let Some(key) = log_key(&log) {
+                        if last_emitted.map_or(false, |last| key <= last) {
+                            continue;
+                        }
+                        last_emitted = Some(key);
+                        next_block = key.0;
+                    }
+                    yield log;
+                }
+                warn!("log subscription dropped, resubscribing");
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Chain position of a mined log.
+fn log_key(log: &Log) -> Option<(u64, u64)> {
+    Some((log.block_number?, log.log_index?))
+}
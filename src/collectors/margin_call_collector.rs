@@ -0,0 +1,53 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use alloy::primitives::{Address, FixedBytes, U256};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+/// A position whose margin level fell within --margin-call-buffer-bps above the
+/// liquidation threshold, an early warning before it can be liquidated.
+#[derive(Debug, Clone)]
+pub struct MarginCall {
+    /// Name of the deployment the position belongs to.
+    pub deployment: String,
+    /// Key of the position, hashed from its account and id.
+    pub position: FixedBytes<32>,
+    pub account: Address,
+    pub position_id: U256,
+    /// Margin level of the position, in ray.
+    pub health: U256,
+    /// Margin level the position becomes liquidatable below, in ray.
+    pub threshold: U256,
+}
+
+/// A collector that feeds the margin calls raised by the strategies' scans
+/// back to them as events.
+pub struct MarginCallCollector {
+    margin_calls: broadcast::Sender<MarginCall>,
+}
+
+impl MarginCallCollector {
+    pub fn new(margin_calls: broadcast::Sender<MarginCall>) -> Self {
+        Self { margin_calls }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [MarginCallCollector](MarginCallCollector).
+#[async_trait]
+impl Collector<MarginCall> for MarginCallCollector {
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, MarginCall>> {
+        let mut receiver = self.margin_calls.subscribe();
+        let stream = async_stream::stream! {
+            loop {
+                match receiver.recv().await {
+                    Ok(margin_call) => yield margin_call,
+                    Err(RecvError::Lagged(skipped)) => warn!("dropped {} margin call events", skipped),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
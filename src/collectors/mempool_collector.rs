@@ -0,0 +1,87 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time::Duration;
+use tracing::{info, warn};
+use alloy::{
+    contract as alloy_contract,
+    network::Ethereum,
+    rpc::types::Transaction,
+};
+
+/// Delay before resubscribing after the pending tx subscription drops.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Decides which pending txs are worth sending to the engine.
+pub type PendingTxFilter = Box<dyn Fn(&Transaction) -> bool + Send + Sync>;
+
+/// A collector that subscribes to full pending transactions and generates a
+/// stream of the ones accepted by its filter. The subscription needs a pubsub
+/// (ws or ipc) provider.
+///
+/// At most `max_txs_per_sec` txs are emitted per second, the rest of a burst is
+/// dropped and counted rather than queued, so the engine channel stays bounded.
+pub struct MempoolCollector<T, P> {
+    provider: Arc<P>,
+    filter: PendingTxFilter,
+    pub max_txs_per_sec: u64,
+    _transport: ::core::marker::PhantomData<T>,
+}
+
+impl<T, P> MempoolCollector<T, P> {
+    pub fn new(provider: Arc<P>, filter: PendingTxFilter, max_txs_per_sec: u64) -> Self {
+        Self { provider, filter, max_txs_per_sec, _transport: ::core::marker::PhantomData }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [MempoolCollector](MempoolCollector).
+/// The subscription is renewed whenever it fails or its stream ends.
+#[async_trait]
+impl<T, P> Collector<Transaction> for MempoolCollector<T, P>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum> + 'static,
+{
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, Transaction>> {
+        let stream = async_stream::stream! {
+            let mut window_start = Instant::now();
+            let mut emitted: u64 = 0;
+            let mut dropped: u64 = 0;
+            loop {
+                match self.provider.subscribe_full_pending_transactions().await {
+                    Ok(subscription) => {
+                        info!("subscribed to pending transactions");
+                        let mut txs = subscription.into_stream();
+                        while let Some(tx) = txs.next().await {
+                            if !(self.filter)(&tx) {
+                                continue;
+                            }
+                            if window_start.elapsed() >= Duration::from_secs(1) {
+                                if dropped > 0 {
+                                    warn!("dropped {} pending txs over the {} per second cap", dropped, self.max_txs_per_sec);
+                                }
+                                window_start = Instant::now();
+                                emitted = 0;
+                                dropped = 0;
+                            }
+                            if self.max_txs_per_sec > 0 && emitted >= self.max_txs_per_sec {
+                                dropped += 1;
+                                continue;
+                            }
+                            emitted += 1;
+                            yield tx;
+                        }
+                        warn!("pending transaction subscription dropped, resubscribing");
+                    }
+                    Err(e) => warn!("pending transaction subscription failed, retrying: {}", e),
+                }
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
@@ -1 +1,11 @@
+pub mod block_collector;
+pub mod control_collector;
+pub mod log_collector;
+pub mod margin_call_collector;
+pub mod mempool_collector;
+pub mod oracle_collector;
+pub mod outcome_collector;
+pub mod pool_collector;
+pub mod supervised_collector;
 pub mod time_collector;
+pub mod throttle_collector;
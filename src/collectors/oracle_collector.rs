@@ -0,0 +1,97 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use bindings_mm::reader::Reader;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::warn;
+use alloy::{
+    contract as alloy_contract,
+    network::Ethereum,
+    primitives::{Address, U256},
+};
+
+/// Pools read per getPoolsInfo call.
+const POLL_CHUNK_SIZE: u64 = 100;
+
+/// A collector that polls the protocol's pool prices every `poll_secs` and
+/// generates a stream of [events](PriceUpdate) for every meme token whose
+/// price moved since the previous poll.
+pub struct OracleCollector<T, P> {
+    provider: Arc<P>,
+    reader: Address,
+    data_store: Address,
+    pub poll_secs: u64,
+    _transport: ::core::marker::PhantomData<T>,
+}
+
+/// A price move, containing the meme token, its new price and the block it was read at.
+#[derive(Debug, Clone)]
+pub struct PriceUpdate {
+    pub token: Address,
+    pub price: U256,
+    pub block: u64,
+}
+
+impl<T, P> OracleCollector<T, P>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum> + 'static,
+{
+    pub fn new(provider: Arc<P>, reader: Address, data_store: Address, poll_secs: u64) -> Self {
+        Self { provider, reader, data_store, poll_secs, _transport: ::core::marker::PhantomData }
+    }
+
+    /// Current price of every pool's meme token.
+    async fn read_prices(&self) -> Result<(u64, Vec<(Address, U256)>)> {
+        let reader = Reader::new(self.reader, self.provider.clone());
+        let block = self.provider.get_block_number().await?;
+        let pools_count: u64 = reader.getPoolsCount(self.data_store).call().await?._0.try_into()?;
+        let mut prices = Vec::new();
+        for start in (0..pools_count).step_by(POLL_CHUNK_SIZE as usize) {
+            let end = std::cmp::min(start + POLL_CHUNK_SIZE, pools_count);
+            let pools = reader
+                .getPoolsInfo_1(self.data_store, U256::from(start), U256::from(end))
+                .call()
+                .await?
+                ._0;
+            prices.extend(pools.iter().map(|pool| (pool.assets[1].token, pool.price)));
+        }
+        Ok((block, prices))
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [OracleCollector](OracleCollector).
+#[async_trait]
+impl<T, P> Collector<PriceUpdate> for OracleCollector<T, P>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum> + 'static,
+{
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, PriceUpdate>> {
+        let stream = async_stream::stream! {
+            let mut last_prices: HashMap<Address, U256> = HashMap::new();
+            let mut interval = tokio::time::interval(Duration::from_secs(self.poll_secs));
+            loop {
+                interval.tick().await;
+                let (block, prices) = match self.read_prices().await {
+                    Ok(prices) => prices,
+                    Err(e) => {
+                        warn!("Error polling pool prices: {}", e);
+                        continue;
+                    }
+                };
+                for (token, price) in prices {
+                    // the first poll only seeds the cache, the strategy already has these prices
+                    match last_prices.insert(token, price) {
+                        Some(last_price) if last_price != price => yield PriceUpdate { token, price, block },
+                        _ => {}
+                    }
+                }
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
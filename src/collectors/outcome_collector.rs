@@ -0,0 +1,38 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use crate::executors::protect_executor::LiquidationOutcome;
+
+/// A collector that feeds the liquidation outcomes reported by the executor
+/// back to the strategies as events.
+pub struct OutcomeCollector {
+    outcomes: broadcast::Sender<LiquidationOutcome>,
+}
+
+impl OutcomeCollector {
+    pub fn new(outcomes: broadcast::Sender<LiquidationOutcome>) -> Self {
+        Self { outcomes }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [OutcomeCollector](OutcomeCollector).
+#[async_trait]
+impl Collector<LiquidationOutcome> for OutcomeCollector {
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, LiquidationOutcome>> {
+        let mut receiver = self.outcomes.subscribe();
+        let stream = async_stream::stream! {
+            loop {
+                match receiver.recv().await {
+                    Ok(outcome) => yield outcome,
+                    Err(RecvError::Lagged(skipped)) => warn!("dropped {} liquidation outcomes", skipped),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use alloy::primitives::{Address, FixedBytes};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+/// A pool a strategy's pool update found for the first time.
+#[derive(Debug, Clone)]
+pub struct NewPool {
+    /// Name of the deployment the pool belongs to.
+    pub deployment: String,
+    /// Key of the pool, hashed from its token pair.
+    pub pool: FixedBytes<32>,
+    pub base_token: Address,
+    pub base_symbol: String,
+    pub meme_token: Address,
+    pub meme_symbol: String,
+}
+
+/// A collector that feeds the pools discovered by the strategies back to
+/// them as events, so a new pool's positions are checked right away.
+pub struct PoolCollector {
+    new_pools: broadcast::Sender<NewPool>,
+}
+
+impl PoolCollector {
+    pub fn new(new_pools: broadcast::Sender<NewPool>) -> Self {
+        Self { new_pools }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [PoolCollector](PoolCollector).
+#[async_trait]
+impl Collector<NewPool> for PoolCollector {
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, NewPool>> {
+        let mut receiver = self.new_pools.subscribe();
+        let stream = async_stream::stream! {
+            loop {
+                match receiver.recv().await {
+                    Ok(new_pool) => yield new_pool,
+                    Err(RecvError::Lagged(skipped)) => warn!("dropped {} new pool events", skipped),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use futures::{FutureExt, StreamExt};
+use std::panic::AssertUnwindSafe;
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::{alerts, shutdown};
+
+/// First wait before restarting a failed collector, doubled on every restart in a row.
+const RESTART_BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+/// Longest wait before restarting a failed collector.
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// A collector that wraps another collector and restarts its event stream
+/// with backoff when it fails to start, ends or panics. Without it the
+/// engine's collector task just exits and that data source goes dark. After
+/// `max_restarts` restarts in a row without an event the process exits, so an
+/// orchestrator can restart it.
+pub struct SupervisedCollector<E> {
+    name: &'static str,
+    inner: Box<dyn Collector<E>>,
+    max_restarts: u32,
+}
+
+impl<E> SupervisedCollector<E> {
+    pub fn new(name: &'static str, inner: Box<dyn Collector<E>>, max_restarts: u32) -> Self {
+        Self { name, inner, max_restarts }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [SupervisedCollector](SupervisedCollector).
+#[async_trait]
+impl<E: Send + Sync + 'static> Collector<E> for SupervisedCollector<E> {
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, E>> {
+        let stream = async_stream::stream! {
+            let mut restarts: u32 = 0;
+            loop {
+                let failure = match AssertUnwindSafe(self.inner.get_event_stream()).catch_unwind().await {
+                    Ok(Ok(mut events)) => loop {
+                        match AssertUnwindSafe(events.next()).catch_unwind().await {
+                            Ok(Some(event)) => {
+                                restarts = 0;
+                                yield event;
+                            }
+                            Ok(None) => break "its stream ended".to_string(),
+                            Err(_) => break "it panicked".to_string(),
+                        }
+                    },
+                    Ok(Err(e)) => format!("it failed to start: {}", e),
+                    Err(_) => "it panicked on start".to_string(),
+                };
+                if shutdown::requested() {
+                    break;
+                }
+                restarts += 1;
+                if restarts > self.max_restarts {
+                    error!("{} collector failed {} times in a row, last because {}, exiting", self.name, restarts, failure);
+                    alerts::send(format!("{} collector failed {} times in a row, exiting: {}", self.name, restarts, failure));
+                    // give the alert a moment to go out
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                    std::process::exit(1);
+                }
+                let backoff = std::cmp::min(RESTART_BACKOFF_BASE * 2u32.saturating_pow(restarts - 1), RESTART_BACKOFF_MAX);
+                warn!("{} collector stopped because {}, restarting in {:?} (restart #{})", self.name, failure, backoff, restarts);
+                alerts::send(format!("{} collector stopped, restarting in {:?}: {}", self.name, backoff, failure));
+                tokio::time::sleep(backoff).await;
+                info!("restarting {} collector", self.name);
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::time::Instant;
+use tokio::time::Duration;
+use tracing::info;
+
+/// A collector that wraps another collector and caps the rate at which its
+/// events reach the engine. The inner stream is only polled once the next
+/// event may be emitted, so bursts apply backpressure instead of piling up.
+pub struct ThrottleCollector<E> {
+    inner: Box<dyn Collector<E>>,
+    pub max_events_per_sec: u64,
+}
+
+impl<E> ThrottleCollector<E> {
+    pub fn new(inner: Box<dyn Collector<E>>, max_events_per_sec: u64) -> Self {
+        Self { inner, max_events_per_sec }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [ThrottleCollector](ThrottleCollector).
+#[async_trait]
+impl<E: Send + Sync + 'static> Collector<E> for ThrottleCollector<E> {
+    async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, E>> {
+        let mut inner = self.inner.get_event_stream().await?;
+        if self.max_events_per_sec == 0 {
+            return Ok(inner);
+        }
+
+        let interval = Duration::from_secs(1) / self.max_events_per_sec as u32;
+        let stream = async_stream::stream! {
+            let mut last_emitted: Option<Instant> = None;
+            let mut throttled = false;
+            while let Some(event) = inner.next().await {
+                let wait = last_emitted
+                    .map(|last| interval.saturating_sub(last.elapsed()))
+                    .unwrap_or_default();
+                if !wait.is_zero() {
+                    if !throttled {
+                        info!("event backpressure engaged at {} events/sec", self.max_events_per_sec);
+                        throttled = true;
+                    }
+                    tokio::time::sleep(wait).await;
+                } else if throttled {
+                    info!("event backpressure released");
+                    throttled = false;
+                }
+                last_emitted = Some(Instant::now());
+                yield event;
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+}
@@ -1,44 +1,75 @@
 use anyhow::Result;
 use artemis_core::types::{Collector, CollectorStream};
 use async_trait::async_trait;
-use futures::StreamExt;
 use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
 use tokio::time::Duration;
-use tokio_stream::wrappers::IntervalStream;
+use tracing::info;
 
-/// A collector that listens for new blocks, and generates a stream of
-/// [events](NewBlock) which contain the block number and hash.
+/// Shortest interval between ticks, lower intervals are raised to it so a
+/// bad setting can't busy-loop the strategy.
+pub const MIN_POLL_SECS: u64 = 1;
+
+/// A collector that generates a stream of [ticks](NewTick) every `poll_secs`,
+/// the first one right away. The interval can be changed while running
+/// through the `watch` sender it was built from, the next tick is then due
+/// one new interval after the change.
 pub struct TimeCollector {
-    pub poll_secs: u64,
+    pub poll_secs: watch::Receiver<u64>,
 }
 
-/// A new block event, containing the block number and hash.
+/// A new tick event, containing the current timestamp.
 #[derive(Debug, Clone)]
 pub struct NewTick {
     pub timestamp: u64,
 }
 
 impl TimeCollector {
-    pub fn new(poll_secs: u64) -> Self {
+    pub fn new(poll_secs: watch::Receiver<u64>) -> Self {
         Self { poll_secs }
     }
 }
 
-/// Implementation of the [Collector](Collector) trait for the [BlockCollector](BlockCollector).
-/// This implementation uses the [PubsubClient](PubsubClient) to subscribe to new blocks.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Invalid timestamp")
+        .as_secs()
+}
+
+/// Implementation of the [Collector](Collector) trait for the [TimeCollector](TimeCollector).
 #[async_trait]
 impl Collector<NewTick> for TimeCollector {
     async fn get_event_stream<'a>(&'a self) -> Result<CollectorStream<'a, NewTick>> {
-        let stream = IntervalStream::new(tokio::time::interval(Duration::from_secs(
-            self.poll_secs,
-        )))
-        .then(|_| async {
-            let timestamp: u64 = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Invalid timestamp")
-                .as_secs();
-            return NewTick { timestamp };
-        });
+        let mut poll_secs = self.poll_secs.clone();
+        let stream = async_stream::stream! {
+            yield NewTick { timestamp: now() };
+            let mut sender_alive = true;
+            loop {
+                let interval = Duration::from_secs(std::cmp::max(*poll_secs.borrow_and_update(), MIN_POLL_SECS));
+                if sender_alive {
+                    let changed = tokio::select! {
+                        _ = tokio::time::sleep(interval) => None,
+                        changed = poll_secs.changed() => Some(changed.is_ok()),
+                    };
+                    match changed {
+                        None => {}
+                        Some(true) => {
+                            info!("tick interval changed to {}s", *poll_secs.borrow());
+                            continue;
+                        }
+                        // the sender is gone, keep ticking at the last interval
+                        Some(false) => {
+                            sender_alive = false;
+                            continue;
+                        }
+                    }
+                } else {
+                    tokio::time::sleep(interval).await;
+                }
+                yield NewTick { timestamp: now() };
+            }
+        };
 
         Ok(Box::pin(stream))
     }
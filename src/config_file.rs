@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use toml::Value;
+
+/// Flags loaded from the TOML file given with `--config`. Keys are the long
+/// flag names, with `_` or `-`, e.g. `update_all_pools_secs = 3600` or
+/// `deployment = "base"`.
+///
+/// Precedence, highest first: flags on the command line, values in the file,
+/// then the CLI defaults. The file only fills in flags the command line leaves
+/// out, except `chain_id`, which must agree when given in both. A repeatable
+/// flag like `rpc` given on the command line replaces all of the file's values.
+#[derive(Debug, Deserialize)]
+pub struct ConfigFile {
+    /// Checked against `--chain-id` instead of being overridden by it.
+    #[serde(default, alias = "chain-id")]
+    pub chain_id: Option<u64>,
+    /// Every other flag, keyed by its long name.
+    #[serde(flatten)]
+    pub flags: BTreeMap<String, Value>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Error reading config file {}", path))?;
+        toml::from_str(&content).with_context(|| format!("Error parsing config file {}", path))
+    }
+
+    /// The file's values as CLI flags.
+    fn to_args(&self) -> Result<Vec<OsString>> {
+        let mut args = Vec::new();
+        if let Some(chain_id) = self.chain_id {
+            args.push(format!("--chain-id={}", chain_id).into());
+        }
+        for (key, value) in &self.flags {
+            let flag = format!("--{}", key.replace('_', "-"));
+            let values = match value {
+                Value::Array(values) => values.clone(),
+                value => vec![value.clone()],
+            };
+            for value in values {
+                match value {
+                    Value::Boolean(true) => args.push(flag.clone().into()),
+                    Value::Boolean(false) => {}
+                    Value::String(value) => args.push(format!("{}={}", flag, value).into()),
+                    Value::Integer(value) => args.push(format!("{}={}", flag, value).into()),
+                    Value::Float(value) => args.push(format!("{}={}", flag, value).into()),
+                    other => anyhow::bail!("Unsupported value for {} in config file: {}", key, other),
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// The process arguments with the values of the `--config` file, if any,
+/// inserted before the command line flags so the latter override them.
+pub fn args_with_config_file() -> Result<Vec<OsString>> {
+    with_config_file(std::env::args_os().collect())
+}
+
+fn with_config_file(mut cli: Vec<OsString>) -> Result<Vec<OsString>> {
+    let Some(path) = flag_value(&cli, "--config") else {
+        return Ok(cli);
+    };
+    let mut config = ConfigFile::load(&path)?;
+    // repeated flags add up instead of overriding each other, so the file's
+    // values of a flag the command line gives are dropped rather than prepended
+    config.flags.retain(|key, _| !has_flag(&cli, &format!("--{}", key.replace('_', "-"))));
+
+    if let (Some(file_chain_id), Some(cli_chain_id)) = (config.chain_id, flag_value(&cli, "--chain-id")) {
+        if cli_chain_id.parse::<u64>().ok() != Some(file_chain_id) {
+            anyhow::bail!(
+                "chain_id {} in config file {} disagrees with --chain-id {}",
+                file_chain_id, path, cli_chain_id
+            );
+        }
+    }
+
+    let rest = cli.split_off(1);
+    cli.extend(config.to_args()?);
+    cli.extend(rest);
+    Ok(cli)
+}
+
+// whether `--flag value` or `--flag=value` is on the command line
+fn has_flag(args: &[OsString], flag: &str) -> bool {
+    args.iter().any(|arg| {
+        let arg = arg.to_string_lossy();
+        arg == flag || arg.starts_with(&format!("{}=", flag))
+    })
+}
+
+// value of `--flag value` or `--flag=value` on the command line
+fn flag_value(args: &[OsString], flag: &str) -> Option<String> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string_lossy().into_owned()).collect();
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == flag {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(&format!("{}=", flag)).map(str::to_string)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn command_line_repeatable_flags_replace_the_files() {
+        let path = std::env::temp_dir().join(format!("mm-liquidator-config-{}.toml", std::process::id()));
+        fs::write(&path, "rpc = [\"http://file-1\", \"http://file-2\"]\ndeployment = \"base\"\n").unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let merged = with_config_file(args(&["mm-liquidator", "--config", &path, "--rpc=http://cli"])).unwrap();
+
+        // the file's deployment fills in, its rpcs give way to the command line's
+        assert_eq!(merged, args(&["mm-liquidator", "--deployment=base", "--config", &path, "--rpc=http://cli"]));
+        fs::remove_file(&path).unwrap();
+    }
+}
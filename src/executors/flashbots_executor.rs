@@ -0,0 +1,180 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use anyhow::{Context, Result};
+use artemis_core::types::Executor;
+use async_trait::async_trait;
+use alloy::{
+    contract as alloy_contract,
+    eips::eip2718::Encodable2718,
+    network::{Ethereum, EthereumWallet, TransactionBuilder},
+    primitives::keccak256,
+    providers::PendingTransactionBuilder,
+    signers::{local::PrivateKeySigner, SignerSync},
+    transports::http::reqwest::{Client, Url},
+};
+use serde_json::json;
+
+use super::protect_executor::{ProtectExecutor, SubmitLiquidation};
+use crate::shutdown;
+
+/// How long to wait for a bundle to land before giving up on its receipt.
+const BUNDLE_RECEIPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Blocks, from the next one on, each liquidation is bundled for, so a bundle
+/// the next builder passes on still gets a few chances to land.
+const BUNDLE_TARGET_BLOCKS: u64 = 3;
+
+/// An executor that submits liquidations as single-tx bundles to a Flashbots
+/// relay, keeping them out of the public mempool. Falls back to the wrapped
+/// [ProtectExecutor] when the relay rejects the bundle.
+pub struct FlashbotsExecutor<T, P> {
+    protect: ProtectExecutor<T, P, Ethereum>,
+    client: Arc<P>,
+    wallet: EthereumWallet,
+    relay_url: Url,
+    auth_signer: PrivateKeySigner,
+    http: Client,
+}
+
+impl<
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum>,
+> FlashbotsExecutor<T, P> {
+    /// `protect` prices the tx and sends it publicly on relay errors. It
+    /// should read nonces from the node, since bundled txs never go through
+    /// its local nonce cache.
+    pub fn new(
+        protect: ProtectExecutor<T, P, Ethereum>,
+        client: Arc<P>,
+        wallet: EthereumWallet,
+        relay_url: Url,
+        auth_signer: PrivateKeySigner,
+    ) -> Self {
+        info!("flashbots relay: {}, auth signer {:?}", relay_url, auth_signer.address());
+        Self { protect, client, wallet, relay_url, auth_signer, http: Client::new() }
+    }
+
+    /// Send `eth_sendBundle` for the signed tx targeting `block_number`.
+    async fn send_bundle(&self, raw_tx: &[u8], block_number: u64) -> Result<()> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendBundle",
+            "params": [{
+                "txs": [format!("0x{}", hex::encode(raw_tx))],
+                "blockNumber": format!("0x{:x}", block_number),
+            }],
+        })
+        .to_string();
+        // the relay authenticates the body hash signed as an EIP-191 message
+        let signature = self
+            .auth_signer
+            .sign_message_sync(format!("{:?}", keccak256(body.as_bytes())).as_bytes())?;
+        let response = self
+            .http
+            .post(self.relay_url.clone())
+            .header("Content-Type", "application/json")
+            .header(
+                "X-Flashbots-Signature",
+                format!("{:?}:0x{}", self.auth_signer.address(), hex::encode(signature.as_bytes())),
+            )
+            .body(body)
+            .send()
+            .await
+            .context("Error sending bundle: {}")?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            anyhow::bail!("relay returned {}: {}", status, text);
+        }
+        let reply: serde_json::Value = serde_json::from_str(&text)?;
+        if let Some(error) = reply.get("error") {
+            anyhow::bail!("relay rejected bundle: {}", error);
+        }
+        info!("bundle accepted by relay: {}", reply["result"]);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum> + 'static,
+> Executor<SubmitLiquidation<Ethereum>> for FlashbotsExecutor<T, P>
+{
+    /// Submit a liquidation bundle for each of the next [BUNDLE_TARGET_BLOCKS]
+    /// blocks, falling back to the public mempool if the relay takes none.
+    async fn execute(&self, action: SubmitLiquidation<Ethereum>) -> Result<()> {
+        if shutdown::requested() {
+            warn!("Shutting down, dropping liquidation for opportunities {:?}", action.opportunity_ids);
+            return Ok(());
+        }
+        let _submission = shutdown::Submission::start();
+        info!("Liquidation for deployment {}", action.deployment.name());
+        let tag = action.tag();
+        let opportunity_ids = tag.opportunity_ids.clone();
+        let total_profit = action.submit.gas_bid_info.as_ref().map(|info| info.total_profit);
+        let tx = match self.protect.prepare(&tag, action.submit).await {
+            Ok(tx) => tx,
+            Err(e) => {
+                self.protect.report_failure(tag, &e);
+                return Err(e);
+            }
+        };
+        if self.protect.dry_run() {
+            return self.protect.send(tag, tx, total_profit).await;
+        }
+        let from = tx.from().context("Tx is missing a sender")?;
+
+        let nonce = self
+            .client
+            .get_transaction_count(from)
+            .pending()
+            .await
+            .context("Error getting transaction count: {}")?;
+        let next_block = self.client.get_block_number().await? + 1;
+        let signed = tx.clone().with_nonce(nonce).build(&self.wallet).await?;
+        let tx_hash = *signed.tx_hash();
+        let raw_tx = signed.encoded_2718();
+
+        // the same signed tx in every bundle, so at most one of them lands
+        let mut bundled = Vec::new();
+        let mut last_error = None;
+        for target_block in next_block..next_block + BUNDLE_TARGET_BLOCKS {
+            match self.send_bundle(&raw_tx, target_block).await {
+                Ok(()) => bundled.push(target_block),
+                Err(e) => {
+                    warn!("Bundle for block {} rejected: {}", target_block, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        let result = match last_error {
+            Some(e) if bundled.is_empty() => Err(e),
+            _ => Ok(()),
+        };
+
+        match result {
+            Ok(()) => {
+                info!(
+                    "Bundled {:?} for blocks {:?}, opportunities {:?}",
+                    tx_hash, bundled, opportunity_ids
+                );
+                let pending = PendingTransactionBuilder::new(self.client.root().clone(), tx_hash)
+                    .with_timeout(Some(BUNDLE_RECEIPT_TIMEOUT));
+                self.protect.watch_confirmation(tag, total_profit, pending);
+            }
+            Err(e) => {
+                warn!("Bundle submission failed, falling back to the public mempool: {}", e);
+                self.protect.send(tag, tx, total_profit).await?;
+            }
+        }
+
+        if let Err(e) = self.protect.flush_fee_share(from).await {
+            warn!("Fee transfer failed, keeping the share accrued: {}", e);
+        }
+        Ok(())
+    }
+}
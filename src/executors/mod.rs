@@ -1 +1,6 @@
+pub mod flashbots_executor;
+pub mod multi_relay_executor;
 pub mod protect_executor;
+pub mod refuel_executor;
+pub mod revert;
+pub mod supervised_executor;
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use anyhow::{Context, Result};
+use artemis_core::types::Executor;
+use async_trait::async_trait;
+use alloy::{
+    contract as alloy_contract,
+    eips::eip2718::Encodable2718,
+    network::{Ethereum, EthereumWallet, TransactionBuilder},
+    primitives::B256,
+    providers::{PendingTransactionBuilder, Provider as _},
+    rpc::types::BlockTransactionsKind,
+    transports::http::reqwest::{Client, Url},
+};
+use futures::future::join_all;
+use serde_json::json;
+
+use super::protect_executor::{ProtectExecutor, SubmitLiquidation};
+use crate::shutdown;
+
+/// How long to wait for a relayed tx to land before giving up on its receipt.
+const RELAY_RECEIPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An executor that sends each liquidation to several private relays at once
+/// with `eth_sendRawTransaction`, whichever gets it included first wins. The
+/// tx is signed once, so every relay holds the same tx and nonce and only one
+/// copy can land. Nonces come from the wrapped [ProtectExecutor]'s local cache.
+/// When every relay rejects it the tx is dropped, or sent to the public mempool
+/// through the [ProtectExecutor] with `public_fallback`, which exposes it to
+/// front-running.
+pub struct MultiRelayExecutor<T, P> {
+    protect: ProtectExecutor<T, P, Ethereum>,
+    client: Arc<P>,
+    wallet: EthereumWallet,
+    relays: Vec<Url>,
+    public_fallback: bool,
+    http: Client,
+}
+
+impl<
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum>,
+> MultiRelayExecutor<T, P> {
+    /// `protect` prices the tx, assigns its nonce and, with `public_fallback`,
+    /// sends it publicly when no relay takes it.
+    pub fn new(
+        protect: ProtectExecutor<T, P, Ethereum>,
+        client: Arc<P>,
+        wallet: EthereumWallet,
+        relays: Vec<Url>,
+        public_fallback: bool,
+    ) -> Self {
+        info!("private relays: {:?}", relays.iter().map(|relay| relay.host_str().unwrap_or_default()).collect::<Vec<_>>());
+        info!("public mempool fallback: {}", public_fallback);
+        Self { protect, client, wallet, relays, public_fallback, http: Client::new() }
+    }
+
+    /// Send `eth_sendRawTransaction` to one relay, checking it answers with the tx's own hash.
+    async fn send_to_relay(&self, relay: &Url, raw_tx: &str, tx_hash: B256) -> Result<()> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendRawTransaction",
+            "params": [raw_tx],
+        })
+        .to_string();
+        let response = self
+            .http
+            .post(relay.clone())
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .context("Error sending to relay")?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            anyhow::bail!("relay returned {}: {}", status, text);
+        }
+        let reply: serde_json::Value = serde_json::from_str(&text)?;
+        if let Some(error) = reply.get("error") {
+            anyhow::bail!("relay rejected tx: {}", error);
+        }
+        let relayed: B256 = serde_json::from_value(reply["result"].clone()).context("Relay answered without a tx hash")?;
+        if relayed != tx_hash {
+            anyhow::bail!("relay answered with tx hash {:?}", relayed);
+        }
+        Ok(())
+    }
+
+    /// Log the builder of the block a relayed tx landed in, the relays that
+    /// accepted it only tell who could have included it.
+    fn watch_landing(&self, tx_hash: B256, accepted: Vec<String>) {
+        let provider = self.client.root().clone();
+        tokio::spawn(async move {
+            let pending = PendingTransactionBuilder::new(provider.clone(), tx_hash)
+                .with_timeout(Some(RELAY_RECEIPT_TIMEOUT));
+            let Ok(receipt) = pending.get_receipt().await else {
+                return;
+            };
+            let Some(block_number) = receipt.block_number else {
+                return;
+            };
+            let builder = match provider.get_block_by_number(block_number.into(), BlockTransactionsKind::Hashes).await {
+                Ok(Some(block)) => String::from_utf8_lossy(&block.header.extra_data).to_string(),
+                _ => "unknown".to_string(),
+            };
+            info!(
+                "Relayed {:?} landed in block {} built by {:?}, accepted by relays {:?}",
+                tx_hash, block_number, builder, accepted
+            );
+        });
+    }
+}
+
+#[async_trait]
+impl<
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum> + 'static,
+> Executor<SubmitLiquidation<Ethereum>> for MultiRelayExecutor<T, P>
+{
+    /// Submit a liquidation to every relay concurrently.
+    async fn execute(&self, action: SubmitLiquidation<Ethereum>) -> Result<()> {
+        if shutdown::requested() {
+            warn!("Shutting down, dropping liquidation for opportunities {:?}", action.opportunity_ids);
+            return Ok(());
+        }
+        let _submission = shutdown::Submission::start();
+        info!("Liquidation for deployment {}", action.deployment.name());
+        let tag = action.tag();
+        let opportunity_ids = tag.opportunity_ids.clone();
+        let total_profit = action.submit.gas_bid_info.as_ref().map(|info| info.total_profit);
+        let tx = match self.protect.prepare(&tag, action.submit).await {
+            Ok(tx) => tx,
+            Err(e) => {
+                self.protect.report_failure(tag, &e);
+                return Err(e);
+            }
+        };
+        if self.protect.dry_run() {
+            return self.protect.send(tag, tx, total_profit).await;
+        }
+        let from = tx.from().context("Tx is missing a sender")?;
+
+        let nonce = self.protect.reserve_nonce(from).await?;
+        let signed = match tx.clone().with_nonce(nonce).build(&self.wallet).await {
+            Ok(signed) => signed,
+            Err(e) => {
+                self.protect.release_nonce(nonce, &e.to_string()).await;
+                return Err(e.into());
+            }
+        };
+        let tx_hash = *signed.tx_hash();
+        let raw_tx = format!("0x{}", hex::encode(signed.encoded_2718()));
+
+        let results = join_all(self.relays.iter().map(|relay| self.send_to_relay(relay, &raw_tx, tx_hash))).await;
+        let mut accepted = Vec::new();
+        let mut rejections = Vec::new();
+        for (relay, result) in self.relays.iter().zip(results) {
+            let relay = relay.host_str().unwrap_or_default().to_string();
+            match result {
+                Ok(()) => accepted.push(relay),
+                Err(e) => {
+                    warn!("Relay {} didn't take {:?}: {}", relay, tx_hash, e);
+                    rejections.push(e.to_string());
+                }
+            }
+        }
+
+        if accepted.is_empty() {
+            self.protect.release_nonce(nonce, &rejections.join("; ")).await;
+            if !self.public_fallback {
+                anyhow::bail!("every relay rejected {:?}, opportunities {:?}", tx_hash, opportunity_ids);
+            }
+            warn!("Every relay rejected {:?}, falling back to the public mempool", tx_hash);
+            self.protect.send(tag, tx, total_profit).await?;
+        } else {
+            info!(
+                "Relayed {:?} through {} of {} relays {:?}, opportunities {:?}",
+                tx_hash, accepted.len(), self.relays.len(), accepted, opportunity_ids
+            );
+            let pending = PendingTransactionBuilder::new(self.client.root().clone(), tx_hash)
+                .with_timeout(Some(RELAY_RECEIPT_TIMEOUT));
+            self.protect.watch_confirmation(tag, total_profit, pending);
+            self.watch_landing(tx_hash, accepted);
+        }
+
+        if let Err(e) = self.protect.flush_fee_share(from).await {
+            warn!("Fee transfer failed, keeping the share accrued: {}", e);
+        }
+        Ok(())
+    }
+}
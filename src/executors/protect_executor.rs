@@ -1,5 +1,17 @@
-use std::{ops::Mul, sync::Arc};
-use tracing::info;
+use std::{collections::BTreeMap, ops::Mul, sync::Arc};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Mutex, Semaphore};
+use tracing::{info, warn};
+
+use crate::executors::revert::{decode_revert, LiquidationFailure};
+use crate::format::format_wei;
+use crate::strategies::decimals::{from_common_unit, COMMON_DECIMALS};
+use crate::strategies::mm_strategy::Deployment;
+use bindings_mm::ierc20::IERC20;
+use crate::alerts;
+use crate::metrics::{self, SkipReason};
+use crate::shutdown;
 
 use anyhow::{Context, Result};
 use artemis_core::executors::mempool_executor::SubmitTxToMempool;
@@ -7,16 +19,261 @@ use artemis_core::types::Executor;
 use async_trait::async_trait;
 use alloy::{
     contract as alloy_contract,
+    eips::BlockId,
     //providers::Provider, 
     // network::Ethereum,
     // transports::BoxTransport,
-    network::TransactionBuilder,
+    network::{BlockResponse, HeaderResponse, ReceiptResponse, TransactionBuilder},
+    primitives::{Address, B256, I256, U256},
+    providers::{utils::Eip1559Estimation, PendingTransactionBuilder, Provider, RootProvider},
+    sol_types::SolCall,
+    rpc::types::{
+        state::{AccountOverride, StateOverride},
+        BlockTransactionsKind, Log,
+    },
 };
+use serde::Deserialize;
+
+/// JSON-RPC error code returned by nodes that don't implement a method.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// How often a mined liquidation's depth is checked while waiting for --confirmations.
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Submissions further apart than this start a new cascade wave.
+const CASCADE_IDLE_GAP: Duration = Duration::from_secs(5);
+
+/// Whether a submission error means the tx's nonce is out of sync with the chain.
+fn is_nonce_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("nonce too low") || message.contains("nonce too high")
+}
+
+/// Forget the cached next nonce after `tx_hash` was dropped: its nonce went
+/// unused, so counting on from the cache would leave a gap every later tx
+/// waits behind. The next submission reads the nonce from the chain instead.
+async fn resync_after_drop(next_nonce: &Mutex<Option<u64>>, tx_hash: B256) {
+    let mut next_nonce = next_nonce.lock().await;
+    if next_nonce.take().is_some() {
+        warn!("{:?} was dropped, resyncing nonce from chain", tx_hash);
+    }
+}
+
+/// How liquidation txs are priced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GasMode {
+    /// Type 2 txs with `maxFeePerGas` and `maxPriorityFeePerGas`.
+    Eip1559,
+    /// Type 0 txs with `gasPrice`, for chains without a base fee.
+    Legacy,
+}
+
+impl GasMode {
+    /// Check `requested` against the latest block, which carries a base fee
+    /// only on EIP-1559 chains, or pick the mode from it when none is requested.
+    pub async fn resolve<T, P, N>(provider: &P, requested: Option<GasMode>) -> Result<GasMode>
+    where
+        T: alloy_contract::private::Transport + ::core::clone::Clone,
+        P: alloy_contract::private::Provider<T, N>,
+        N: alloy_contract::private::Network,
+    {
+        let block = provider
+            .get_block(BlockId::latest(), BlockTransactionsKind::Hashes)
+            .await?
+            .context("Latest block not found")?;
+        let supports_eip1559 = block.header().base_fee_per_gas().is_some();
+        let mode = match requested {
+            Some(GasMode::Eip1559) if !supports_eip1559 => {
+                anyhow::bail!("--gas-mode eip1559 but the chain has no base fee, use --gas-mode legacy")
+            }
+            Some(mode) => mode,
+            None if supports_eip1559 => GasMode::Eip1559,
+            None => GasMode::Legacy,
+        };
+        info!("gas mode: {:?}", mode);
+        Ok(mode)
+    }
+}
+
+/// Progress of the current submission wave during a liquidation cascade.
+#[derive(Debug)]
+struct CascadeWave {
+    submitted: usize,
+    succeeded: usize,
+    last_submission: Option<Instant>,
+}
+
+/// Share of confirmed liquidation profit forwarded to a fee recipient.
+#[derive(Debug, Clone)]
+pub struct FeeShare {
+    /// Share of net profit, in bps.
+    pub bps: u64,
+    /// Address the share is transferred to.
+    pub recipient: Address,
+    /// Token the share is paid in.
+    pub token: Address,
+    /// Accrued shares are held back until they reach this amount.
+    pub batch_min: U256,
+}
+
+/// Prices of a liquidation's tokens and of gas in the profit token, taken from
+/// the pools when the liquidation is emitted. Gas costs and realized token
+/// deltas are valued with them in the unit of the expected profit,
+/// [COMMON_DECIMALS] units of the profit token.
+#[derive(Debug, Clone, Default)]
+pub struct Valuation {
+    /// Decimals of the profit token.
+    pub profit_decimals: u8,
+    /// Value of one native token, None when no pool prices it.
+    pub native_price: Option<U256>,
+    /// Decimals and value of one whole token, for the tokens the liquidation moves.
+    pub token_prices: BTreeMap<Address, (u8, U256)>,
+}
+
+impl Valuation {
+    /// `wei` of gas in [COMMON_DECIMALS] units of the profit token.
+    pub fn gas_cost(&self, wei: u128) -> Option<u128> {
+        let price = self.native_price?;
+        Some((U256::from(wei).saturating_mul(price) / native_unit()).saturating_to())
+    }
+
+    /// `amount` in [COMMON_DECIMALS] units of the profit token as wei of gas.
+    pub fn to_wei(&self, amount: u128) -> Option<u128> {
+        let price = self.native_price.filter(|price| !price.is_zero())?;
+        Some((U256::from(amount).saturating_mul(native_unit()) / price).saturating_to())
+    }
+
+    /// Net value of token deltas in [COMMON_DECIMALS] units of the profit token,
+    /// None when one of the tokens isn't priced.
+    pub fn value_deltas(&self, deltas: &[(Address, I256)]) -> Option<I256> {
+        let mut net = I256::ZERO;
+        for (token, delta) in deltas {
+            let (decimals, price) = self.token_prices.get(token)?;
+            let value = delta.unsigned_abs().saturating_mul(*price) / U256::from(10).pow(U256::from(*decimals));
+            let value = I256::try_from(value).unwrap_or(I256::MAX);
+            net = if delta.is_negative() { net.saturating_sub(value) } else { net.saturating_add(value) };
+        }
+        Some(net)
+    }
+}
+
+// one native token in wei
+fn native_unit() -> U256 {
+    U256::from(10).pow(U256::from(COMMON_DECIMALS))
+}
+
+/// A liquidation tx tagged with the ids of the opportunities it covers.
+#[derive(Debug, Clone)]
+pub struct SubmitLiquidation<N: alloy_contract::private::Network> {
+    pub deployment: Deployment,
+    pub opportunity_ids: Vec<B256>,
+    /// Keys of the liquidated positions, echoed back in [LiquidationOutcome].
+    pub positions: Vec<B256>,
+    /// Prices valuing gas like the expected profit, None before the profit token is known.
+    pub valuation: Option<Valuation>,
+    pub submit: SubmitTxToMempool<N>,
+}
+
+impl<N: alloy_contract::private::Network> SubmitLiquidation<N> {
+    pub fn tag(&self) -> LiquidationTag {
+        LiquidationTag {
+            deployment: self.deployment.clone(),
+            opportunity_ids: self.opportunity_ids.clone(),
+            positions: self.positions.clone(),
+            valuation: self.valuation.clone(),
+        }
+    }
+}
+
+/// Identifies a liquidation from submission to its reported outcome.
+#[derive(Debug, Clone)]
+pub struct LiquidationTag {
+    pub deployment: Deployment,
+    pub opportunity_ids: Vec<B256>,
+    pub positions: Vec<B256>,
+    pub valuation: Option<Valuation>,
+}
+
+/// A liquidation that reverted before it was sent.
+#[derive(Debug, Clone)]
+pub struct LiquidationFailed {
+    pub tag: LiquidationTag,
+    pub failure: LiquidationFailure,
+}
+
+/// A liquidation confirmed on-chain, with the liquidator's net token
+/// transfers in the tx.
+#[derive(Debug, Clone)]
+pub struct LiquidationConfirmed {
+    pub tag: LiquidationTag,
+    pub tx_hash: B256,
+    pub block_number: Option<u64>,
+    pub gas_used: u64,
+    pub effective_gas_price: u128,
+    pub token_deltas: Vec<(Address, I256)>,
+}
+
+/// A liquidation mined but reverted on-chain.
+#[derive(Debug, Clone)]
+pub struct LiquidationReverted {
+    pub tag: LiquidationTag,
+    pub tx_hash: B256,
+    pub gas_used: u64,
+    pub effective_gas_price: u128,
+}
+
+/// A liquidation sent but not mined within the receipt timeout, its
+/// positions may be submitted again.
+#[derive(Debug, Clone)]
+pub struct LiquidationDropped {
+    pub tag: LiquidationTag,
+    pub tx_hash: B256,
+}
+
+/// Outcome of a liquidation, reported back to the strategy.
+#[derive(Debug, Clone)]
+pub enum LiquidationOutcome {
+    Failed(LiquidationFailed),
+    Confirmed(LiquidationConfirmed),
+    Reverted(LiquidationReverted),
+    Dropped(LiquidationDropped),
+}
+
+/// Only the logs of a receipt, read without the network's receipt type.
+#[derive(Deserialize)]
+struct ReceiptLogs {
+    logs: Vec<Log>,
+}
 
 /// An executor that sends transactions to the mempool.
 pub struct ProtectExecutor<T, P, N = alloy_contract::private::Ethereum> {
     client: Arc<P>,
     sender_client: Arc<P>,
+    fallback_priority_fee_wei: u128,
+    gas_mode: GasMode,
+    estimate_with_overrides: bool,
+    external_nonce_coordination: bool,
+    simulate_before_send: bool,
+    simulation_permits: Semaphore,
+    simulations_in_flight: AtomicUsize,
+    fee_history_unsupported: AtomicBool,
+    next_nonce: Arc<Mutex<Option<u64>>>,
+    last_submission: Mutex<Option<Instant>>,
+    nonce_resync_idle: Option<Duration>,
+    cascade_chunk_size: usize,
+    cascade_chunk_delay: Duration,
+    wave: Mutex<CascadeWave>,
+    min_native_reserve_wei: U256,
+    fee_share: Option<FeeShare>,
+    accrued_fee: Arc<Mutex<U256>>,
+    dry_run: bool,
+    receipt_timeout: Duration,
+    gas_limit_multiplier: f64,
+    confirmations: u64,
+    tip_escalation_pct: u64,
+    max_priority_fee_wei: u128,
+    tip_escalation_timeout: Duration,
+    outcomes: Option<broadcast::Sender<LiquidationOutcome>>,
     _network_transport: ::core::marker::PhantomData<(N, T)>,
 }
 
@@ -25,63 +282,1287 @@ impl<
     P: alloy_contract::private::Provider<T, N>,
     N: alloy_contract::private::Network,
 > ProtectExecutor<T, P, N> {
-    pub fn new(client: Arc<P>, sender_client: Arc<P>) -> Self {
+    pub fn new(
+        client: Arc<P>,
+        sender_client: Arc<P>,
+        fallback_priority_fee_wei: u128,
+        gas_mode: GasMode,
+        estimate_with_overrides: bool,
+        external_nonce_coordination: bool,
+        simulate_before_send: bool,
+        max_concurrent_simulations: usize,
+        nonce_resync_idle_secs: u64,
+        cascade_chunk_size: usize,
+        cascade_chunk_delay_ms: u64,
+        min_native_reserve_wei: U256,
+        fee_share: Option<FeeShare>,
+        dry_run: bool,
+        receipt_timeout_secs: u64,
+        gas_limit_multiplier: f64,
+        confirmations: u64,
+        tip_escalation_pct: u64,
+        max_priority_fee_wei: u128,
+        tip_escalation_timeout_secs: u64,
+        outcomes: Option<broadcast::Sender<LiquidationOutcome>>,
+    ) -> Self {
+        info!("estimate with overrides: {}", estimate_with_overrides);
+        info!("simulate before send: {}", simulate_before_send);
+        info!(
+            "nonce source: {}",
+            if external_nonce_coordination { "pending transaction count per submission" } else { "local cache" }
+        );
+        info!("min native reserve: {}", min_native_reserve_wei);
+        info!("receipt timeout: {}s", receipt_timeout_secs);
+        if gas_limit_multiplier < 1.0 {
+            warn!("gas limit multiplier {} is below 1, using 1", gas_limit_multiplier);
+        }
+        let gas_limit_multiplier = gas_limit_multiplier.max(1.0);
+        info!("gas limit multiplier: {}", gas_limit_multiplier);
+        let confirmations = confirmations.max(1);
+        info!("confirmations: {}", confirmations);
+        let tip_escalation_pct = match gas_mode {
+            GasMode::Legacy if tip_escalation_pct > 0 => {
+                warn!("tip escalation needs EIP-1559 fees, disabled in legacy gas mode");
+                0
+            }
+            _ => tip_escalation_pct,
+        };
+        if tip_escalation_pct > 0 {
+            info!(
+                "tip escalation: +{}% every {}s up to a priority fee of {}",
+                tip_escalation_pct, tip_escalation_timeout_secs, max_priority_fee_wei
+            );
+        }
+        if let Some(fee_share) = &fee_share {
+            info!("fee share: {:?}", fee_share);
+        }
+        if dry_run {
+            warn!("dry run: liquidation txs are logged, never broadcast");
+        }
         Self {
             client,
             sender_client,
+            fallback_priority_fee_wei,
+            gas_mode,
+            estimate_with_overrides,
+            external_nonce_coordination,
+            simulate_before_send,
+            simulation_permits: Semaphore::new(max_concurrent_simulations.max(1)),
+            simulations_in_flight: AtomicUsize::new(0),
+            fee_history_unsupported: AtomicBool::new(false),
+            next_nonce: Arc::new(Mutex::new(None)),
+            last_submission: Mutex::new(None),
+            nonce_resync_idle: (nonce_resync_idle_secs > 0).then(|| Duration::from_secs(nonce_resync_idle_secs)),
+            cascade_chunk_size,
+            cascade_chunk_delay: Duration::from_millis(cascade_chunk_delay_ms),
+            wave: Mutex::new(CascadeWave { submitted: 0, succeeded: 0, last_submission: None }),
+            min_native_reserve_wei,
+            fee_share,
+            accrued_fee: Arc::new(Mutex::new(U256::ZERO)),
+            dry_run,
+            receipt_timeout: Duration::from_secs(receipt_timeout_secs),
+            gas_limit_multiplier,
+            confirmations,
+            tip_escalation_pct,
+            max_priority_fee_wei,
+            tip_escalation_timeout: Duration::from_secs(tip_escalation_timeout_secs),
+            outcomes,
             _network_transport: ::core::marker::PhantomData,
         }
     }
+
+    /// Whether prepared txs are only logged instead of sent.
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Number of pre-flight simulations currently running.
+    pub fn simulations_in_flight(&self) -> usize {
+        self.simulations_in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Gas limit of a liquidation whose gas was estimated at `gas_estimate`:
+    /// the estimate times the multiplier, for liquidations touching more
+    /// storage than estimated, capped at the latest block's gas limit.
+    async fn gas_limit(&self, gas_estimate: u64) -> u64 {
+        let gas_limit = (gas_estimate as f64 * self.gas_limit_multiplier).ceil() as u64;
+        let gas_limit = match self.client.get_block(BlockId::latest(), BlockTransactionsKind::Hashes).await {
+            Ok(Some(block)) => std::cmp::min(gas_limit, block.header().gas_limit()),
+            Ok(None) => gas_limit,
+            Err(e) => {
+                warn!("Can't read the block gas limit, not capping the tx's: {}", e);
+                gas_limit
+            }
+        };
+        info!(
+            gas_estimate,
+            gas_limit,
+            "gas estimate {}, gas limit {} at multiplier {}",
+            gas_estimate, gas_limit, self.gas_limit_multiplier
+        );
+        gas_limit
+    }
+
+    /// Estimate gas, optionally against a state override funding the sender so
+    /// the estimate follows the real execution path. Falls back to a plain
+    /// estimate when the node doesn't support overrides.
+    async fn estimate_gas(&self, tx: &N::TransactionRequest) -> Result<u64> {
+        // bound concurrent simulations, excess ones queue on the semaphore
+        let _permit = self.simulation_permits.acquire().await?;
+        let in_flight = self.simulations_in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        metrics::record_simulation_started();
+        info!("simulations in flight: {}", in_flight);
+        let result = self.estimate_gas_inner(tx).await;
+        self.simulations_in_flight.fetch_sub(1, Ordering::Relaxed);
+        metrics::record_simulation_finished();
+        result
+    }
+
+    /// `eth_call` the tx against the pending state, failing with the decoded
+    /// revert reason when it would revert on-chain.
+    async fn simulate(&self, tx: &N::TransactionRequest) -> Result<()> {
+        let _permit = self.simulation_permits.acquire().await?;
+        self.simulations_in_flight.fetch_add(1, Ordering::Relaxed);
+        metrics::record_simulation_started();
+        let result = self.client.call(tx).block(BlockId::pending()).await;
+        self.simulations_in_flight.fetch_sub(1, Ordering::Relaxed);
+        metrics::record_simulation_finished();
+        if let Err(e) = result {
+            let data = e.as_error_resp().and_then(|resp| resp.as_revert_data());
+            let reason = data
+                .as_ref()
+                .and_then(|data| decode_revert(data))
+                .unwrap_or_else(|| e.to_string());
+            let failure = data.map_or(LiquidationFailure::Unknown, |data| LiquidationFailure::classify(&data));
+            return Err(anyhow::Error::new(failure).context(format!("Simulation reverted: {}", reason)));
+        }
+        Ok(())
+    }
+
+    /// Count a liquidation that reverted in simulation or gas estimation by
+    /// its failure kind and report it to the strategy. Other errors, like
+    /// fee caps or RPC failures, aren't reverts and are left alone.
+    pub fn report_failure(&self, tag: LiquidationTag, error: &anyhow::Error) {
+        alerts::submission_failed(error);
+        let Some(failure) = error.downcast_ref::<LiquidationFailure>() else {
+            return;
+        };
+        metrics::record_failure(failure.kind());
+        warn!(
+            "Liquidation of opportunities {:?} failed with {}, {}",
+            tag.opportunity_ids, failure,
+            if failure.is_transient() { "transient" } else { "deterministic" }
+        );
+        self.report(LiquidationOutcome::Failed(LiquidationFailed { tag, failure: failure.clone() }));
+    }
+
+    fn report(&self, outcome: LiquidationOutcome) {
+        if let Some(outcomes) = &self.outcomes {
+            // no receiver only means no strategy listens for outcomes
+            let _ = outcomes.send(outcome);
+        }
+    }
+
+    async fn estimate_gas_inner(&self, tx: &N::TransactionRequest) -> Result<u64> {
+        if self.estimate_with_overrides {
+            if let Some(from) = tx.from() {
+                let mut overrides = StateOverride::default();
+                overrides.insert(
+                    from,
+                    AccountOverride {
+                        balance: Some(U256::MAX >> 1),
+                        ..Default::default()
+                    },
+                );
+                match self.client.estimate_gas(tx).overrides(&overrides).await {
+                    Ok(gas) => return Ok(gas),
+                    Err(e) => warn!("Estimate with overrides failed, falling back to plain estimate: {}", e),
+                }
+            }
+        }
+
+        match self.client.estimate_gas(tx).await {
+            Ok(gas) => Ok(gas),
+            Err(e) => {
+                if let Some(data) = e.as_error_resp().and_then(|resp| resp.as_revert_data()) {
+                    if let Some(reason) = decode_revert(&data) {
+                        warn!("Simulation reverted: {}", reason);
+                    }
+                    let failure = LiquidationFailure::classify(&data);
+                    return Err(anyhow::Error::new(failure).context(format!("Error estimating gas usage: {}", e)));
+                }
+                Err(e).context("Error estimating gas usage: {}")
+            }
+        }
+    }
+
+    /// Estimate EIP-1559 fees, falling back to `eth_gasPrice` plus a fixed
+    /// priority fee when the node doesn't implement `eth_feeHistory`. In
+    /// legacy mode the max fee is the gas price and there is no priority fee.
+    async fn estimate_fees(&self) -> Result<Eip1559Estimation> {
+        if self.gas_mode == GasMode::Legacy {
+            let gas_price = self
+                .client
+                .get_gas_price()
+                .await
+                .context("Error getting gas price: {}")?;
+            return Ok(Eip1559Estimation { max_fee_per_gas: gas_price, max_priority_fee_per_gas: 0 });
+        }
+        if !self.fee_history_unsupported.load(Ordering::Relaxed) {
+            match self.client.estimate_eip1559_fees(None).await {
+                Ok(fees) => return Ok(fees),
+                Err(e) => {
+                    let unsupported = e
+                        .as_error_resp()
+                        .map_or(false, |resp| resp.code == METHOD_NOT_FOUND);
+                    if !unsupported {
+                        return Err(e).context("Error estimating eip1559 fees: {}");
+                    }
+                    warn!(
+                        "eth_feeHistory unavailable, falling back to eth_gasPrice with priority fee {}",
+                        self.fallback_priority_fee_wei
+                    );
+                    self.fee_history_unsupported.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+
+        let gas_price = self
+            .client
+            .get_gas_price()
+            .await
+            .context("Error getting gas price: {}")?;
+        Ok(Eip1559Estimation {
+            max_fee_per_gas: gas_price + self.fallback_priority_fee_wei,
+            max_priority_fee_per_gas: self.fallback_priority_fee_wei,
+        })
+    }
+
+    /// Price a tx with `fees` as the gas mode calls for.
+    fn set_fees(&self, tx: &mut N::TransactionRequest, fees: Eip1559Estimation) {
+        match self.gas_mode {
+            GasMode::Eip1559 => {
+                tx.set_max_fee_per_gas(fees.max_fee_per_gas);
+                tx.set_max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+            }
+            GasMode::Legacy => tx.set_gas_price(fees.max_fee_per_gas),
+        }
+    }
+
+    /// Refuse a tx whose worst-case gas cost and value would take the sender's
+    /// balance below the native reserve, so there is always gas left for the
+    /// next tx.
+    async fn check_native_reserve(&self, tx: &N::TransactionRequest, gas_cost: u128) -> Result<()> {
+        if self.min_native_reserve_wei.is_zero() {
+            return Ok(());
+        }
+        let from = tx.from().context("Tx is missing a sender")?;
+        let balance = self
+            .client
+            .get_balance(from)
+            .await
+            .context("Error getting native balance: {}")?;
+        let spend = U256::from(gas_cost) + tx.value().unwrap_or_default();
+        if balance < spend + self.min_native_reserve_wei {
+            metrics::record_skip(SkipReason::NativeReserve);
+            warn!(
+                "Tx blocked by native reserve: balance {}, worst-case spend {}, reserve {}",
+                format_wei(balance.saturating_to::<u128>()), format_wei(spend.saturating_to::<u128>()), format_wei(self.min_native_reserve_wei.saturating_to::<u128>())
+            );
+            anyhow::bail!("Tx would dip the native balance below the reserve");
+        }
+        Ok(())
+    }
+
+    /// Cap the max fee so the worst-case gas cost stays within `bid_percentage`
+    /// of the expected profit, failing when that cap can't cover the current
+    /// base fee plus the priority fee. The budget is in the profit token and is
+    /// turned into wei at the valuation's native token price; without one the
+    /// fees are left uncapped.
+    async fn bid_fees(
+        &self,
+        fees: Eip1559Estimation,
+        gas_usage: u64,
+        total_profit: u128,
+        bid_percentage: u64,
+        valuation: Option<&Valuation>,
+    ) -> Result<Eip1559Estimation> {
+        let budget = total_profit.saturating_mul(bid_percentage as u128) / 100;
+        let Some(budget) = valuation.and_then(|valuation| valuation.to_wei(budget)) else {
+            warn!("Can't price gas in the profit token, not capping the bid at {}% of profit", bid_percentage);
+            return Ok(fees);
+        };
+        let bid_max_fee = budget / std::cmp::max(1, gas_usage as u128);
+        let base_fee = match self.gas_mode {
+            GasMode::Eip1559 => self
+                .client
+                .get_block(BlockId::latest(), BlockTransactionsKind::Hashes)
+                .await?
+                .and_then(|block| block.header().base_fee_per_gas())
+                .unwrap_or_default() as u128,
+            // the current gas price is the floor of a legacy tx
+            GasMode::Legacy => fees.max_fee_per_gas,
+        };
+        let min_inclusion_fee = base_fee + fees.max_priority_fee_per_gas;
+        if bid_max_fee < min_inclusion_fee {
+            metrics::record_skip(SkipReason::GasCap);
+            anyhow::bail!(
+                "Bid of {}% of profit {} ({} wei) allows max fee {} per gas, below base fee {} plus priority fee {}",
+                bid_percentage, total_profit, budget, bid_max_fee, base_fee, fees.max_priority_fee_per_gas
+            );
+        }
+        Ok(Eip1559Estimation {
+            max_fee_per_gas: std::cmp::min(fees.max_fee_per_gas, bid_max_fee),
+            max_priority_fee_per_gas: fees.max_priority_fee_per_gas,
+        })
+    }
+
+    /// Pause between waves of `cascade_chunk_size` submissions. Every tx is
+    /// simulated again right before sending, so opportunities that were
+    /// liquidated or turned healthy during the pause drop out.
+    async fn wait_for_wave(&self) {
+        if self.cascade_chunk_size == 0 {
+            return;
+        }
+        let mut wave = self.wave.lock().await;
+        if wave.last_submission.map_or(true, |last| last.elapsed() > CASCADE_IDLE_GAP) {
+            wave.submitted = 0;
+            wave.succeeded = 0;
+        }
+        if wave.submitted >= self.cascade_chunk_size {
+            info!(
+                "Cascade wave done: {} submitted, {} succeeded, waiting {:?} before the next wave",
+                wave.submitted, wave.succeeded, self.cascade_chunk_delay
+            );
+            tokio::time::sleep(self.cascade_chunk_delay).await;
+            wave.submitted = 0;
+            wave.succeeded = 0;
+        }
+    }
+
+    async fn record_wave_result(&self, succeeded: bool) {
+        if self.cascade_chunk_size == 0 {
+            return;
+        }
+        let mut wave = self.wave.lock().await;
+        wave.submitted += 1;
+        if succeeded {
+            wave.succeeded += 1;
+        }
+        wave.last_submission = Some(Instant::now());
+    }
 }
 
 #[async_trait]
 impl<
     T: alloy_contract::private::Transport + ::core::clone::Clone,
-    P: alloy_contract::private::Provider<T, N>,
+    P: alloy_contract::private::Provider<T, N> + 'static,
     N: alloy_contract::private::Network,
-> Executor<SubmitTxToMempool<N>> for ProtectExecutor<T, P, N>
+> Executor<SubmitLiquidation<N>> for ProtectExecutor<T, P, N>
 {
     /// Send a transaction to the mempool.
-    async fn execute(&self, mut action: SubmitTxToMempool<N>) -> Result<()> {
-        info!("Executing tx {:?}", action.tx);
+    async fn execute(&self, action: SubmitLiquidation<N>) -> Result<()> {
+        if shutdown::requested() {
+            warn!("Shutting down, dropping liquidation for opportunities {:?}", action.opportunity_ids);
+            return Ok(());
+        }
+        let _submission = shutdown::Submission::start();
+        self.wait_for_wave().await;
+        let from = action.submit.tx.from();
+        info!("Liquidation for deployment {}", action.deployment.name());
+        let tag = action.tag();
+        let result = self.submit(tag.clone(), action.submit).await;
+        if let Err(e) = &result {
+            self.report_failure(tag, e);
+        }
+        self.record_wave_result(result.is_ok()).await;
+        if let Some(from) = from {
+            if let Err(e) = self.flush_fee_share(from).await {
+                warn!("Fee transfer failed, keeping the share accrued: {}", e);
+            }
+        }
+        result
+    }
+}
+
+impl<
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, N> + 'static,
+    N: alloy_contract::private::Network,
+> ProtectExecutor<T, P, N> {
+    async fn submit(&self, tag: LiquidationTag, action: SubmitTxToMempool<N>) -> Result<()> {
+        let total_profit = action.gas_bid_info.as_ref().map(|info| info.total_profit);
+        let tx = self.prepare(&tag, action).await?;
+        self.send(tag, tx, total_profit).await
+    }
+
+    /// Simulate a liquidation tx and set its gas limit and fees, failing when
+    /// it reverts, dips into the native reserve or costs more than its profit.
+    pub async fn prepare(&self, tag: &LiquidationTag, mut action: SubmitTxToMempool<N>) -> Result<N::TransactionRequest> {
+        let opportunity_ids = &tag.opportunity_ids;
+        info!("Executing tx for opportunities {:?}: {:?}", opportunity_ids, action.tx);
         //action.tx.set_from(self.client.default_signer_address());
-        let gas_usage_result = self
-            .client
-            .estimate_gas(&action.tx)
-            .await
-            .context("Error estimating gas usage: {}");
+        if self.simulate_before_send {
+            if let Err(e) = self.simulate(&action.tx).await {
+                metrics::record_skip(SkipReason::SimulationRevert);
+                warn!("Dropping tx for opportunities {:?}: {}", opportunity_ids, e);
+                return Err(e);
+            }
+        }
+        let gas_usage_result = self.estimate_gas(&action.tx).await;
 
         info!("Gas Usage {:?}", gas_usage_result);
+        if gas_usage_result.is_err() {
+            metrics::record_skip(SkipReason::SimulationRevert);
+        }
         let gas_usage = gas_usage_result?;
 
-        let bid_gas_price;
+        // Just use estimated fees but throw if they are too high
+        let mut fees = self.estimate_fees().await?;
         if let Some(gas_bid_info) = action.gas_bid_info {
-            // Just use estimated gas price but throw if its too low
-            bid_gas_price = self
-                .client
-                .get_gas_price()
-                .await
-                .context("Error getting gas price: {}")?;
-            let estimated_cost = bid_gas_price.mul(gas_usage as u128);
+            if gas_bid_info.bid_percentage > 0 {
+                fees = self
+                    .bid_fees(
+                        fees,
+                        gas_usage,
+                        gas_bid_info.total_profit,
+                        gas_bid_info.bid_percentage,
+                        tag.valuation.as_ref(),
+                    )
+                    .await?;
+            }
+            let estimated_cost = fees.max_fee_per_gas.mul(gas_usage as u128);
             info!(
-                "Gas bid info: {:?}, estimated cost: {}, bid gas price: {}",
-                gas_bid_info, estimated_cost, bid_gas_price
+                "Gas bid info: {:?}, estimated cost: {}, max fee per gas: {}",
+                gas_bid_info, format_wei(estimated_cost), fees.max_fee_per_gas
             );
-            if estimated_cost > gas_bid_info.total_profit {
-                anyhow::bail!("Estimated cost of tx is greater than total profit");
+            // the profit is in the profit token, compare it to the gas priced in that token
+            match tag.valuation.as_ref().and_then(|valuation| valuation.gas_cost(estimated_cost)) {
+                Some(cost) if cost > gas_bid_info.total_profit => {
+                    metrics::record_skip(SkipReason::GasCap);
+                    anyhow::bail!(
+                        "Estimated cost of tx {} is greater than total profit {}, in the profit token",
+                        cost, gas_bid_info.total_profit
+                    );
+                }
+                Some(_) => {}
+                None => warn!("Can't price gas in the profit token, not checking the tx's cost against its profit"),
             }
-        } else {
-            bid_gas_price = self
-                .client
-                .get_gas_price()
-                .await
-                .context("Error getting gas price: {}")?;
         }
-        info!("bid_gas_price {:?}", bid_gas_price);
-        action.tx.set_gas_price(bid_gas_price);
-        action.tx.set_gas_limit(gas_usage);
-        info!("gas limit {:?}", action.tx.gas_limit().unwrap());
-        let _ = self.sender_client.send_transaction(action.tx).await?;
+        let gas_limit = self.gas_limit(gas_usage).await;
+        self.check_native_reserve(&action.tx, fees.max_fee_per_gas.mul(gas_limit as u128)).await?;
+        info!("fees {:?}", fees);
+        self.set_fees(&mut action.tx, fees);
+        action.tx.set_gas_limit(gas_limit);
+        Ok(action.tx)
+    }
+
+    /// Send a prepared tx to the mempool and watch for its confirmation.
+    pub async fn send(&self, tag: LiquidationTag, mut tx: N::TransactionRequest, total_profit: Option<u128>) -> Result<()> {
+        if self.dry_run {
+            info!(
+                deployment = tag.deployment.name(),
+                positions = ?tag.positions,
+                profit = total_profit,
+                "Dry run, not sending tx for opportunities {:?}: to {:?}, calldata {}, gas limit {:?}, max fee per gas {:?}, expected profit {}",
+                tag.opportunity_ids,
+                tx.to(),
+                tx.input().map_or("0x".to_string(), |input| input.to_string()),
+                tx.gas_limit(),
+                tx.max_fee_per_gas().or(tx.gas_price()),
+                total_profit.map_or("unknown".to_string(), format_wei),
+            );
+            return Ok(());
+        }
+        let pending = self.send_with_nonce(&mut tx).await?;
+        info!(
+            deployment = tag.deployment.name(),
+            positions = ?tag.positions,
+            profit = total_profit,
+            tx_hash = ?pending.tx_hash(),
+            "Sent {:?} for opportunities {:?}", pending.tx_hash(), tag.opportunity_ids
+        );
+        // escalated in the watch task, so the next action isn't held back meanwhile
+        let escalation = (self.tip_escalation_pct > 0).then(|| {
+            let escalation = TipEscalation {
+                sender_client: self.sender_client.clone(),
+                pct: self.tip_escalation_pct,
+                max_priority_fee_wei: self.max_priority_fee_wei,
+                timeout: self.tip_escalation_timeout,
+                _network_transport: ::core::marker::PhantomData,
+            };
+            (escalation, tx)
+        });
+        self.watch(tag, total_profit, pending, escalation);
+        Ok(())
+    }
+
+    /// Assign nonces explicitly so a tx that fails to go out doesn't leave
+    /// a gap that wedges every later submission behind it.
+    async fn send_with_nonce(&self, tx: &mut N::TransactionRequest) -> Result<PendingTransactionBuilder<T, N>> {
+        let mut next_nonce = self.next_nonce.lock().await;
+        let mut last_submission = self.last_submission.lock().await;
+        if let (Some(idle), Some(last)) = (self.nonce_resync_idle, *last_submission) {
+            if last.elapsed() > idle && next_nonce.is_some() {
+                info!("Idle for {:?}, resyncing nonce from chain", last.elapsed());
+                *next_nonce = None;
+            }
+        }
+        let from = tx.from().context("Tx is missing a sender")?;
+        let cached_nonce = if self.external_nonce_coordination { None } else { *next_nonce };
+        let mut nonce = match cached_nonce {
+            Some(nonce) => nonce,
+            None => {
+                let nonce = self.pending_nonce(from).await?;
+                if !self.external_nonce_coordination {
+                    info!("Nonce of {:?} synced from chain, sending with nonce {}", from, nonce);
+                }
+                nonce
+            }
+        };
+        let mut resynced = false;
+        loop {
+            tx.set_nonce(nonce);
+            match self.sender_client.send_transaction(tx.clone()).await {
+                Ok(pending) => {
+                    *next_nonce = Some(nonce + 1);
+                    *last_submission = Some(Instant::now());
+                    return Ok(pending);
+                }
+                // the cached nonce drifted from the chain, resync and retry once
+                Err(e) if !resynced && is_nonce_error(&e.to_string()) => {
+                    let chain_nonce = self.pending_nonce(from).await?;
+                    warn!("Submission with nonce {} failed ({}), retrying with chain nonce {}", nonce, e, chain_nonce);
+                    nonce = chain_nonce;
+                    resynced = true;
+                }
+                Err(e) => {
+                    warn!("Submission with nonce {} failed, releasing it for the next tx: {}", nonce, e);
+                    *next_nonce = Some(nonce);
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    /// Take the next nonce of `from` for a tx sent outside [send](Self::send),
+    /// from the same local cache, so both kinds of submissions share one
+    /// sequence. Hand it back with [release_nonce](Self::release_nonce) when the
+    /// tx doesn't go out.
+    pub async fn reserve_nonce(&self, from: Address) -> Result<u64> {
+        let mut next_nonce = self.next_nonce.lock().await;
+        let cached_nonce = if self.external_nonce_coordination { None } else { *next_nonce };
+        let nonce = match cached_nonce {
+            Some(nonce) => nonce,
+            None => self.pending_nonce(from).await?,
+        };
+        *next_nonce = Some(nonce + 1);
+        *self.last_submission.lock().await = Some(Instant::now());
+        Ok(nonce)
+    }
+
+    /// Give back a nonce from [reserve_nonce](Self::reserve_nonce) whose tx
+    /// didn't go out, or resync from the chain on the next tx when it was
+    /// refused for its nonce.
+    pub async fn release_nonce(&self, nonce: u64, error: &str) {
+        let mut next_nonce = self.next_nonce.lock().await;
+        if is_nonce_error(error) {
+            *next_nonce = None;
+        } else if *next_nonce == Some(nonce + 1) {
+            *next_nonce = Some(nonce);
+        }
+    }
+
+    async fn pending_nonce(&self, from: Address) -> Result<u64> {
+        self.client
+            .get_transaction_count(from)
+            .pending()
+            .await
+            .context("Error getting transaction count: {}")
+    }
+
+    /// Log and count the outcome of a liquidation against its opportunity ids
+    /// and report it to the strategy. A tx without a receipt after the receipt
+    /// timeout is reported as dropped so its positions can be sent again, as is
+    /// one reorged out before reaching --confirmations, and either resyncs the
+    /// nonce from the chain, as the dropped tx's went unused. Once confirmed, the
+    /// liquidator's token deltas are reported and the fee share of their value
+    /// net of the gas actually paid, both priced in the profit token, is
+    /// accrued; reverted or dropped txs accrue nothing.
+    pub fn watch_confirmation(&self, tag: LiquidationTag, total_profit: Option<u128>, pending: PendingTransactionBuilder<T, N>) {
+        self.watch(tag, total_profit, pending, None)
+    }
+
+    /// [watch_confirmation](Self::watch_confirmation), escalating the tip of
+    /// `escalation`'s tx first.
+    fn watch(
+        &self,
+        tag: LiquidationTag,
+        total_profit: Option<u128>,
+        pending: PendingTransactionBuilder<T, N>,
+        escalation: Option<(TipEscalation<T, P, N>, N::TransactionRequest)>,
+    ) {
+        let fee_share_bps = self.fee_share.as_ref().map(|fee_share| fee_share.bps);
+        let accrued_fee = self.accrued_fee.clone();
+        let outcomes = self.outcomes.clone();
+        let receipt_timeout = self.receipt_timeout;
+        let confirmations = self.confirmations;
+        let next_nonce = self.next_nonce.clone();
+        let provider = pending.provider().clone();
+        let tx_hash = *pending.tx_hash();
+        alerts::submission_succeeded();
+        // tracked until settled so a shutdown can wait for the outcome
+        shutdown::track_tx(tx_hash, tag.opportunity_ids.clone());
+        tokio::spawn(async move {
+            let report = |outcome| {
+                if let Some(outcomes) = &outcomes {
+                    let _ = outcomes.send(outcome);
+                }
+            };
+            let watch = async {
+                let opportunity_ids = tag.opportunity_ids.clone();
+                let pending = match escalation {
+                    Some((escalation, tx)) => escalation.run(&tag, tx, pending, total_profit).await,
+                    None => pending,
+                };
+                let receipt = match tokio::time::timeout(receipt_timeout, pending.get_receipt()).await {
+                    Ok(Ok(receipt)) => receipt,
+                    Ok(Err(e)) => {
+                        warn!("No receipt for {:?}, opportunities {:?}: {}", tx_hash, opportunity_ids, e);
+                        return;
+                    }
+                    Err(_) => {
+                        metrics::record_dropped(opportunity_ids.len() as u64);
+                        warn!(
+                            deployment = tag.deployment.name(),
+                            positions = ?tag.positions,
+                            tx_hash = ?tx_hash,
+                            "No receipt for {:?} after {:?}, considering it dropped, opportunities {:?}",
+                            tx_hash, receipt_timeout, opportunity_ids
+                        );
+                        resync_after_drop(&next_nonce, tx_hash).await;
+                        report(LiquidationOutcome::Dropped(LiquidationDropped { tag, tx_hash }));
+                        return;
+                    }
+                };
+                let receipt = if confirmations > 1 {
+                    match wait_for_confirmations(&provider, tx_hash, confirmations, receipt_timeout).await {
+                        Ok(Some(receipt)) => receipt,
+                        Ok(None) => {
+                            metrics::record_dropped(opportunity_ids.len() as u64);
+                            warn!(
+                                deployment = tag.deployment.name(),
+                                positions = ?tag.positions,
+                                tx_hash = ?tx_hash,
+                                "Liquidation {:?} reorged out before {} confirmations, considering it dropped, opportunities {:?}",
+                                tx_hash, confirmations, opportunity_ids
+                            );
+                            resync_after_drop(&next_nonce, tx_hash).await;
+                            report(LiquidationOutcome::Dropped(LiquidationDropped { tag, tx_hash }));
+                            return;
+                        }
+                        Err(e) => {
+                            warn!("Failed waiting for confirmations of {:?}, opportunities {:?}: {}", tx_hash, opportunity_ids, e);
+                            return;
+                        }
+                    }
+                } else {
+                    receipt
+                };
+                let gas_used = receipt.gas_used() as u64;
+                let effective_gas_price = receipt.effective_gas_price();
+                if !receipt.status() {
+                    metrics::record_reverted(opportunity_ids.len() as u64);
+                    warn!(
+                        deployment = tag.deployment.name(),
+                        positions = ?tag.positions,
+                        tx_hash = ?tx_hash,
+                        "Liquidation {:?} reverted, gas used {}, effective gas price {}, opportunities {:?}",
+                        tx_hash, gas_used, effective_gas_price, opportunity_ids
+                    );
+                    report(LiquidationOutcome::Reverted(LiquidationReverted { tag, tx_hash, gas_used, effective_gas_price }));
+                    return;
+                }
+                metrics::record_succeeded(opportunity_ids.len() as u64);
+                info!(
+                    deployment = tag.deployment.name(),
+                    positions = ?tag.positions,
+                    profit = total_profit,
+                    tx_hash = ?tx_hash,
+                    "Liquidation {:?} confirmed, gas used {}, effective gas price {}, opportunities {:?}",
+                    tx_hash, gas_used, effective_gas_price, opportunity_ids
+                );
+                alerts::send(format!(
+                    "Liquidation {:?} confirmed on {}: {} positions, expected profit {}, gas cost {}",
+                    tx_hash,
+                    tag.deployment.name(),
+                    tag.positions.len(),
+                    total_profit.map_or("unknown".to_string(), format_wei),
+                    format_wei(gas_used as u128 * effective_gas_price),
+                ));
+
+                let token_deltas = match token_deltas(&provider, tx_hash, receipt.from()).await {
+                    Ok(token_deltas) => token_deltas,
+                    Err(e) => {
+                        warn!("Failed to read token deltas of {:?}: {}", tx_hash, e);
+                        return;
+                    }
+                };
+                info!("Liquidation {:?} token deltas {:?}", tx_hash, token_deltas);
+                // what the tx actually earned, its token deltas net of the gas it
+                // paid, both valued in the profit token
+                let valuation = tag.valuation.clone();
+                report(LiquidationOutcome::Confirmed(LiquidationConfirmed {
+                    tag,
+                    tx_hash,
+                    block_number: receipt.block_number(),
+                    gas_used,
+                    effective_gas_price,
+                    token_deltas: token_deltas.clone(),
+                }));
+                let Some(valuation) = valuation else {
+                    warn!("Liquidation {:?} has no prices, can't value its profit", tx_hash);
+                    return;
+                };
+                let realized = valuation
+                    .value_deltas(&token_deltas)
+                    .zip(valuation.gas_cost(gas_used as u128 * effective_gas_price));
+                let Some((gross_profit, gas_cost)) = realized else {
+                    warn!("Can't value the token deltas or gas of liquidation {:?} in the profit token", tx_hash);
+                    return;
+                };
+                let net_profit = gross_profit.saturating_sub(I256::try_from(gas_cost).unwrap_or(I256::MAX));
+                metrics::record_profit(net_profit);
+                let net_profit: u128 = net_profit.max(I256::ZERO).try_into().unwrap_or(u128::MAX);
+
+                let Some(bps) = fee_share_bps else {
+                    return;
+                };
+                // the fee token is the profit token, turn the share into its own decimals
+                let share = U256::from(net_profit) * U256::from(bps) / U256::from(10_000);
+                let share = from_common_unit(share, valuation.profit_decimals);
+                let mut accrued_fee = accrued_fee.lock().await;
+                *accrued_fee += share;
+                info!(
+                    "Liquidation {:?} realized net profit {}, fee share {} accrued, {} pending transfer",
+                    tx_hash, net_profit, share, *accrued_fee
+                );
+            };
+            watch.await;
+            shutdown::settle_tx(tx_hash);
+        });
+    }
+
+    /// Transfer the accrued fee share to the recipient once it reaches the
+    /// batch minimum, so small shares don't each pay for a transfer.
+    pub async fn flush_fee_share(&self, from: Address) -> Result<()> {
+        let Some(fee_share) = &self.fee_share else {
+            return Ok(());
+        };
+        let mut accrued_fee = self.accrued_fee.lock().await;
+        if accrued_fee.is_zero() || *accrued_fee < fee_share.batch_min {
+            return Ok(());
+        }
+        let amount = *accrued_fee;
+        let input = IERC20::transferCall { to: fee_share.recipient, value: amount }.abi_encode();
+        let mut tx = N::TransactionRequest::default()
+            .with_from(from)
+            .with_to(fee_share.token)
+            .with_input(input);
+        let gas_usage = self.estimate_gas(&tx).await?;
+        let fees = self.estimate_fees().await?;
+        self.check_native_reserve(&tx, fees.max_fee_per_gas.mul(gas_usage as u128)).await?;
+        self.set_fees(&mut tx, fees);
+        tx.set_gas_limit(gas_usage);
+        let pending = self.send_with_nonce(&mut tx).await?;
+        *accrued_fee = U256::ZERO;
+        info!(
+            "Fee transfer of {} token {:?} to {:?} sent in {:?}",
+            amount, fee_share.token, fee_share.recipient, pending.tx_hash()
+        );
         Ok(())
     }
 }
+
+/// Wait until the tx is `confirmations` blocks deep and return its receipt then,
+/// which may differ from the first one if a reorg moved the tx to another
+/// block. None when the tx stays out of the chain for `timeout` after a reorg.
+async fn wait_for_confirmations<T, N>(
+    provider: &RootProvider<T, N>,
+    tx_hash: B256,
+    confirmations: u64,
+    timeout: Duration,
+) -> Result<Option<N::ReceiptResponse>>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    N: alloy_contract::private::Network,
+{
+    let mut missing_since: Option<Instant> = None;
+    loop {
+        let head = provider.get_block_number().await?;
+        match provider.get_transaction_receipt(tx_hash).await? {
+            Some(receipt) => {
+                missing_since = None;
+                let block_number = receipt.block_number().unwrap_or(head);
+                if head + 1 >= block_number + confirmations {
+                    return Ok(Some(receipt));
+                }
+            }
+            None => {
+                let since = *missing_since.get_or_insert_with(|| {
+                    warn!("Liquidation {:?} is no longer in the chain, waiting for it to be mined again", tx_hash);
+                    Instant::now()
+                });
+                if since.elapsed() >= timeout {
+                    return Ok(None);
+                }
+            }
+        }
+        tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+    }
+}
+
+/// Resends a tx left out of the chain with a higher tip.
+struct TipEscalation<T, P, N> {
+    sender_client: Arc<P>,
+    pct: u64,
+    max_priority_fee_wei: u128,
+    timeout: Duration,
+    _network_transport: ::core::marker::PhantomData<(N, T)>,
+}
+
+impl<
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, N>,
+    N: alloy_contract::private::Network,
+> TipEscalation<T, P, N> {
+    /// Resend a tx left out of the chain for --tip-escalation-timeout-secs with
+    /// the same nonce and its tip raised by --tip-escalation-pct, until a copy
+    /// lands, the tip reaches --max-priority-fee or the worst-case gas cost,
+    /// priced in the profit token, would exceed the profit. Receipts are polled
+    /// meanwhile, so a copy that lands early ends it early. Returns the copy that
+    /// landed, or the last one sent.
+    async fn run(
+        self,
+        tag: &LiquidationTag,
+        mut tx: N::TransactionRequest,
+        mut pending: PendingTransactionBuilder<T, N>,
+        total_profit: Option<u128>,
+    ) -> PendingTransactionBuilder<T, N> {
+        let provider = pending.provider().clone();
+        let mut sent = vec![*pending.tx_hash()];
+        let mut escalate_at = Instant::now() + self.timeout;
+        loop {
+            // any copy may land, not only the last one sent
+            for tx_hash in &sent {
+                if let Ok(Some(_)) = provider.get_transaction_receipt(*tx_hash).await {
+                    return PendingTransactionBuilder::new(provider, *tx_hash);
+                }
+            }
+            if shutdown::requested() {
+                return pending;
+            }
+            let now = Instant::now();
+            if now < escalate_at {
+                tokio::time::sleep(std::cmp::min(CONFIRMATION_POLL_INTERVAL, escalate_at - now)).await;
+                continue;
+            }
+            let (Some(tip), Some(max_fee), Some(gas_limit)) =
+                (tx.max_priority_fee_per_gas(), tx.max_fee_per_gas(), tx.gas_limit())
+            else {
+                return pending;
+            };
+            if tip >= self.max_priority_fee_wei {
+                info!("{:?} not included, tip {} already at the cap, not escalating", pending.tx_hash(), tip);
+                return pending;
+            }
+            let bumped_tip = std::cmp::min(
+                std::cmp::max(tip * (100 + self.pct as u128) / 100, tip + 1),
+                self.max_priority_fee_wei,
+            );
+            // nodes only replace a tx when both fees go up
+            let bumped_max_fee = std::cmp::max(
+                max_fee * (100 + self.pct as u128) / 100,
+                max_fee + (bumped_tip - tip),
+            );
+            let gas_wei = bumped_max_fee.saturating_mul(gas_limit as u128);
+            let gas_cost = tag.valuation.as_ref().and_then(|valuation| valuation.gas_cost(gas_wei));
+            match (gas_cost, total_profit) {
+                (Some(gas_cost), Some(profit)) if gas_cost <= profit => {}
+                (Some(gas_cost), Some(profit)) => {
+                    info!(
+                        "{:?} not included, a tip of {} would cost up to {} for a profit of {}, in the profit token, not escalating",
+                        pending.tx_hash(), bumped_tip, gas_cost, profit
+                    );
+                    return pending;
+                }
+                _ => {
+                    info!("{:?} not included, can't price a higher tip against its profit, not escalating", pending.tx_hash());
+                    return pending;
+                }
+            }
+            tx.set_max_fee_per_gas(bumped_max_fee);
+            tx.set_max_priority_fee_per_gas(bumped_tip);
+            match self.sender_client.send_transaction(tx.clone()).await {
+                Ok(replacement) => {
+                    info!(
+                        deployment = tag.deployment.name(),
+                        positions = ?tag.positions,
+                        tx_hash = ?replacement.tx_hash(),
+                        "{:?} not included, replaced by {:?} with tip {} and max fee {}, opportunities {:?}",
+                        pending.tx_hash(), replacement.tx_hash(), bumped_tip, bumped_max_fee, tag.opportunity_ids
+                    );
+                    sent.push(*replacement.tx_hash());
+                    pending = replacement;
+                    escalate_at = Instant::now() + self.timeout;
+                }
+                Err(e) => {
+                    // usually an earlier copy just landed, taking the nonce
+                    warn!("Replacing {:?} with tip {} failed, not escalating: {}", pending.tx_hash(), bumped_tip, e);
+                    return pending;
+                }
+            }
+        }
+    }
+}
+
+/// Net ERC20 transfers to and from `account` in a tx, per token.
+async fn token_deltas<T, N>(provider: &RootProvider<T, N>, tx_hash: B256, account: Address) -> Result<Vec<(Address, I256)>>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    N: alloy_contract::private::Network,
+{
+    let receipt: ReceiptLogs = provider.client().request("eth_getTransactionReceipt", (tx_hash,)).await?;
+    let mut deltas: BTreeMap<Address, I256> = BTreeMap::new();
+    for log in receipt.logs {
+        let Ok(transfer) = log.log_decode::<IERC20::Transfer>() else {
+            continue;
+        };
+        let token = transfer.inner.address;
+        let transfer = transfer.inner.data;
+        let value = I256::try_from(transfer.value).unwrap_or(I256::MAX);
+        if transfer.to == account {
+            *deltas.entry(token).or_default() += value;
+        }
+        if transfer.from == account {
+            *deltas.entry(token).or_default() -= value;
+        }
+    }
+    Ok(deltas.into_iter().filter(|(_, delta)| !delta.is_zero()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        network::Ethereum,
+        providers::ProviderBuilder,
+        transports::http::{Client, Http},
+    };
+    use mockito::{Matcher, Server, ServerGuard};
+    use serde_json::{json, Value};
+
+    type HttpExecutor = ProtectExecutor<Http<Client>, RootProvider<Http<Client>>, Ethereum>;
+
+    // answer calls of `method` with `reply`, a `result` or an `error`, echoing the request id
+    async fn mock_rpc(server: &mut ServerGuard, method: &str, reply: Value) -> mockito::Mock {
+        mock_rpc_matching(server, Matcher::PartialJson(json!({ "method": method })), reply).await
+    }
+
+    async fn mock_rpc_matching(server: &mut ServerGuard, body: Matcher, reply: Value) -> mockito::Mock {
+        mock_rpc_replies(server, body, move || reply.clone()).await
+    }
+
+    // answer each matching call with the next of `replies`
+    async fn mock_rpc_replies(
+        server: &mut ServerGuard,
+        body: Matcher,
+        replies: impl Fn() -> Value + Send + Sync + 'static,
+    ) -> mockito::Mock {
+        server
+            .mock("POST", "/")
+            .match_body(body)
+            .with_header("content-type", "application/json")
+            .with_body_from_request(move |request| {
+                let id = request
+                    .body()
+                    .ok()
+                    .and_then(|body| serde_json::from_slice::<Value>(body).ok())
+                    .map_or(Value::Null, |body| body["id"].clone());
+                let mut response = json!({ "jsonrpc": "2.0", "id": id });
+                for (key, value) in replies().as_object().unwrap() {
+                    response[key] = value.clone();
+                }
+                response.to_string().into_bytes()
+            })
+            .create_async()
+            .await
+    }
+
+    // a chain at block 1 that never mines anything
+    async fn idle_chain(server: &mut ServerGuard) {
+        mock_rpc(server, "eth_blockNumber", json!({ "result": "0x1" })).await;
+        mock_rpc(server, "eth_newBlockFilter", json!({ "result": "0x1" })).await;
+        mock_rpc(server, "eth_getFilterChanges", json!({ "result": [] })).await;
+    }
+
+    // an executor sending through `server`, with a 1s receipt timeout
+    fn executor(server: &ServerGuard, outcomes: Option<broadcast::Sender<LiquidationOutcome>>) -> HttpExecutor {
+        let provider = Arc::new(ProviderBuilder::new().on_http(server.url().parse().unwrap()));
+        ProtectExecutor::new(
+            provider.clone(),
+            provider,
+            0,
+            GasMode::Eip1559,
+            false,
+            false,
+            false,
+            1,
+            0,
+            0,
+            0,
+            U256::ZERO,
+            None,
+            false,
+            1,
+            1.0,
+            1,
+            0,
+            0,
+            0,
+            outcomes,
+        )
+    }
+
+    fn tag() -> LiquidationTag {
+        LiquidationTag {
+            deployment: Deployment::LOCALNET,
+            opportunity_ids: vec![B256::repeat_byte(1)],
+            positions: vec![B256::repeat_byte(2)],
+            valuation: None,
+        }
+    }
+
+    fn unit(decimals: u8) -> U256 {
+        U256::from(10).pow(U256::from(decimals))
+    }
+
+    // one native token is worth 2000 profit tokens
+    fn valuation() -> Valuation {
+        Valuation {
+            profit_decimals: 6,
+            native_price: Some(unit(COMMON_DECIMALS) * U256::from(2000)),
+            token_prices: BTreeMap::from([
+                (Address::repeat_byte(1), (6, unit(COMMON_DECIMALS))),
+                (Address::repeat_byte(2), (18, unit(COMMON_DECIMALS) / U256::from(2))),
+            ]),
+        }
+    }
+
+    #[test]
+    fn gas_is_priced_in_the_profit_token() {
+        let valuation = valuation();
+        // 0.001 native is 2 profit tokens
+        let wei = 10u128.pow(15);
+        let cost = 2 * 10u128.pow(18);
+        assert_eq!(valuation.gas_cost(wei), Some(cost));
+        assert_eq!(valuation.to_wei(cost), Some(wei));
+        let unpriced = Valuation { native_price: None, ..valuation };
+        assert_eq!(unpriced.gas_cost(wei), None);
+        assert_eq!(unpriced.to_wei(cost), None);
+    }
+
+    #[test]
+    fn deltas_are_valued_in_the_profit_token() {
+        let valuation = valuation();
+        // +3 of the 6 decimal token at 1, -2 of the 18 decimal one at 0.5
+        let deltas = [
+            (Address::repeat_byte(1), I256::try_from(3_000_000).unwrap()),
+            (Address::repeat_byte(2), -I256::try_from(2 * 10u128.pow(18)).unwrap()),
+        ];
+        assert_eq!(valuation.value_deltas(&deltas), Some(I256::try_from(2 * 10u128.pow(18)).unwrap()));
+        assert_eq!(valuation.value_deltas(&[(Address::repeat_byte(3), I256::ONE)]), None);
+    }
+
+    #[tokio::test]
+    async fn a_dropped_liquidation_resyncs_the_nonce() {
+        let mut server = Server::new_async().await;
+        idle_chain(&mut server).await;
+        mock_rpc(&mut server, "eth_getTransactionReceipt", json!({ "result": null })).await;
+        let (outcomes, mut outcome) = broadcast::channel(1);
+        let executor = executor(&server, Some(outcomes));
+        // nonces 6 and 7 went out from the cache, 7 is never mined
+        *executor.next_nonce.lock().await = Some(8);
+
+        let tx_hash = B256::repeat_byte(3);
+        let pending = PendingTransactionBuilder::new(executor.client.root().clone(), tx_hash);
+        executor.watch_confirmation(tag(), None, pending);
+
+        let Ok(LiquidationOutcome::Dropped(dropped)) = outcome.recv().await else {
+            panic!("expected the liquidation to be dropped");
+        };
+        assert_eq!(dropped.tx_hash, tx_hash);
+        assert_eq!(*executor.next_nonce.lock().await, None);
+    }
+
+    #[tokio::test]
+    async fn a_nonce_too_low_resyncs_and_retries_once() {
+        let mut server = Server::new_async().await;
+        mock_rpc(&mut server, "eth_getTransactionCount", json!({ "result": "0x5" })).await;
+        let send = |nonce: &str| {
+            Matcher::AllOf(vec![
+                Matcher::PartialJson(json!({ "method": "eth_sendTransaction" })),
+                Matcher::Regex(format!(r#""nonce":"{}""#, nonce)),
+            ])
+        };
+        // the cached nonce 3 was taken by txs sent from elsewhere
+        let stale = mock_rpc_matching(
+            &mut server,
+            send("0x3"),
+            json!({ "error": { "code": -32000, "message": "nonce too low" } }),
+        )
+        .await;
+        let tx_hash = B256::repeat_byte(4);
+        let resent = mock_rpc_matching(&mut server, send("0x5"), json!({ "result": tx_hash })).await;
+        let executor = executor(&server, None);
+        *executor.next_nonce.lock().await = Some(3);
+
+        let mut tx = alloy::rpc::types::TransactionRequest::default().with_from(Address::repeat_byte(5));
+        let pending = executor.send_with_nonce(&mut tx).await.unwrap();
+
+        assert_eq!(*pending.tx_hash(), tx_hash);
+        assert_eq!(*executor.next_nonce.lock().await, Some(6));
+        stale.assert_async().await;
+        resent.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_dropped_liquidation_mid_sequence_does_not_wedge_the_next() {
+        let mut server = Server::new_async().await;
+        idle_chain(&mut server).await;
+        mock_rpc(&mut server, "eth_getTransactionReceipt", json!({ "result": null })).await;
+        // the dropped nonce 4 is still the next one on chain
+        mock_rpc(&mut server, "eth_getTransactionCount", json!({ "result": "0x4" })).await;
+        let send = |nonce: &str| {
+            Matcher::AllOf(vec![
+                Matcher::PartialJson(json!({ "method": "eth_sendTransaction" })),
+                Matcher::Regex(format!(r#""nonce":"{}""#, nonce)),
+            ])
+        };
+        mock_rpc_matching(&mut server, send("0x3"), json!({ "result": B256::repeat_byte(1) })).await;
+        let sends_of_4 = Arc::new(std::sync::atomic::AtomicU8::new(0));
+        let counted = sends_of_4.clone();
+        mock_rpc_replies(&mut server, send("0x4"), move || {
+            json!({ "result": B256::repeat_byte(2 + counted.fetch_add(1, Ordering::SeqCst)) })
+        })
+        .await;
+        let (outcomes, mut outcome) = broadcast::channel(1);
+        let executor = executor(&server, Some(outcomes));
+        *executor.next_nonce.lock().await = Some(3);
+        let mut tx = alloy::rpc::types::TransactionRequest::default().with_from(Address::repeat_byte(5));
+
+        // #1 and #2 go out with 3 and 4
+        executor.send_with_nonce(&mut tx).await.unwrap();
+        let second = executor.send_with_nonce(&mut tx).await.unwrap();
+        assert_eq!(*second.tx_hash(), B256::repeat_byte(2));
+        assert_eq!(*executor.next_nonce.lock().await, Some(5));
+
+        // #2 never lands, the cache resyncs instead of sending #3 with the gapped 5
+        executor.watch_confirmation(tag(), None, second);
+        let Ok(LiquidationOutcome::Dropped(_)) = outcome.recv().await else {
+            panic!("expected #2 to be dropped");
+        };
+        assert_eq!(*executor.next_nonce.lock().await, None);
+
+        let third = executor.send_with_nonce(&mut tx).await.unwrap();
+        assert_eq!(*third.tx_hash(), B256::repeat_byte(3));
+        assert_eq!(sends_of_4.load(Ordering::SeqCst), 2);
+        assert_eq!(*executor.next_nonce.lock().await, Some(5));
+    }
+
+    #[tokio::test]
+    async fn the_first_submission_after_an_idle_period_resyncs_the_nonce() {
+        let mut server = Server::new_async().await;
+        // txs sent from elsewhere while the bot was idle took nonces 3 to 6
+        mock_rpc(&mut server, "eth_getTransactionCount", json!({ "result": "0x7" })).await;
+        let tx_hash = B256::repeat_byte(4);
+        let sent = mock_rpc_matching(
+            &mut server,
+            Matcher::AllOf(vec![
+                Matcher::PartialJson(json!({ "method": "eth_sendTransaction" })),
+                Matcher::Regex(r#""nonce":"0x7""#.to_string()),
+            ]),
+            json!({ "result": tx_hash }),
+        )
+        .await;
+        let mut executor = executor(&server, None);
+        executor.nonce_resync_idle = Some(Duration::from_secs(60));
+        *executor.next_nonce.lock().await = Some(3);
+        *executor.last_submission.lock().await = Instant::now().checked_sub(Duration::from_secs(120));
+
+        let mut tx = alloy::rpc::types::TransactionRequest::default().with_from(Address::repeat_byte(5));
+        let pending = executor.send_with_nonce(&mut tx).await.unwrap();
+
+        assert_eq!(*pending.tx_hash(), tx_hash);
+        assert_eq!(*executor.next_nonce.lock().await, Some(8));
+        sent.assert_async().await;
+    }
+
+    // a successful receipt of `tx_hash` mined in `block_number`
+    fn receipt(tx_hash: B256, block_number: u64) -> Value {
+        json!({
+            "type": "0x2",
+            "status": "0x1",
+            "cumulativeGasUsed": "0x5208",
+            "logs": [],
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+            "transactionHash": tx_hash,
+            "transactionIndex": "0x0",
+            "blockHash": B256::repeat_byte(0x11),
+            "blockNumber": format!("0x{:x}", block_number),
+            "gasUsed": "0x5208",
+            "effectiveGasPrice": "0x1",
+            "from": Address::repeat_byte(0x22),
+            "to": Address::repeat_byte(0x33),
+            "contractAddress": null,
+        })
+    }
+
+    #[tokio::test]
+    async fn confirms_once_the_receipt_is_deep_enough() {
+        let mut server = Server::new_async().await;
+        let tx_hash = B256::repeat_byte(5);
+        // the head moves from 11 to 12 between polls
+        let head = Arc::new(std::sync::atomic::AtomicU64::new(11));
+        let polled = head.clone();
+        mock_rpc_replies(
+            &mut server,
+            Matcher::PartialJson(json!({ "method": "eth_blockNumber" })),
+            move || json!({ "result": format!("0x{:x}", head.fetch_add(1, Ordering::SeqCst)) }),
+        )
+        .await;
+        mock_rpc(&mut server, "eth_getTransactionReceipt", json!({ "result": receipt(tx_hash, 10) })).await;
+        let provider = ProviderBuilder::new().on_http(server.url().parse().unwrap());
+
+        // mined in 10, 3 confirmations need head 12
+        let confirmed = wait_for_confirmations(&provider, tx_hash, 3, Duration::from_secs(60)).await.unwrap();
+
+        assert_eq!(confirmed.map(|receipt| receipt.transaction_hash), Some(tx_hash));
+        // not confirmed at head 11, confirmed on the next poll
+        assert_eq!(polled.load(Ordering::SeqCst), 13);
+    }
+
+    #[tokio::test]
+    async fn a_liquidation_reorged_out_is_dropped() {
+        let mut server = Server::new_async().await;
+        let tx_hash = B256::repeat_byte(5);
+        mock_rpc(&mut server, "eth_blockNumber", json!({ "result": "0xc" })).await;
+        // the receipt is gone after the reorg and never comes back
+        mock_rpc(&mut server, "eth_getTransactionReceipt", json!({ "result": null })).await;
+        let provider = ProviderBuilder::new().on_http(server.url().parse().unwrap());
+
+        let confirmed = wait_for_confirmations(&provider, tx_hash, 3, Duration::ZERO).await.unwrap();
+
+        assert!(confirmed.is_none());
+    }
+}
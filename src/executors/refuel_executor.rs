@@ -0,0 +1,80 @@
+use std::sync::Arc;
+use tracing::info;
+
+use anyhow::{Context, Result};
+use artemis_core::types::Executor;
+use async_trait::async_trait;
+use alloy::{
+    contract as alloy_contract,
+    network::TransactionBuilder,
+    primitives::{Address, U256},
+};
+
+use crate::format::format_amount;
+
+/// Native token to move from the funding source to the liquidator.
+#[derive(Debug, Clone)]
+pub struct Refuel {
+    pub amount: U256,
+}
+
+/// An executor that tops up the liquidator's gas balance from a funding
+/// account signed for by its own key.
+pub struct RefuelExecutor<T, P, N = alloy_contract::private::Ethereum> {
+    funding_client: Arc<P>,
+    funding_source: Address,
+    liquidator: Address,
+    max_refuel_wei: U256,
+    _network_transport: ::core::marker::PhantomData<(N, T)>,
+}
+
+impl<
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, N>,
+    N: alloy_contract::private::Network,
+> RefuelExecutor<T, P, N> {
+    pub fn new(funding_client: Arc<P>, funding_source: Address, liquidator: Address, max_refuel_wei: U256) -> Self {
+        Self {
+            funding_client,
+            funding_source,
+            liquidator,
+            max_refuel_wei,
+            _network_transport: ::core::marker::PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, N>,
+    N: alloy_contract::private::Network,
+> Executor<Refuel> for RefuelExecutor<T, P, N>
+{
+    /// Transfer native token from the funding source to the liquidator.
+    async fn execute(&self, action: Refuel) -> Result<()> {
+        if action.amount.is_zero() || action.amount > self.max_refuel_wei {
+            anyhow::bail!(
+                "Refusing refuel of {}, max is {}",
+                format_amount(action.amount, Some(U256::from(18)), "ETH"),
+                format_amount(self.max_refuel_wei, Some(U256::from(18)), "ETH")
+            );
+        }
+
+        let tx = N::TransactionRequest::default()
+            .with_from(self.funding_source)
+            .with_to(self.liquidator)
+            .with_value(action.amount);
+        let pending = self
+            .funding_client
+            .send_transaction(tx)
+            .await
+            .context("Error sending refuel tx: {}")?;
+        info!(
+            "Refuel {} from {:?} to {:?}, tx {:?}",
+            format_amount(action.amount, Some(U256::from(18)), "ETH"),
+            self.funding_source, self.liquidator, pending.tx_hash()
+        );
+        Ok(())
+    }
+}
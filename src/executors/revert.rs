@@ -0,0 +1,243 @@
+use alloy::{
+    dyn_abi::{DynSolType, DynSolValue},
+    sol_types::{decode_revert_reason, SolError, SolInterface, SolType},
+};
+use crate::metrics::FailureKind;
+use bindings_mm::{
+    errors::Errors::ErrorsErrors,
+    exchangerouter::ExchangeRouter::ExchangeRouterErrors,
+    ierc20errors::IERC20Errors::IERC20ErrorsErrors,
+    liquidationhandler::LiquidationHandler::LiquidationHandlerErrors,
+    safeerc20::SafeERC20::SafeERC20Errors,
+};
+
+/// Return from the calling function with the rendered error if `data` decodes
+/// as one of the listed error enums. The matches over their variants are
+/// exhaustive, so an error added to the bindings won't compile until it's
+/// listed here too.
+macro_rules! decode_errors {
+    ($selector:expr, $data:expr, $($errors:ident { $($variant:ident),* $(,)? }),* $(,)?) => {
+        $(
+            if let Ok(error) = <$errors as SolInterface>::abi_decode_raw($selector, $data, true) {
+                return Some(match error {
+                    $($errors::$variant(error) => format_error(&error),)*
+                });
+            }
+        )*
+    };
+}
+
+/// Decode revert data into a readable reason like
+/// `SafeERC20FailedOperation(0x...)`, trying the protocol's generated error
+/// bindings first and then the standard `Error(string)` and `Panic(uint256)`.
+pub fn decode_revert(data: &[u8]) -> Option<String> {
+    let (selector, args) = split_selector(data)?;
+    decode_errors!(
+        selector,
+        args,
+        SafeERC20Errors {
+            SafeERC20FailedDecreaseAllowance,
+            SafeERC20FailedOperation,
+        },
+        IERC20ErrorsErrors {
+            ERC20InsufficientAllowance,
+            ERC20InsufficientBalance,
+            ERC20InvalidApprover,
+            ERC20InvalidReceiver,
+            ERC20InvalidSender,
+            ERC20InvalidSpender,
+        },
+        ExchangeRouterErrors {
+            EmptyReceiver,
+            ReentrancyGuardReentrantCall,
+            Unauthorized,
+        },
+        LiquidationHandlerErrors {
+            AccountNotMatch,
+            EmptyPool,
+            EmptyPosition,
+            MarginAboveThreshold,
+            MathOverflowedMulDiv,
+            PositionNotFound,
+            Unauthorized,
+        },
+        ErrorsErrors {
+            AccountNotMatch,
+            Balance1Insufficient,
+            ClosePercentageExceeding,
+            CollateralCanNotCoverDebt,
+            CompactedArrayOutOfBounds,
+            CouldNotSendNativeToken,
+            DebtTokenOperationNotSupported,
+            EmptyAccount,
+            EmptyAddAmounts,
+            EmptyAmount,
+            EmptyBase,
+            EmptyBorrowAmounts,
+            EmptyBurnAmounts,
+            EmptyCollateral,
+            EmptyConfiguration,
+            EmptyDepositAmounts,
+            EmptyHoldingAddress,
+            EmptyInterestRateStrategy,
+            EmptyMintAmounts,
+            EmptyPool,
+            EmptyPoolKeys,
+            EmptyPosition,
+            EmptyPositions,
+            EmptyReceiver,
+            EmptyRemoveAmounts,
+            EmptyRepayAmounts,
+            EmptySwapInAmount,
+            EmptySwapOutAmount,
+            EmptyTokenBase,
+            EmptyTokenTranferGasLimit,
+            EmptyTreasury,
+            EmptyUnclaimedFee,
+            EmptyWithdrawAmounts,
+            ErrorStep,
+            InsufficientAvailableLiquidity,
+            InsufficientCollateralAmount,
+            InsufficientCollateralAmountForRepay,
+            InsufficientCollateralForWidthdraw,
+            InsufficientReverveForBorrow,
+            InsufficientSwapAmount,
+            InsufficientSwapCollateral,
+            InsufficientUserBalance,
+            InvalidBorrowCapacity,
+            InvalidDecimals,
+            InvalidFeeFactor,
+            InvalidNativeTokenSender,
+            InvalidOptimalUsageRate,
+            InvalidPoolIndex,
+            InvalidSupplyCapacity,
+            MarginAboveThreshold,
+            MarginBelowThreshold,
+            MaxDepositExceeds,
+            MaxPositionId,
+            PoolAlreadyExists,
+            PositionNotFound,
+            RequestedAmount1ExceedsPriceLimit,
+            RequestedAmountOExceedsPriceLimit,
+            Reserve0Insufficient,
+            Reserve1Insufficient,
+            SelfTransferNotSupported,
+            SingleTokenInOutSwapOnly,
+            ThereMustBeAtLeastOneRoleAdmin,
+            ThereMustBeAtLeastOneTimelockMultiSig,
+            TokenIndexNotSupport,
+            TokenTransferError,
+            TokensNotMatch,
+            Unauthorized,
+            UsdCollateralCanNotCoverDebt,
+            UserDoNotHaveDebtInPosition,
+            liquidityDidNotReachShortThreshord,
+        },
+    );
+    decode_revert_reason(data)
+}
+
+// the selector and abi encoded arguments of revert data
+fn split_selector(data: &[u8]) -> Option<([u8; 4], &[u8])> {
+    let selector = data.get(..4)?.try_into().ok()?;
+    Some((selector, &data[4..]))
+}
+
+/// Why a liquidation reverted, deciding whether it is worth retrying.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiquidationFailure {
+    /// A token transfer inside the liquidation failed.
+    SafeERC20FailedOperation,
+    /// Decreasing a token allowance failed.
+    SafeERC20FailedDecreaseAllowance,
+    /// The protocol rejected the liquidation, with the decoded error.
+    Protocol(String),
+    /// The protocol rejected the liquidation on a margin level that moves
+    /// with prices and interest, with the decoded error.
+    MarginState(String),
+    /// No revert data, or none that could be decoded.
+    Unknown,
+}
+
+impl LiquidationFailure {
+    /// Classify the revert data of a failed liquidation.
+    pub fn classify(data: &[u8]) -> Self {
+        let Some((selector, args)) = split_selector(data) else {
+            return LiquidationFailure::Unknown;
+        };
+        match SafeERC20Errors::abi_decode_raw(selector, args, true) {
+            Ok(SafeERC20Errors::SafeERC20FailedOperation(_)) => {
+                return LiquidationFailure::SafeERC20FailedOperation;
+            }
+            Ok(SafeERC20Errors::SafeERC20FailedDecreaseAllowance(_)) => {
+                return LiquidationFailure::SafeERC20FailedDecreaseAllowance;
+            }
+            Err(_) => {}
+        }
+        let Some(reason) = decode_revert(data) else {
+            return LiquidationFailure::Unknown;
+        };
+        match ErrorsErrors::abi_decode_raw(selector, args, true) {
+            Ok(ErrorsErrors::MarginAboveThreshold(_) | ErrorsErrors::MarginBelowThreshold(_)) => {
+                LiquidationFailure::MarginState(reason)
+            }
+            _ => LiquidationFailure::Protocol(reason),
+        }
+    }
+
+    /// Whether the same liquidation may go through on a later try. Token
+    /// transfer and allowance failures depend on balances that change, margin
+    /// level errors on prices and interest, other protocol errors repeat until
+    /// the position itself changes.
+    pub fn is_transient(&self) -> bool {
+        !matches!(self, LiquidationFailure::Protocol(_))
+    }
+
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            LiquidationFailure::SafeERC20FailedOperation => FailureKind::SafeErc20FailedOperation,
+            LiquidationFailure::SafeERC20FailedDecreaseAllowance => FailureKind::SafeErc20FailedDecreaseAllowance,
+            LiquidationFailure::Protocol(_) | LiquidationFailure::MarginState(_) => FailureKind::Protocol,
+            LiquidationFailure::Unknown => FailureKind::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for LiquidationFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LiquidationFailure::Protocol(reason) | LiquidationFailure::MarginState(reason) => {
+                write!(f, "protocol revert {}", reason)
+            }
+            other => write!(f, "{}", other.kind().as_str()),
+        }
+    }
+}
+
+impl std::error::Error for LiquidationFailure {}
+
+// render a decoded error as its name and arguments
+fn format_error<E: SolError>(error: &E) -> String {
+    let name = E::SIGNATURE.split('(').next().unwrap_or(E::SIGNATURE);
+    let values = DynSolType::parse(<E::Parameters<'_> as SolType>::SOL_NAME)
+        .ok()
+        .and_then(|params| params.abi_decode_sequence(&error.abi_encode()[4..]).ok());
+    let values: Vec<String> = match values {
+        Some(DynSolValue::Tuple(values)) => values.iter().map(format_value).collect(),
+        _ => Vec::new(),
+    };
+    format!("{}({})", name, values.join(", "))
+}
+
+fn format_value(value: &DynSolValue) -> String {
+    match value {
+        DynSolValue::Address(address) => address.to_string(),
+        DynSolValue::Uint(value, _) => value.to_string(),
+        DynSolValue::Int(value, _) => value.to_string(),
+        DynSolValue::Bool(value) => value.to_string(),
+        DynSolValue::FixedBytes(value, _) => value.to_string(),
+        DynSolValue::Bytes(value) => format!("0x{}", hex::encode(value)),
+        DynSolValue::String(value) => value.clone(),
+        other => format!("{:?}", other),
+    }
+}
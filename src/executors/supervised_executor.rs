@@ -0,0 +1,54 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tracing::{error, warn};
+
+use anyhow::Result;
+use artemis_core::types::Executor;
+use async_trait::async_trait;
+use futures::FutureExt;
+
+use crate::alerts;
+
+/// An executor that wraps another executor and catches panics while it
+/// executes an action. Without it a panic ends the engine's executor task and
+/// every later action is dropped. The failed action is reported as an error
+/// and the executor keeps serving the next ones; after `max_restarts` panics in
+/// a row the process exits, so an orchestrator can restart it.
+pub struct SupervisedExecutor<A> {
+    name: &'static str,
+    inner: Box<dyn Executor<A>>,
+    max_restarts: u32,
+    panics: AtomicU32,
+}
+
+impl<A> SupervisedExecutor<A> {
+    pub fn new(name: &'static str, inner: Box<dyn Executor<A>>, max_restarts: u32) -> Self {
+        Self { name, inner, max_restarts, panics: AtomicU32::new(0) }
+    }
+}
+
+#[async_trait]
+impl<A: Send + Sync + 'static> Executor<A> for SupervisedExecutor<A> {
+    async fn execute(&self, action: A) -> Result<()> {
+        match AssertUnwindSafe(self.inner.execute(action)).catch_unwind().await {
+            Ok(result) => {
+                self.panics.store(0, Ordering::Relaxed);
+                result
+            }
+            Err(_) => {
+                let panics = self.panics.fetch_add(1, Ordering::Relaxed) + 1;
+                if panics > self.max_restarts {
+                    error!("{} executor panicked {} times in a row, exiting", self.name, panics);
+                    alerts::send(format!("{} executor panicked {} times in a row, exiting", self.name, panics));
+                    // give the alert a moment to go out
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                    std::process::exit(1);
+                }
+                warn!("{} executor panicked, dropping the action (restart #{})", self.name, panics);
+                alerts::send(format!("{} executor panicked, dropping the action", self.name));
+                anyhow::bail!("{} executor panicked", self.name)
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::time::Duration;
+use tower::Service;
+use tracing::{info, warn};
+use alloy::{
+    providers::{Provider, ProviderBuilder, WsConnect},
+    pubsub::PubSubConnect,
+    rpc::{client::RpcClient, json_rpc::{RequestPacket, ResponsePacket}},
+    transports::{
+        http::{reqwest::Url, Client, Http},
+        BoxTransport, RpcError, Transport, TransportError, TransportFut,
+    },
+};
+
+/// Timeout of a single health check ping.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Transport an rpc url's scheme calls for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcScheme {
+    Http,
+    Ws,
+}
+
+impl RpcScheme {
+    /// `http`/`https` or `ws`/`wss`, any other scheme is an error.
+    pub fn detect(url: &Url) -> anyhow::Result<Self> {
+        match url.scheme() {
+            "http" | "https" => Ok(RpcScheme::Http),
+            "ws" | "wss" => Ok(RpcScheme::Ws),
+            scheme => anyhow::bail!("unsupported rpc scheme {} in {}, use http(s) or ws(s)", scheme, url),
+        }
+    }
+}
+
+struct Endpoint {
+    url: Url,
+    transport: BoxTransport,
+    healthy: AtomicBool,
+}
+
+/// A transport over several HTTP or WS RPC endpoints in order of preference.
+/// Requests go to the first healthy endpoint and rotate to the next one on
+/// connection errors. Endpoints are marked healthy again by [health_check],
+/// so traffic returns to the primary once it recovers.
+#[derive(Clone)]
+pub struct FailoverTransport {
+    endpoints: Arc<Vec<Endpoint>>,
+    active: Arc<AtomicUsize>,
+}
+
+impl FailoverTransport {
+    /// Connect to every endpoint, picking the transport from each url's scheme.
+    pub async fn connect(urls: Vec<Url>) -> anyhow::Result<Self> {
+        let mut endpoints = Vec::with_capacity(urls.len());
+        for url in urls {
+            let transport = match RpcScheme::detect(&url)? {
+                RpcScheme::Http => Http::<Client>::new(url.clone()).boxed(),
+                RpcScheme::Ws => WsConnect::new(url.to_string()).into_service().await?.boxed(),
+            };
+            endpoints.push(Endpoint { url, transport, healthy: AtomicBool::new(true) });
+        }
+        Ok(Self { endpoints: Arc::new(endpoints), active: Arc::new(AtomicUsize::new(0)) })
+    }
+
+    /// Healthy endpoints first, each group in order of preference, so a
+    /// request still has somewhere to go when every endpoint looks down.
+    fn candidates(&self) -> Vec<usize> {
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|i| self.endpoints[*i].healthy.load(Ordering::Relaxed));
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    async fn request(self, request: RequestPacket) -> Result<ResponsePacket, TransportError> {
+        let mut last_error = None;
+        for i in self.candidates() {
+            let endpoint = &self.endpoints[i];
+            match endpoint.transport.clone().call(request.clone()).await {
+                Ok(response) => {
+                    let previous = self.active.swap(i, Ordering::Relaxed);
+                    if previous != i {
+                        info!("rpc switched from {} to {}", self.endpoints[previous].url, endpoint.url);
+                    }
+                    return Ok(response);
+                }
+                // the endpoint itself is unreachable or broken, try the next one
+                Err(e @ (RpcError::Transport(_) | RpcError::DeserError { .. })) => {
+                    if endpoint.healthy.swap(false, Ordering::Relaxed) {
+                        warn!("rpc {} failed, demoting it: {}", endpoint.url, e);
+                    }
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| TransportError::local_usage_str("no rpc endpoints configured")))
+    }
+
+    /// Ping every endpoint each `interval`, demoting those that fail or lag
+    /// more than `max_block_lag` blocks behind the highest head seen and
+    /// restoring those that caught up.
+    pub async fn health_check(self, interval: Duration, max_block_lag: u64) {
+        let providers: Vec<_> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| ProviderBuilder::new().on_client(RpcClient::new(endpoint.transport.clone(), false)))
+            .collect();
+        loop {
+            let mut heads = Vec::with_capacity(providers.len());
+            for provider in providers.iter() {
+                let head = tokio::time::timeout(PING_TIMEOUT, provider.get_block_number()).await;
+                heads.push(match head {
+                    Ok(Ok(head)) => Some(head),
+                    _ => None,
+                });
+            }
+            let best = heads.iter().flatten().copied().max().unwrap_or_default();
+            for (endpoint, head) in self.endpoints.iter().zip(heads) {
+                let healthy = head.map_or(false, |head| best.saturating_sub(head) <= max_block_lag);
+                if endpoint.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+                    if healthy {
+                        info!("rpc {} recovered at block {:?}", endpoint.url, head);
+                    } else {
+                        warn!("rpc {} is down or stale at block {:?}, best head {}, demoting it", endpoint.url, head, best);
+                    }
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+impl Service<RequestPacket> for FailoverTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        Box::pin(self.clone().request(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(url: &str) -> anyhow::Result<RpcScheme> {
+        RpcScheme::detect(&url.parse().unwrap())
+    }
+
+    #[test]
+    fn detects_the_transport_from_the_scheme() {
+        assert_eq!(detect("http://127.0.0.1:8545").unwrap(), RpcScheme::Http);
+        assert_eq!(detect("https://rpc.example.com/key").unwrap(), RpcScheme::Http);
+        assert_eq!(detect("ws://127.0.0.1:8546").unwrap(), RpcScheme::Ws);
+        assert_eq!(detect("wss://rpc.example.com/key").unwrap(), RpcScheme::Ws);
+        // schemes are case insensitive and parsed lowercase
+        assert_eq!(detect("WSS://rpc.example.com").unwrap(), RpcScheme::Ws);
+
+        let error = detect("ipc:///tmp/node.ipc").unwrap_err();
+        assert!(error.to_string().contains("unsupported rpc scheme ipc"));
+    }
+}
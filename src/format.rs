@@ -0,0 +1,34 @@
+use alloy::primitives::U256;
+
+/// Render a raw token amount as a human readable decimal, e.g. `1234.56 USDC`.
+///
+/// Falls back to the raw amount when the decimals are unknown.
+pub fn format_amount(amount: U256, decimals: Option<U256>, symbol: &str) -> String {
+    let decimals = match decimals.and_then(|decimals| u8::try_from(decimals).ok()) {
+        Some(decimals) if decimals > 0 && decimals <= 77 => decimals,
+        _ => return format!("{} (raw) {}", amount, symbol).trim_end().to_string(),
+    };
+
+    let unit = U256::from(10).pow(U256::from(decimals));
+    let integer = amount / unit;
+    let fraction = (amount % unit).to_string();
+    let fraction = format!("{:0>width$}", fraction, width = decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+
+    let formatted = if fraction.is_empty() {
+        integer.to_string()
+    } else {
+        format!("{}.{}", integer, fraction)
+    };
+    format!("{} {}", formatted, symbol).trim_end().to_string()
+}
+
+/// Render a margin level in ray as a percentage, e.g. `112.5 %`.
+pub fn format_margin_level(margin_level: U256) -> String {
+    format_amount(margin_level, Some(U256::from(25)), "%")
+}
+
+/// Render a wei amount as native token.
+pub fn format_wei(amount: u128) -> String {
+    format_amount(U256::from(amount), Some(U256::from(18)), "ETH")
+}
@@ -0,0 +1,171 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tracing::{info, warn};
+
+use crate::collectors::control_collector::ControlCommand;
+use crate::collectors::time_collector::MIN_POLL_SECS;
+use crate::watchdog;
+
+/// State of one deployment's strategy, as last reported by it.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeploymentStatus {
+    pub last_block: u64,
+    pub pools: usize,
+    pub positions: usize,
+    /// Net profit of confirmed liquidations in 18-decimal units of the accounting token.
+    pub realized_profit: String,
+    pub profit_target_reached: bool,
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    healthy: bool,
+    events_processed: u64,
+    last_event_secs_ago: u64,
+    deployments: &'a BTreeMap<String, DeploymentStatus>,
+}
+
+static DEPLOYMENTS: Mutex<BTreeMap<String, DeploymentStatus>> = Mutex::new(BTreeMap::new());
+
+/// Replace the reported status of `deployment`.
+pub fn report(deployment: &str, status: DeploymentStatus) {
+    DEPLOYMENTS.lock().unwrap().insert(deployment.to_string(), status);
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Answer `/interval`: GET reads the tick interval, `POST /interval?secs=N`
+/// sets it, N at least [MIN_POLL_SECS].
+fn tick_interval(method: &str, query: &str, tick_interval: &watch::Sender<u64>) -> String {
+    if method != "POST" {
+        return response("200 OK", "text/plain", &format!("{}\n", *tick_interval.borrow()));
+    }
+    let secs = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("secs="))
+        .and_then(|secs| secs.parse::<u64>().ok());
+    match secs {
+        Some(secs) if secs < MIN_POLL_SECS => {
+            response("400 Bad Request", "text/plain", &format!("interval must be at least {}s\n", MIN_POLL_SECS))
+        }
+        Some(_) if tick_interval.receiver_count() == 0 => {
+            response("409 Conflict", "text/plain", "no time collector, scans follow new blocks\n")
+        }
+        Some(secs) => {
+            let previous = tick_interval.send_replace(secs);
+            info!("tick interval set to {}s from {}s", secs, previous);
+            response("200 OK", "text/plain", &format!("{}\n", secs))
+        }
+        None => response("400 Bad Request", "text/plain", "expected ?secs=N\n"),
+    }
+}
+
+/// Answer `POST /skipped-accounts/clear`: liquidate the permanently skipped
+/// `?account=0x..` again, or every skipped account without one.
+fn clear_skipped_accounts(method: &str, query: &str, control_commands: &broadcast::Sender<ControlCommand>) -> String {
+    if method != "POST" {
+        return response("405 Method Not Allowed", "text/plain", "expected POST\n");
+    }
+    let account = match query.split('&').find_map(|param| param.strip_prefix("account=")) {
+        Some(account) => match account.parse() {
+            Ok(account) => Some(account),
+            Err(_) => return response("400 Bad Request", "text/plain", "expected ?account=0x..\n"),
+        },
+        None => None,
+    };
+    match control_commands.send(ControlCommand::ClearSkippedAccounts(account)) {
+        Ok(_) => {
+            info!("clearing skipped accounts: {:?}", account);
+            response("200 OK", "text/plain", "ok\n")
+        }
+        Err(_) => response("503 Service Unavailable", "text/plain", "no strategy listening\n"),
+    }
+}
+
+/// Read a request off `stream`, returning its method, path and query.
+async fn read_request(stream: &mut TcpStream) -> (String, String, String) {
+    let mut request = [0u8; 1024];
+    let read = stream.read(&mut request).await.unwrap_or(0);
+    let request = String::from_utf8_lossy(&request[..read]);
+    let mut request_line = request.split_whitespace();
+    let method = request_line.next().unwrap_or("GET");
+    let target = request_line.next().unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    (method.to_string(), path.to_string(), query.to_string())
+}
+
+/// Serve probes on `port`: `/healthz` answers 200 while an event was
+/// processed within `max_idle_secs` and 503 otherwise, `/status` the
+/// deployments' reported status as JSON.
+pub async fn serve(port: u16, max_idle_secs: u64) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    info!("health server listening on port {}", port);
+    watchdog::arm();
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let (_, path, _) = read_request(&mut stream).await;
+            let idle = watchdog::idle_secs();
+            let healthy = idle < max_idle_secs;
+            let response = match path.as_str() {
+                "/healthz" if healthy => response("200 OK", "text/plain", "ok\n"),
+                "/healthz" => response("503 Service Unavailable", "text/plain", &format!("no event for {}s\n", idle)),
+                "/status" => {
+                    let deployments = DEPLOYMENTS.lock().unwrap().clone();
+                    let status = Status {
+                        healthy,
+                        events_processed: watchdog::events_processed(),
+                        last_event_secs_ago: idle,
+                        deployments: &deployments,
+                    };
+                    let body = serde_json::to_string(&status).unwrap_or_default();
+                    response("200 OK", "application/json", &body)
+                }
+                _ => response("404 Not Found", "text/plain", "not found\n"),
+            };
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                warn!("health probe failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Serve the operator routes on `port` of localhost only: `/interval` the
+/// time collector's tick interval, changed with `POST /interval?secs=N`,
+/// and `POST /skipped-accounts/clear` lifts permanent account skips.
+pub async fn serve_control(
+    port: u16,
+    tick_interval_control: Arc<watch::Sender<u64>>,
+    control_commands: broadcast::Sender<ControlCommand>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    info!("control server listening on 127.0.0.1:{}", port);
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let tick_interval_control = tick_interval_control.clone();
+        let control_commands = control_commands.clone();
+        tokio::spawn(async move {
+            let (method, path, query) = read_request(&mut stream).await;
+            let response = match path.as_str() {
+                "/interval" => tick_interval(&method, &query, &tick_interval_control),
+                "/skipped-accounts/clear" => clear_skipped_accounts(&method, &query, &control_commands),
+                _ => response("404 Not Found", "text/plain", "not found\n"),
+            };
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                warn!("control request failed: {}", e);
+            }
+        });
+    }
+}
@@ -1,3 +1,14 @@
+pub mod alerts;
+pub mod backfill;
+pub mod backtest;
 pub mod collectors;
+pub mod config_file;
 pub mod executors;
+pub mod failover;
+pub mod format;
+pub mod health;
+pub mod metrics;
+pub mod rate_limit;
+pub mod shutdown;
 pub mod strategies;
+pub mod watchdog;
@@ -2,53 +2,313 @@ use anyhow::Result;
 use clap::Parser;
 
 use artemis_core::engine::Engine;
-use artemis_core::types::{CollectorMap, ExecutorMap};
-use collectors::time_collector::TimeCollector;
+use artemis_core::types::{CollectorMap, Executor, ExecutorMap, Strategy};
+use collectors::{
+    block_collector::BlockCollector,
+    control_collector::ControlCollector,
+    log_collector::LogCollector,
+    margin_call_collector::{MarginCall, MarginCallCollector},
+    mempool_collector::MempoolCollector,
+    oracle_collector::OracleCollector,
+    outcome_collector::OutcomeCollector,
+    pool_collector::{NewPool, PoolCollector},
+    supervised_collector::SupervisedCollector,
+    throttle_collector::ThrottleCollector,
+    time_collector::{NewTick, TimeCollector},
+};
 use alloy::{
-    network::{EthereumWallet, Ethereum},
-    signers::local::PrivateKeySigner,
-    providers::ProviderBuilder, 
+    consensus::Transaction as _,
+    contract as alloy_contract,
+    network::{EthereumWallet, Ethereum, TransactionBuilder},
+    node_bindings::Anvil,
+    primitives::{Address, U256},
+    rpc::{client::RpcClient, types::{BlockTransactionsKind, Transaction}},
+    transports::http::reqwest::Url,
+    signers::{aws::AwsSigner, local::PrivateKeySigner, Signer as _},
+    providers::{ProviderBuilder, WsConnect},
 };
 
-use executors::protect_executor::ProtectExecutor;
+use failover::{FailoverTransport, RpcScheme};
+use rate_limit::RateLimited;
+use backtest::{BacktestReport, PinnedBlock};
+use executors::{
+    flashbots_executor::FlashbotsExecutor,
+    multi_relay_executor::MultiRelayExecutor,
+    protect_executor::{FeeShare, GasMode, LiquidationOutcome, ProtectExecutor},
+    refuel_executor::RefuelExecutor,
+    supervised_executor::SupervisedExecutor,
+};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::path::Path;
+use tokio::sync::{broadcast, watch};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use format::{format_amount, format_margin_level};
 use strategies::{
-    mm_strategy::{MmStrategy, Deployment},
-    types::{Action, Config, Event},
+    decimals::COMMON_DECIMALS,
+    deployments::{self, DeploymentAddresses},
+    flashloan::FlashloanConfig,
+    mm_strategy::{MmStrategy, Deployment, DEPLOYED_ADDRESSES, STATE_CACHE_FILE},
+    profit_model::DefaultProfitModel,
+    supervised_strategy::{OnStrategyPanic, SupervisedStrategy},
+    types::{Action, Config, Event, PeriodicTask},
 };
-use tracing::{info, Level};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::{filter, prelude::*};
 
+pub mod alerts;
+pub mod backfill;
+pub mod backtest;
 pub mod collectors;
+pub mod config_file;
 pub mod executors;
+pub mod failover;
+pub mod format;
+pub mod health;
+pub mod metrics;
+pub mod rate_limit;
+pub mod shutdown;
 pub mod strategies;
+pub mod watchdog;
+
+/// Liquidation outcomes buffered for the strategies before the oldest are dropped.
+const OUTCOME_CHANNEL_CAPACITY: usize = 64;
+
+/// Newly discovered pools buffered for the strategies before the oldest are dropped.
+const NEW_POOL_CHANNEL_CAPACITY: usize = 64;
+
+/// Margin calls buffered for the strategies before the oldest are dropped.
+const MARGIN_CALL_CHANNEL_CAPACITY: usize = 256;
+
+/// Operator commands buffered for the strategies before the oldest are dropped.
+const CONTROL_CHANNEL_CAPACITY: usize = 16;
+
+/// Env var the signing key may be read from instead of --private-key.
+const PRIVATE_KEY_ENV: &str = "PRIVATE_KEY";
 
 //static POLL_INTERVAL_SECS: u64 = 1 * 10;
 //pub const CHAIN_ID: u64 = 31337;
 
 /// CLI Options.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
+#[command(args_override_self = true)]
 pub struct Args {
-    /// Ethereum node WS endpoint.
+    /// TOML file with values for any of these flags, the command line takes precedence.
     #[arg(long)]
-    pub rpc: String,
+    pub config: Option<String>,
 
-    /// Private key for sending txs.
+    /// Ethereum node HTTP or WS endpoint, repeat to fail over to the next one in order.
+    #[arg(long, required = true)]
+    pub rpc: Vec<String>,
+
+    /// Ping every rpc endpoint this often to demote failing or stale ones.
+    #[arg(long, default_value_t = 10)]
+    pub rpc_health_check_secs: u64,
+
+    /// An rpc endpoint this many blocks behind the best one is demoted.
+    #[arg(long, default_value_t = 3)]
+    pub rpc_max_block_lag: u64,
+
+    /// Cap on requests per second of the strategies and collectors, calls over
+    /// it wait instead of failing. Liquidation txs aren't limited. 0 disables.
+    #[arg(long, default_value_t = 0)]
+    pub rpc_max_rps: u64,
+
+    /// Ethereum node WS endpoint, scans on every new block instead of every pool_interval_secs.
+    #[arg(long)]
+    pub ws_rpc: Option<String>,
+
+    /// Watch pending txs over ws_rpc for calls that may push positions under water.
+    #[arg(long)]
+    pub mempool: bool,
+
+    /// Replay position logs from the cached block and stream new ones over ws_rpc
+    /// instead of fetching them with eth_getLogs every tick.
     #[arg(long)]
-    pub private_key: String,
+    pub log_collector: bool,
+
+    /// Initial blocks per eth_getLogs call when backfilling position logs. Halved
+    /// while the provider refuses a range as too large, grown back after successes.
+    #[arg(long, alias = "log-chunk-size", default_value_t = 1024)]
+    pub backfill_chunk_size: u64,
 
-    /// Percentage of profit to pay in gas.
+    /// Warn when no event was processed for this long, 0 disables the watchdog.
+    #[arg(long, default_value_t = 0)]
+    pub watchdog_timeout_secs: u64,
+
+    /// Exit with a nonzero code instead of only warning when the watchdog fires.
     #[arg(long)]
-    pub bid_percentage: u64,
+    pub watchdog_exit: bool,
+
+    /// Consider a sent liquidation dropped when it has no receipt after this
+    /// long, its positions may then be submitted again.
+    #[arg(long, default_value_t = 120)]
+    pub receipt_timeout_secs: u64,
+
+    /// Multiply liquidation gas estimates by this for the tx's gas limit, capped at
+    /// the block gas limit, as estimates fall short for liquidations touching many slots.
+    #[arg(long, default_value_t = 1.2)]
+    pub gas_limit_multiplier: f64,
+
+    /// Blocks a liquidation must be deep before it counts as final and its profit is
+    /// realized. One reorged out before then is treated as dropped and may be resent.
+    #[arg(long, default_value_t = 1)]
+    pub confirmations: u64,
+
+    /// Raise the priority fee of a liquidation not included within
+    /// --tip-escalation-timeout-secs by this percentage and resend it with the
+    /// same nonce, up to --max-priority-fee. 0 disables.
+    #[arg(long, default_value_t = 0)]
+    pub tip_escalation_pct: u64,
+
+    /// Priority fee, in wei, tip escalation stops at.
+    #[arg(long, default_value_t = 10_000_000_000)]
+    pub max_priority_fee: u128,
+
+    /// Wait this long for a liquidation to be included before escalating its tip.
+    #[arg(long, default_value_t = 12)]
+    pub tip_escalation_timeout_secs: u64,
+
+    /// On SIGINT/SIGTERM, wait this long for in-flight liquidations to settle before exiting.
+    #[arg(long, default_value_t = 30)]
+    pub shutdown_timeout_secs: u64,
+
+    /// Pending txs passed to the strategy per second, the rest of a burst is dropped.
+    #[arg(long, default_value_t = 100)]
+    pub max_pending_txs_per_sec: u64,
 
+    /// Poll pool prices this often and rescan pools whose price moved, 0 disables.
+    #[arg(long, default_value_t = 0)]
+    pub oracle_poll_secs: u64,
+
+    /// Private key for sending txs. Prefer --keystore or the PRIVATE_KEY env
+    /// var, a key on the command line shows up in the shell history and process list.
+    #[arg(long, conflicts_with = "observe_only")]
+    pub private_key: Option<String>,
+
+    /// Web3 Secret Storage JSON keystore holding the key for sending txs.
+    #[arg(long, conflicts_with = "observe_only", requires = "keystore_password")]
+    pub keystore: Option<String>,
+
+    /// Password of the --keystore.
+    #[arg(long, env = "KEYSTORE_PASSWORD", hide_env_values = true)]
+    pub keystore_password: Option<String>,
+
+    /// Where the liquidator key is held: a local key from --private-key, --keystore
+    /// or PRIVATE_KEY, or an AWS KMS key that never leaves KMS.
+    #[arg(long, value_enum, default_value_t = SignerKind::Local)]
+    pub signer_kind: SignerKind,
+
+    /// Id or ARN of the secp256k1 KMS key for --signer-kind aws-kms. Credentials and
+    /// region come from the usual AWS environment, profile or instance role.
+    #[arg(long, env = "AWS_KMS_KEY_ID", required_if_eq("signer_kind", "aws-kms"))]
+    pub kms_key_id: Option<String>,
+
+    /// Percentage of profit to pay in gas, required unless observing only.
+    #[arg(long, conflicts_with = "observe_only")]
+    pub bid_percentage: Option<u64>,
+
+    /// Track positions without a signer, never submitting any tx. Implied when no
+    /// signing key is given, so a monitor instance never needs one.
     #[arg(long)]
-    pub deployment: Deployment,
+    pub observe_only: bool,
+
+    /// Position read RPC calls in flight at once during a scan, lower it for rate limited endpoints.
+    #[arg(long, default_value_t = 4)]
+    pub scan_concurrency: usize,
 
+    /// Multicall3 contract batching the scan's position and pool reads into
+    /// aggregate3 calls, falls back to individual calls when nothing is deployed there.
+    #[arg(long)]
+    pub multicall_address: Option<Address>,
+
+    /// Reader calls per aggregate3, each one covering a chunk of positions or pools.
+    #[arg(long, default_value_t = 10)]
+    pub multicall_batch_size: usize,
+
+    /// Skip a position whose liquidation reverted in the protocol for this long, 0 disables.
+    #[arg(long, default_value_t = 3600)]
+    pub blacklist_ttl_secs: u64,
+
+    /// Detect and fully prepare liquidations but only log the txs, never broadcast them.
+    #[arg(long, conflicts_with = "observe_only")]
+    pub dry_run: bool,
+
+    /// Deployments to liquidate on, repeat to run several markets in one process.
+    /// `localnet` and `base` are built in, other names are looked up in --deployments-file.
+    #[arg(long, required = true)]
+    pub deployment: Vec<Deployment>,
+
+    /// JSON or TOML file mapping deployment names to their contract addresses,
+    /// takes precedence over the built-in deployments' address files.
+    #[arg(long)]
+    pub deployments_file: Option<String>,
+
+    /// Expected profit of every liquidation, in 18-decimal units of the profit token,
+    /// converted to each pool's base token at the oracle price.
     #[arg(long)]
     pub total_profit: u128,    
 
-    // #[arg(long)]
-    // pub liquidator_address: String,
+    /// Skip a liquidation whose expected profit net of its estimated gas cost is
+    /// below this, in 18-decimal units of the profit token. 0 disables the check.
+    #[arg(long, default_value_t = 0)]
+    pub min_profit: u128,
+
+    /// Stop liquidating once confirmed liquidations realized this much profit, in
+    /// 18-decimal units of the profit token, 0 disables.
+    #[arg(long, default_value_t = 0)]
+    pub profit_target: u128,
+
+    /// Stop sending liquidations after this many failed or reverted submissions in a
+    /// row within 10 minutes, as they point at bad addresses or a stale ABI. 0 disables.
+    #[arg(long, default_value_t = 0)]
+    pub breaker_threshold: u64,
+
+    /// Resume liquidations this long after the circuit breaker tripped, 0 waits for a restart.
+    #[arg(long, default_value_t = 0)]
+    pub breaker_cooldown_secs: u64,
+
+    /// Lending pool to flashloan the repay amounts from, so liquidations need no balance
+    /// of the debt tokens. Sends each batch through --flashloan-router in a single tx.
+    #[arg(long, requires_all = ["flashloan_router", "swap_router"])]
+    pub flashloan_provider: Option<Address>,
+
+    /// Helper contract taking the flashloan, liquidating, swapping the seized collateral
+    /// and repaying the loan atomically, a deployment of
+    /// crates/liquidator-contract/src/FlashLiquidator.sol. The lender must be an Aave V3
+    /// style pool and the swap router a Uniswap V2 style one.
+    #[arg(long, requires = "flashloan_provider")]
+    pub flashloan_router: Option<Address>,
+
+    /// DEX router the flashloan helper swaps the seized collateral through.
+    #[arg(long, requires = "flashloan_provider")]
+    pub swap_router: Option<Address>,
+
+    /// Premium the flashloan provider charges, in bps, deducted from expected profits.
+    #[arg(long, default_value_t = 5, requires = "flashloan_provider")]
+    pub flashloan_fee_bps: u64,
+
+    /// Worst price, in bps below the pools' oracle price, the flashloan helper's
+    /// collateral swap may get. Deducted from expected profits and enforced on-chain as
+    /// the minimum profit left after the swap, so a sandwich can take no more. The
+    /// reference is the MM pools' price, not a DEX quote, so a DEX trading away from the
+    /// oracle eats into the margin. Only flashloan liquidations swap: without
+    /// --flashloan-provider the seized collateral is kept as is and nothing is swapped.
+    #[arg(long, default_value_t = 50, requires = "flashloan_provider")]
+    pub max_slippage_bps: u64,
+
+    /// Send txs unsigned from an impersonated --liquidator-address on an anvil
+    /// node (e.g. a fork), for testing the full path without a private key.
+    #[arg(
+        long,
+        requires = "liquidator_address",
+        conflicts_with_all = ["observe_only", "private_key", "keystore", "flashbots_relay", "relays", "validate_on_fork"]
+    )]
+    pub test_mode: bool,
+
+    /// Liquidator address to impersonate in --test-mode.
+    #[arg(long, requires = "test_mode")]
+    pub liquidator_address: Option<Address>,
 
     #[arg(long)]
     pub chain_id: u64,
@@ -56,12 +316,20 @@ pub struct Args {
     #[arg(long)]
     pub last_block_number: u64,
 
+    /// Seconds between scans without --ws-rpc, at least 1. Adjustable while running with
+    /// `POST /interval?secs=N` on the control port, the periodic tasks' intervals are
+    /// counted in ticks and scale along.
     #[arg(long, default_value_t = 10)]
     pub pool_interval_secs: u64,
 
     #[arg(long, default_value_t = 60*60*24*2)]
     pub update_all_pools_secs: u64,
 
+    /// How long a position event keeps the position active. Every borrow, repay,
+    /// deposit, withdraw, swap or other position event raises the position's activity
+    /// by this long, up to 4 events' worth, and active positions below
+    /// --monitor-margin-level-threshold are scanned every tick. Dormant positions
+    /// are only checked on the full scan. Pools are refreshed by the same activity.
     #[arg(long, default_value_t = 60*60*24*7)]
     pub activity_level_clean_secs: u64,
 
@@ -71,54 +339,915 @@ pub struct Args {
     #[arg(long, default_value_t = 60*60*24)]
     pub calc_all_positions_secs: u64,
 
-    // #[arg(long, default_value_t = 130)]
-    // pub monitor_margin_level_thresold: u128,
+    /// Periodic tasks to hold for a full interval after startup, all run at once by default.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub defer_on_startup: Vec<PeriodicTask>,
+
+    /// Price txs with `gasPrice` (legacy) or EIP-1559 fees, picked from the
+    /// chain's latest block when not given.
+    #[arg(long, value_enum)]
+    pub gas_mode: Option<GasMode>,
+
+    /// Priority fee used when the node lacks eth_feeHistory.
+    #[arg(long, default_value_t = 1_000_000_000)]
+    pub fallback_priority_fee_wei: u128,
+
+    /// Estimate gas with a state override funding the liquidator.
+    #[arg(long)]
+    pub estimate_with_overrides: bool,
+
+    /// Read the pending nonce from the node for every submission, for keys shared with other senders.
+    #[arg(long)]
+    pub external_nonce_coordination: bool,
+
+    /// eth_call each tx against the pending state and drop it if it reverts.
+    #[arg(long)]
+    pub simulate_before_send: bool,
+
+    /// Serve Prometheus metrics on this port, disabled when unset.
+    #[arg(long)]
+    pub metrics_port: Option<u16>,
+
+    /// Webhook alerts are posted to (Discord, Slack, or Telegram's sendMessage
+    /// with chat_id in the query), disabled when unset.
+    #[arg(long)]
+    pub alert_webhook: Option<String>,
+
+    /// Minimum time between alert posts, alerts raised in between are combined.
+    #[arg(long, default_value_t = 30)]
+    pub alert_min_interval_secs: u64,
+
+    /// Serve /healthz and /status probes on this port, disabled when unset.
+    #[arg(long)]
+    pub health_port: Option<u16>,
+
+    /// /healthz fails once no event was processed for this long.
+    #[arg(long, default_value_t = 120)]
+    pub health_max_idle_secs: u64,
+
+    /// Serve the operator routes, `/interval` and `POST /skipped-accounts/clear`, on this
+    /// port of 127.0.0.1 only, disabled when unset.
+    #[arg(long)]
+    pub control_port: Option<u16>,
+
+    /// Retries of the strategy's pool and position reads on transient RPC errors.
+    #[arg(long, default_value_t = 3)]
+    pub rpc_max_retries: u32,
+
+    /// Capacity of the engine's event channel.
+    #[arg(long, default_value_t = 512)]
+    pub event_buffer_size: usize,
+
+    /// Maximum events per second fed to the strategy, 0 disables the limit.
+    #[arg(long, default_value_t = 0)]
+    pub max_events_per_sec: u64,
+
+    /// Token all profits are valued in at the pools' oracle prices: expected profits
+    /// for ranking, --total-profit, --min-profit, --profit-target and the realized
+    /// profit of seized collateral. Defaults to the base token most pools quote in.
+    #[arg(long, visible_alias = "profit-token")]
+    pub accounting_token: Option<Address>,
+
+    /// Wrapped native token (e.g. WETH) pricing gas in the profit token through its
+    /// pool with it, for the bid cap, --min-profit and the fee share. Without it
+    /// gas costs are not compared to profits.
+    #[arg(long)]
+    pub native_token: Option<Address>,
+
+    /// Between full scans, also scan this many of the positions skipped as dormant
+    /// each tick, in turn, so every one is checked well before the next full scan
+    /// without a burst. The full scan still runs every --calc-all-positions-secs.
+    #[arg(long, default_value_t = 0)]
+    pub positions_per_tick: usize,
+
+    /// Scan positions within this margin above the liquidation threshold every tick, in bps.
+    #[arg(long, default_value_t = 0)]
+    pub fast_watch_margin_bps: u64,
+
+    /// Raise a margin call, logged and sent to the --alert-webhook, when a position's
+    /// margin level comes within this margin above the liquidation threshold, in bps.
+    /// Raised once until the position recovers above it. 0 disables.
+    #[arg(long, alias = "margin-call-buffer", default_value_t = 0)]
+    pub margin_call_buffer_bps: u64,
+
+    /// Maximum concurrent pre-flight simulations in the executor.
+    #[arg(long, default_value_t = 8)]
+    pub max_concurrent_simulations: usize,
+
+    /// Fork the chain at head with anvil, run one strategy and executor pass against it and exit.
+    #[arg(long, conflicts_with = "observe_only")]
+    pub validate_on_fork: bool,
+
+    /// Replay the strategy from this block to --to-block against an archive node,
+    /// writing the liquidations it would have sent to --backtest-output, and exit.
+    #[arg(long, requires_all = ["to_block", "observe_only"])]
+    pub from_block: Option<u64>,
+
+    /// Last block of the --from-block backtest.
+    #[arg(long, requires = "from_block")]
+    pub to_block: Option<u64>,
+
+    /// Blocks between two scans of the backtest.
+    #[arg(long, default_value_t = 1)]
+    pub backtest_step: u64,
+
+    /// CSV file the backtest writes its per block results to.
+    #[arg(long, default_value = "backtest.csv")]
+    pub backtest_output: String,
+
+    /// File with one account per line to always scan and liquidate first, reloaded on change.
+    #[arg(long)]
+    pub priority_accounts_file: Option<String>,
+
+    /// Only scan and liquidate positions of this borrower, repeat for several.
+    /// All positions are scanned when not given.
+    #[arg(long)]
+    pub target_borrower: Vec<Address>,
+
+    /// Resync the nonce from chain before submitting after this long idle, 0 disables.
+    #[arg(long, default_value_t = 0)]
+    pub nonce_resync_idle_secs: u64,
+
+    /// Re-fetch logs around eth_getLogs chunk boundaries to fill dropped logs.
+    #[arg(long)]
+    pub verify_log_boundaries: bool,
+
+    /// Scan every N blocks instead of every pool_interval_secs.
+    #[arg(long)]
+    pub scan_every_blocks: Option<u64>,
+
+    /// Block time used with scan_every_blocks, estimated from recent blocks when unset.
+    #[arg(long)]
+    pub block_time_ms: Option<u64>,
+
+    /// Request a refuel from --funding-source when the liquidator's native balance drops
+    /// below this, 0 disables. Needs a nonzero --refuel-amount-wei.
+    #[arg(long, default_value_t = 0)]
+    pub min_native_balance_wei: u128,
+
+    /// Native amount moved per refuel.
+    #[arg(long, default_value_t = 0)]
+    pub refuel_amount_wei: u128,
+
+    /// Pool balance, in each token's base units, a liquidation may not leave a pool
+    /// below. Read from the deployment's PoolUtils when it has one, this is for
+    /// deployments without it, where the floor is otherwise unchecked.
+    #[arg(long)]
+    pub minimum_liquidity: Option<u128>,
+
+    /// Upper bound the refuel executor accepts for a single refuel.
+    #[arg(long, default_value_t = 0)]
+    pub max_refuel_wei: u128,
+
+    /// Account refuels are paid from, needs --funding-private-key.
+    #[arg(long, requires = "funding_private_key")]
+    pub funding_source: Option<Address>,
+
+    /// Private key of the funding source.
+    #[arg(long, requires = "funding_source")]
+    pub funding_private_key: Option<String>,
+
+    /// Submit at most this many txs per wave during a cascade, 0 disables waves.
+    #[arg(long, default_value_t = 0)]
+    pub cascade_chunk_size: usize,
+
+    /// Delay between cascade waves.
+    #[arg(long, default_value_t = 1000)]
+    pub cascade_chunk_delay_ms: u64,
+
+    /// Native balance no tx may spend into, txs that would dip below it are skipped.
+    #[arg(long, default_value_t = 0)]
+    pub min_native_reserve_wei: u128,
+
+    /// Share of confirmed net profit transferred to fee_recipient, in bps.
+    #[arg(long, default_value_t = 0, requires_all = ["fee_recipient", "accounting_token"])]
+    pub fee_share_bps: u64,
+
+    /// Recipient of the profit share, paid in the --accounting-token.
+    #[arg(long)]
+    pub fee_recipient: Option<Address>,
+
+    /// Accrue fee shares until they reach this amount before transferring.
+    #[arg(long, default_value_t = 0)]
+    pub fee_batch_min: u128,
+
+    /// Restart the strategy from persisted state or exit the process when it panics.
+    #[arg(long, value_enum, default_value_t = OnStrategyPanic::Restart)]
+    pub on_strategy_panic: OnStrategyPanic,
+
+    /// Restart a collector whose stream fails, ends or panics with backoff, and keep
+    /// an executor that panics serving, exiting after this many failures in a row.
+    #[arg(long, default_value_t = 5)]
+    pub max_task_restarts: u32,
+
+    /// Flashbots relay liquidations are bundled to, instead of the public mempool. Each
+    /// is bundled for the next 3 blocks.
+    #[arg(long, requires = "flashbots_signing_key")]
+    pub flashbots_relay: Option<String>,
+
+    /// Private key identifying the bot to the relay, not the liquidator key.
+    #[arg(long, requires = "flashbots_relay")]
+    pub flashbots_signing_key: Option<String>,
+
+    /// Private relay RPC endpoint taking eth_sendRawTransaction, repeat to send each
+    /// liquidation to all of them at once, the first to get it included wins.
+    #[arg(long = "relays", conflicts_with = "flashbots_relay")]
+    pub relays: Vec<String>,
+
+    /// Send a liquidation every --relays endpoint rejected to the public mempool,
+    /// where it can be front-run, instead of dropping it.
+    #[arg(long, requires = "relays")]
+    pub relay_public_fallback: bool,
+
+    /// Stop liquidating when a proxied deployment contract's implementation changes.
+    #[arg(long)]
+    pub pause_on_upgrade: bool,
+
+    /// Minimum blocks between two full pool refreshes, whatever update_all_pools_secs says.
+    #[arg(long, default_value_t = 0)]
+    pub min_pool_refresh_blocks: u64,
+
+    /// Skip positions with no activity since this block during backfill.
+    #[arg(long, default_value_t = 0)]
+    pub skip_positions_inactive_since: u64,
+
+    /// Liquidation attempts of any one position before its account is skipped permanently,
+    /// 0 disables. Cleared with --clear-skipped-accounts or `POST /skipped-accounts/clear`
+    /// on the control port.
+    #[arg(long, default_value_t = 0)]
+    pub max_account_attempts: u64,
+
+    /// Clear permanently skipped accounts from the state cache on startup.
+    #[arg(long)]
+    pub clear_skipped_accounts: bool,
+
+    /// Allowed deviation of the computed debt from the on-chain debt, in bps.
+    #[arg(long, default_value_t = 100)]
+    pub repay_sanity_tolerance_bps: u64,
+
+    /// Between full scans only positions below this margin level are scanned, in percent, 0 scans all.
+    #[arg(long, default_value_t = 130)]
+    pub monitor_margin_level_threshold: u128,
+
+    /// Log as plain text or as one JSON object per line for log aggregators.
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// One-shot commands run instead of the bot, after the flags.
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Run one full scan of every --deployment at the chain head, print the positions
+    /// it would liquidate and exit. Nothing is sent, so --observe-only spares the signer flags.
+    Scan {
+        /// Also print the tx the bot would send to liquidate each position on its own.
+        #[arg(long)]
+        calldata: bool,
+    },
+}
+
+/// Output format of the logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Custody of the liquidator key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SignerKind {
+    Local,
+    AwsKms,
+}
+
+/// The liquidator's signer, whichever custody it comes from.
+enum LiquidatorSigner {
+    Local(PrivateKeySigner),
+    AwsKms(AwsSigner),
+}
+
+impl LiquidatorSigner {
+    fn address(&self) -> Address {
+        match self {
+            LiquidatorSigner::Local(signer) => signer.address(),
+            LiquidatorSigner::AwsKms(signer) => signer.address(),
+        }
+    }
+
+    fn wallet(self) -> EthereumWallet {
+        match self {
+            LiquidatorSigner::Local(signer) => EthereumWallet::from(signer),
+            LiquidatorSigner::AwsKms(signer) => EthereumWallet::from(signer),
+        }
+    }
 }
 
 
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Set up tracing and parse args.
+    // Parse args and set up tracing.
+    let mut args = Args::parse_from(config_file::args_with_config_file()?);
+
     let filter = filter::Targets::new()
         .with_target("artemis_core", Level::INFO)
         .with_target("mm_liquidator", Level::INFO);
 
+    let (json_layer, text_layer) = match args.log_format {
+        LogFormat::Json => (Some(tracing_subscriber::fmt::layer().json()), None),
+        LogFormat::Text => (None, Some(tracing_subscriber::fmt::layer())),
+    };
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
+        .with(json_layer)
+        .with(text_layer)
         .with(filter)
         .init();
 
-    let args = Args::parse();
-    println!("{:?}", args);
+    // Set up alloy provider.
+    let rpc_urls = args.rpc.iter().map(|rpc| rpc.parse()).collect::<Result<Vec<Url>, _>>()?;
+    // subscriptions need ws, fall back to the first ws --rpc when --ws-rpc isn't given
+    if args.ws_rpc.is_none() {
+        for url in rpc_urls.iter() {
+            if RpcScheme::detect(url)? == RpcScheme::Ws {
+                args.ws_rpc = Some(url.to_string());
+                break;
+            }
+        }
+    }
+    if (args.mempool || args.log_collector) && args.ws_rpc.is_none() {
+        anyhow::bail!("--mempool and --log-collector need a ws endpoint in --ws-rpc or --rpc");
+    }
+    let rpc = FailoverTransport::connect(rpc_urls).await?;
+    if args.rpc.len() > 1 {
+        info!("failing over between {} rpc endpoints", args.rpc.len());
+        tokio::spawn(rpc.clone().health_check(Duration::from_secs(args.rpc_health_check_secs), args.rpc_max_block_lag));
+    }
+    let read_provider = ProviderBuilder::new().on_client(RpcClient::new(RateLimited::new(rpc.clone(), args.rpc_max_rps), false));
 
-    let chain_id: u64 = args.chain_id;
+    // Derive the tick interval from the chain's block time when scanning by blocks.
+    if let Some(scan_every_blocks) = args.scan_every_blocks {
+        let block_time_ms = match args.block_time_ms {
+            Some(block_time_ms) => block_time_ms,
+            None => estimate_block_time_ms(&read_provider).await?,
+        };
+        args.pool_interval_secs = std::cmp::max(1, (block_time_ms * scan_every_blocks).div_ceil(1000));
+        info!(
+            "block time {}ms, scanning every {} blocks, resolved pool interval {}s",
+            block_time_ms, scan_every_blocks, args.pool_interval_secs
+        );
+    }
 
-    // Set up alloy provider.
-    let signer: PrivateKeySigner = args.private_key.parse().expect("should parse private key");
-    let wallet = EthereumWallet::from(signer.clone());
-    let liquidator = signer.address();
+    args.gas_mode = Some(GasMode::resolve(&read_provider, args.gas_mode).await?);
 
-    let rpc = (&args.rpc).parse()?;
-    let provider = ProviderBuilder::new().with_cached_nonce_management().wallet(wallet.clone()).on_http(rpc);
-    //let provider = ProviderBuilder::new().wallet(wallet.clone()).on_http(rpc);
+    // without any key run as a read-only observer instead of failing, unless a
+    // flag that only makes sense with a signer asked for one
+    let env_key = std::env::var(PRIVATE_KEY_ENV).ok().filter(|key| !key.is_empty());
+    let has_key = args.private_key.is_some() || args.keystore.is_some() || env_key.is_some();
+    let needs_signer = args.validate_on_fork || args.flashbots_relay.is_some() || !args.relays.is_empty();
+    if !has_key && needs_signer && args.signer_kind == SignerKind::Local {
+        anyhow::bail!("--validate-on-fork, --flashbots-relay and --relays need a signing key");
+    }
+    if !args.observe_only && !args.test_mode && !args.dry_run && args.signer_kind == SignerKind::Local && !has_key {
+        warn!("no signing key given, running as a read-only observer, liquidations are only logged");
+        args.observe_only = true;
+    }
+    if !args.observe_only && args.bid_percentage.is_none() {
+        anyhow::bail!("--bid-percentage is required to send liquidations");
+    }
+    let signer = load_signer(&args).await?;
+    let liquidator = match (&signer, args.liquidator_address) {
+        (Some(signer), _) => signer.address(),
+        (None, Some(liquidator_address)) => liquidator_address,
+        (None, None) => Address::ZERO,
+    };
+    if args.test_mode {
+        impersonate(&read_provider, liquidator).await?;
+    }
+    if args.observe_only {
+        info!("observe only mode, no signer and no executor");
+    }
+    if args.target_borrower.contains(&Address::ZERO) {
+        anyhow::bail!("--target-borrower can't be the zero address");
+    }
+    args.target_borrower.sort();
+    args.target_borrower.dedup();
+    if args.native_token.is_none() && !args.observe_only {
+        warn!("no --native-token, gas costs can't be priced in the profit token and are not checked against profits");
+    }
+    if args.flashloan_provider.is_none() && !args.observe_only {
+        info!("no --flashloan-provider, seized collateral is kept unswapped, --max-slippage-bps doesn't apply");
+    }
+    if !args.target_borrower.is_empty() {
+        info!("targeting only {} borrowers: {:?}", args.target_borrower.len(), args.target_borrower);
+    }
+    if args.min_native_balance_wei > 0 {
+        if args.refuel_amount_wei == 0 {
+            anyhow::bail!("--min-native-balance-wei needs a nonzero --refuel-amount-wei");
+        }
+        if args.funding_source.is_none() {
+            warn!("no --funding-source, --min-native-balance-wei is ignored and the liquidator is never refueled");
+        } else if args.refuel_amount_wei > args.max_refuel_wei {
+            anyhow::bail!("--refuel-amount-wei is above --max-refuel-wei, every refuel would be rejected");
+        }
+    }
+
+    if let Some(metrics_port) = args.metrics_port {
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(metrics_port).await {
+                error!("metrics exporter stopped: {}", e);
+            }
+        });
+    }
+
+    if let Some(alert_webhook) = &args.alert_webhook {
+        alerts::init(alert_webhook.parse()?, Duration::from_secs(args.alert_min_interval_secs));
+    }
+
+    // the time collector's tick interval, adjustable through the control port
+    let (tick_interval_control, tick_interval) = watch::channel(args.pool_interval_secs);
+    let tick_interval_control = Arc::new(tick_interval_control);
+    // operator commands from the control port, fed to the strategies as Event::Control
+    let (control_commands, _) = broadcast::channel(CONTROL_CHANNEL_CAPACITY);
+
+    if let Some(health_port) = args.health_port {
+        let max_idle_secs = args.health_max_idle_secs;
+        tokio::spawn(async move {
+            if let Err(e) = health::serve(health_port, max_idle_secs).await {
+                error!("health server stopped: {}", e);
+            }
+        });
+    }
+
+    if let Some(control_port) = args.control_port {
+        let tick_interval_control = tick_interval_control.clone();
+        let control_commands = control_commands.clone();
+        tokio::spawn(async move {
+            if let Err(e) = health::serve_control(control_port, tick_interval_control, control_commands).await {
+                error!("control server stopped: {}", e);
+            }
+        });
+    }
+
+    if args.watchdog_timeout_secs > 0 {
+        tokio::spawn(watchdog::run(Duration::from_secs(args.watchdog_timeout_secs), args.watchdog_exit));
+    }
 
     // // Set up engine.
-    let mut engine: Engine<Event, Action<Ethereum>> = Engine::default();
+    let mut engine: Engine<Event, Action<Ethereum>> = Engine::new()
+        .with_event_channel_capacity(args.event_buffer_size);
+
+    // Set up outcome collector, feeding failed and confirmed liquidations back to the strategies.
+    let (outcomes, _) = broadcast::channel(OUTCOME_CHANNEL_CAPACITY);
+    let outcome_collector = Box::new(OutcomeCollector::new(outcomes.clone()));
+    let outcome_collector = CollectorMap::new(outcome_collector, Event::LiquidationOutcome);
+    engine.add_collector(Box::new(SupervisedCollector::new("outcome", Box::new(outcome_collector), args.max_task_restarts)));
+
+    // Set up pool collector, feeding pools found after startup back to the strategies.
+    let (new_pools, _) = broadcast::channel(NEW_POOL_CHANNEL_CAPACITY);
+    let pool_collector = Box::new(PoolCollector::new(new_pools.clone()));
+    let pool_collector = CollectorMap::new(pool_collector, Event::NewPool);
+    engine.add_collector(Box::new(SupervisedCollector::new("pool", Box::new(pool_collector), args.max_task_restarts)));
+
+    // Set up margin call collector, feeding positions nearing the threshold back to the strategies.
+    let (margin_calls, _) = broadcast::channel(MARGIN_CALL_CHANNEL_CAPACITY);
+    let margin_call_collector = Box::new(MarginCallCollector::new(margin_calls.clone()));
+    let margin_call_collector = CollectorMap::new(margin_call_collector, Event::MarginCall);
+    engine.add_collector(Box::new(SupervisedCollector::new("margin-call", Box::new(margin_call_collector), args.max_task_restarts)));
 
-    // // Set up time collector.
-    let time_collector = Box::new(TimeCollector::new(args.pool_interval_secs));
-    let time_collector = CollectorMap::new(time_collector, Event::NewTick);
-    engine.add_collector(Box::new(time_collector));
+    // Set up control collector, feeding the control port's operator commands to the strategies.
+    let control_collector = Box::new(ControlCollector::new(control_commands));
+    let control_collector = CollectorMap::new(control_collector, Event::Control);
+    engine.add_collector(Box::new(SupervisedCollector::new("control", Box::new(control_collector), args.max_task_restarts)));
 
-    let config = Config {
-        chain_id: chain_id,
+    let mut log_provider = None;
+    if let Some(ws_rpc) = &args.ws_rpc {
+        // Set up block collector, ticks follow blocks so there is no interval to control.
+        drop(tick_interval);
+        info!("scanning on new blocks from {}", ws_rpc);
+        let ws_provider = ProviderBuilder::new().on_ws(WsConnect::new(ws_rpc.clone())).await?;
+        let block_collector = Box::new(BlockCollector::new(Arc::new(ws_provider.clone())));
+        let block_collector = CollectorMap::new(block_collector, Event::from);
+        let block_collector = ThrottleCollector::new(Box::new(block_collector), args.max_events_per_sec);
+        engine.add_collector(Box::new(SupervisedCollector::new("block", Box::new(block_collector), args.max_task_restarts)));
+        if args.log_collector {
+            log_provider = Some(Arc::new(ws_provider.clone()));
+        }
+
+        if args.mempool {
+            // Set up mempool collector, dropping plain transfers and deployments early.
+            let mempool_collector = Box::new(MempoolCollector::new(
+                Arc::new(ws_provider),
+                Box::new(|tx: &Transaction| tx.to().is_some() && !tx.input().is_empty()),
+                args.max_pending_txs_per_sec,
+            ));
+            let mempool_collector = CollectorMap::new(mempool_collector, Event::PendingTx);
+            engine.add_collector(Box::new(SupervisedCollector::new("mempool", Box::new(mempool_collector), args.max_task_restarts)));
+        }
+    } else {
+        // // Set up time collector.
+        let time_collector = Box::new(TimeCollector::new(tick_interval));
+        let time_collector = CollectorMap::new(time_collector, Event::NewTick);
+        let time_collector = ThrottleCollector::new(Box::new(time_collector), args.max_events_per_sec);
+        engine.add_collector(Box::new(SupervisedCollector::new("time", Box::new(time_collector), args.max_task_restarts)));
+    }
+
+    let config = strategy_config(&args);
+
+    let registry = match &args.deployments_file {
+        Some(path) => {
+            let registry = deployments::load_registry(path)?;
+            info!("loaded deployments {:?} from {}", registry.keys().collect::<Vec<_>>(), path);
+            registry
+        }
+        None => HashMap::new(),
     };
+    for deployment in args.deployment.iter() {
+        if matches!(deployment, Deployment::Custom(_)) && !registry.contains_key(deployment.name()) {
+            anyhow::bail!("unknown deployment {}, add it to --deployments-file", deployment.name());
+        }
+    }
+
+    if let Some(Command::Scan { calldata }) = args.command {
+        for deployment in args.deployment.iter() {
+            let config = deployment_config(&config, &registry, deployment, args.deployment.len() > 1)?;
+            scan(Arc::new(read_provider.clone()), &args, config, deployment.clone(), calldata).await?;
+        }
+        return Ok(());
+    }
+
+    if args.validate_on_fork {
+        let Some(signer) = signer else {
+            anyhow::bail!("--validate-on-fork needs a signing key");
+        };
+        let config = deployment_config(&config, &registry, &args.deployment[0], args.deployment.len() > 1)?;
+        let passed = validate_on_fork(&args, config, args.deployment[0].clone(), signer).await?;
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
+    if let (Some(from_block), Some(to_block)) = (args.from_block, args.to_block) {
+        let config = deployment_config(&config, &registry, &args.deployment[0], args.deployment.len() > 1)?;
+        let transport = PinnedBlock::new(RateLimited::new(rpc.clone(), args.rpc_max_rps), from_block);
+        backtest(&args, transport, config, args.deployment[0].clone(), from_block, to_block).await?;
+        return Ok(());
+    }
+
+    info!("on strategy panic: {:?}", args.on_strategy_panic);
+    let multiple_deployments = args.deployment.len() > 1;
+    for deployment in args.deployment.clone() {
+        info!("setting up deployment {}", deployment.name());
+        let config = deployment_config(&config, &registry, &deployment, multiple_deployments)?;
+        let mut strategy = new_strategy(
+            Arc::new(read_provider.clone()),
+            &args,
+            config.clone(),
+            deployment.clone(),
+            liquidator,
+            Some(new_pools.clone()),
+            Some(margin_calls.clone()),
+        )?;
+        if !args.observe_only {
+            strategy.check_liquidator_authorized().await?;
+        }
+        if args.oracle_poll_secs > 0 {
+            // Set up oracle collector.
+            let oracle_collector = Box::new(OracleCollector::new(
+                Arc::new(read_provider.clone()),
+                strategy.reader(),
+                strategy.data_store(),
+                args.oracle_poll_secs,
+            ));
+            let oracle_collector = CollectorMap::new(oracle_collector, Event::PriceUpdate);
+            engine.add_collector(Box::new(SupervisedCollector::new("oracle", Box::new(oracle_collector), args.max_task_restarts)));
+        }
+        if let Some(log_provider) = &log_provider {
+            // Set up log collector, resuming where the cached state left off.
+            let log_collector = Box::new(LogCollector::new(
+                log_provider.clone(),
+                strategy.position_log_filter(),
+                strategy.resume_block()?,
+                args.backfill_chunk_size,
+            ));
+            let log_collector = CollectorMap::new(log_collector, Event::Log);
+            engine.add_collector(Box::new(SupervisedCollector::new("log", Box::new(log_collector), args.max_task_restarts)));
+        }
 
-    let strategy = MmStrategy::new(
-        Arc::new(provider.clone()),
+        // Set up strategy, supervised so a panic doesn't leave it silently gone.
+        let strategy_client = Arc::new(read_provider.clone());
+        let strategy_args = args.clone();
+        let new_pools = new_pools.clone();
+        let margin_calls = margin_calls.clone();
+        let strategy = SupervisedStrategy::new(
+            Box::new(strategy),
+            Box::new(move || {
+                let strategy = new_strategy(
+                    strategy_client.clone(),
+                    &strategy_args,
+                    config.clone(),
+                    deployment.clone(),
+                    liquidator,
+                    Some(new_pools.clone()),
+                    Some(margin_calls.clone()),
+                )?;
+                Ok(Box::new(strategy) as Box<dyn Strategy<Event, Action<Ethereum>>>)
+            }),
+            args.on_strategy_panic,
+        );
+        engine.add_strategy(Box::new(strategy));
+    }
+
+    if args.test_mode {
+        // Set up executor sending unsigned txs, the node signs for the impersonated liquidator.
+        let provider = ProviderBuilder::new().with_cached_nonce_management().on_client(RpcClient::new(rpc.clone(), false));
+        let executor = Box::new(new_executor(Arc::new(provider), &args, Some(outcomes.clone())));
+        let executor = ExecutorMap::new(executor, |action| match action {
+            Action::SubmitTx(tx) => Some(tx),
+            _ => None,
+        });
+        engine.add_executor(Box::new(SupervisedExecutor::new("liquidation", Box::new(executor), args.max_task_restarts)));
+    }
+
+    if let Some(signer) = signer {
+        let wallet = signer.wallet();
+        let provider = ProviderBuilder::new().with_cached_nonce_management().wallet(wallet.clone()).on_client(RpcClient::new(rpc.clone(), false));
+
+        if let (Some(relay), Some(signing_key)) = (&args.flashbots_relay, &args.flashbots_signing_key) {
+            // Set up flashbots executor, bundled txs bypass the local nonce cache.
+            let auth_signer: PrivateKeySigner = signing_key
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid --flashbots-signing-key: {}", e))?;
+            let mut protect_args = args.clone();
+            protect_args.external_nonce_coordination = true;
+            let protect = new_executor(Arc::new(provider.clone()), &protect_args, Some(outcomes.clone()));
+            let executor = Box::new(FlashbotsExecutor::new(
+                protect,
+                Arc::new(provider.clone()),
+                wallet,
+                relay.parse()?,
+                auth_signer,
+            ));
+            let executor = ExecutorMap::new(executor, |action| match action {
+                Action::SubmitTx(tx) => Some(tx),
+                _ => None,
+            });
+            engine.add_executor(Box::new(SupervisedExecutor::new("liquidation", Box::new(executor), args.max_task_restarts)));
+        } else if !args.relays.is_empty() {
+            // Set up multi relay executor, relayed txs bypass the local nonce cache.
+            let relays = args.relays.iter().map(|relay| relay.parse()).collect::<Result<Vec<Url>, _>>()?;
+            let protect = new_executor(Arc::new(provider.clone()), &args, Some(outcomes.clone()));
+            let executor = Box::new(MultiRelayExecutor::new(
+                protect,
+                Arc::new(provider.clone()),
+                wallet,
+                relays,
+                args.relay_public_fallback,
+            ));
+            let executor = ExecutorMap::new(executor, |action| match action {
+                Action::SubmitTx(tx) => Some(tx),
+                _ => None,
+            });
+            engine.add_executor(Box::new(SupervisedExecutor::new("liquidation", Box::new(executor), args.max_task_restarts)));
+        } else {
+            let executor = Box::new(new_executor(Arc::new(provider.clone()), &args, Some(outcomes.clone())));
+
+            let executor = ExecutorMap::new(executor, |action| match action {
+                Action::SubmitTx(tx) => Some(tx),
+                _ => None,
+            });
+
+            engine.add_executor(Box::new(SupervisedExecutor::new("liquidation", Box::new(executor), args.max_task_restarts)));
+        }
+
+        // Set up refuel executor with the funding source's own key, a dry run moves no funds.
+        if !refuels_enabled(&args) {
+            if args.dry_run {
+                info!("dry run, refuels disabled");
+            }
+        } else if let (Some(funding_source), Some(funding_key)) = (args.funding_source, &args.funding_private_key) {
+            let funding_signer: PrivateKeySigner = funding_key
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid --funding-private-key: {}", e))?;
+            if funding_signer.address() != funding_source {
+                anyhow::bail!("funding private key doesn't match funding source {:?}", funding_source);
+            }
+            let funding_provider = ProviderBuilder::new()
+                .with_recommended_fillers()
+                .wallet(EthereumWallet::from(funding_signer))
+                .on_client(RpcClient::new(rpc.clone(), false));
+            let refuel_executor = Box::new(RefuelExecutor::new(
+                Arc::new(funding_provider),
+                funding_source,
+                liquidator,
+                U256::from(args.max_refuel_wei),
+            ));
+            let refuel_executor = ExecutorMap::new(refuel_executor, |action| match action {
+                Action::Refuel(refuel) => Some(refuel),
+                _ => None,
+            });
+            engine.add_executor(Box::new(SupervisedExecutor::new("refuel", Box::new(refuel_executor), args.max_task_restarts)));
+        }
+    }
+    // Start engine.
+    if let Ok(mut set) = engine.run().await {
+        tokio::select! {
+            _ = async {
+                while let Some(res) = set.join_next().await {
+                    info!("res: {:?}", res);
+                }
+            } => {}
+            res = shutdown::wait_for_signal() => {
+                res?;
+                info!("shutdown requested, no new liquidations will be started");
+                shutdown::drain(Duration::from_secs(args.shutdown_timeout_secs)).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Average block time over the last BLOCK_TIME_SAMPLE blocks.
+async fn estimate_block_time_ms<T, P>(provider: &P) -> Result<u64>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum>,
+{
+    const BLOCK_TIME_SAMPLE: u64 = 100;
+    let latest = provider.get_block_number().await?;
+    let earlier = latest.saturating_sub(BLOCK_TIME_SAMPLE);
+    let timestamp = |number: u64| async move {
+        provider
+            .get_block(number.into(), BlockTransactionsKind::Hashes)
+            .await?
+            .map(|block| block.header.timestamp)
+            .ok_or_else(|| anyhow::anyhow!("block {} not found", number))
+    };
+    let elapsed_secs = timestamp(latest).await?.saturating_sub(timestamp(earlier).await?);
+    Ok(std::cmp::max(1, elapsed_secs * 1000 / std::cmp::max(1, latest - earlier)))
+}
+
+/// The signer from the AWS KMS key with --signer-kind aws-kms, otherwise
+/// from exactly one of --private-key, --keystore or the PRIVATE_KEY env var,
+/// or none in observe only and test mode.
+async fn load_signer(args: &Args) -> Result<Option<LiquidatorSigner>> {
+    if args.observe_only || args.test_mode {
+        return Ok(None);
+    }
+    let env_key = std::env::var(PRIVATE_KEY_ENV).ok().filter(|key| !key.is_empty());
+    let sources = [args.private_key.is_some(), args.keystore.is_some(), env_key.is_some()];
+    let local_keys = sources.iter().filter(|given| **given).count();
+    if args.signer_kind == SignerKind::AwsKms {
+        if local_keys > 0 {
+            anyhow::bail!("--signer-kind aws-kms signs in KMS, drop --private-key, --keystore and {}", PRIVATE_KEY_ENV);
+        }
+        let key_id = args.kms_key_id.clone().unwrap_or_default();
+        let aws_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let client = aws_sdk_kms::Client::new(&aws_config);
+        let signer = AwsSigner::new(client, key_id.clone(), Some(args.chain_id))
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to load KMS key {}: {}", key_id, e))?;
+        info!("signer {:?} from KMS key {}", signer.address(), key_id);
+        return Ok(Some(LiquidatorSigner::AwsKms(signer)));
+    }
+    match local_keys {
+        0 => anyhow::bail!("no signing key, give one of --private-key, --keystore or {}", PRIVATE_KEY_ENV),
+        1 => {}
+        _ => anyhow::bail!("several signing keys given, use only one of --private-key, --keystore or {}", PRIVATE_KEY_ENV),
+    }
+    let signer = if let Some(keystore) = &args.keystore {
+        let password = args.keystore_password.as_deref().unwrap_or_default();
+        let signer = PrivateKeySigner::decrypt_keystore(keystore, password)
+            .map_err(|e| anyhow::anyhow!("failed to decrypt keystore {}: {}", keystore, e))?;
+        info!("signer {:?} from keystore {}", signer.address(), keystore);
+        signer
+    } else {
+        let key = args.private_key.clone().or(env_key).unwrap_or_default();
+        key.parse().map_err(|e| anyhow::anyhow!("failed to parse private key: {}", e))?
+    };
+    Ok(Some(LiquidatorSigner::Local(signer)))
+}
+
+/// Let the node send txs from `liquidator` without its key. Only anvil (or a
+/// node with the same test API) allows this, unlocked dev accounts included.
+async fn impersonate<T, P>(provider: &P, liquidator: Address) -> Result<()>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum>,
+{
+    provider
+        .raw_request::<_, ()>("anvil_impersonateAccount".into(), (liquidator,))
+        .await
+        .map_err(|e| anyhow::anyhow!("--test-mode needs an anvil node, impersonating {:?} failed: {}", liquidator, e))?;
+    let balance = provider.get_balance(liquidator).await?;
+    info!("test mode, sending txs from impersonated liquidator {:?} with balance {}", liquidator, balance);
+    Ok(())
+}
+
+/// Config for one of the deployments. Addresses come from the registry when
+/// it has the deployment, from `deployments/deployed_addresses.json` otherwise.
+/// With several deployments each one not in the registry reads
+/// `deployments/<name>/deployed_addresses.json` instead, failing when it's
+/// missing, and each keeps its own state cache.
+fn deployment_config(
+    config: &Config,
+    registry: &HashMap<String, DeploymentAddresses>,
+    deployment: &Deployment,
+    multiple: bool,
+) -> Result<Config> {
+    let mut config = config.clone();
+    config.deployment_addresses = registry.get(deployment.name()).cloned();
+    if multiple {
+        config.deployed_addresses_file = format!("deployments/{}/deployed_addresses.json", deployment.name());
+        config.state_cache_file = format!("borrowers_{}.json", deployment.name());
+    }
+    if config.deployment_addresses.is_none() && !Path::new(&config.deployed_addresses_file).exists() {
+        anyhow::bail!(
+            "no addresses for deployment {}, add it to --deployments-file or create {}",
+            deployment.name(), config.deployed_addresses_file
+        );
+    }
+    Ok(config)
+}
+
+/// The strategy config the command line describes, before any per-deployment
+/// addresses and files.
+fn strategy_config(args: &Args) -> Config {
+    Config {
+        chain_id: args.chain_id,
+        skip_positions_inactive_since: args.skip_positions_inactive_since,
+        max_account_attempts: args.max_account_attempts,
+        clear_skipped_accounts: args.clear_skipped_accounts,
+        repay_sanity_tolerance_bps: args.repay_sanity_tolerance_bps,
+        observe_only: args.observe_only,
+        min_pool_refresh_blocks: args.min_pool_refresh_blocks,
+        accounting_token: args.accounting_token,
+        native_token: args.native_token,
+        fast_watch_margin_bps: args.fast_watch_margin_bps,
+        margin_call_buffer_bps: args.margin_call_buffer_bps,
+        priority_accounts_file: args.priority_accounts_file.clone(),
+        target_borrowers: args.target_borrower.clone(),
+        verify_log_boundaries: args.verify_log_boundaries,
+        backfill_chunk_size: args.backfill_chunk_size,
+        // refuels go nowhere without a refuel executor
+        min_native_balance_wei: if refuels_enabled(args) {
+            U256::from(args.min_native_balance_wei)
+        } else {
+            U256::ZERO
+        },
+        refuel_amount_wei: U256::from(args.refuel_amount_wei),
+        minimum_liquidity: args.minimum_liquidity.map(U256::from),
+        pause_on_upgrade: args.pause_on_upgrade,
+        logs_from_collector: args.log_collector,
+        blacklist_ttl_secs: args.blacklist_ttl_secs,
+        scan_concurrency: args.scan_concurrency,
+        min_profit: args.min_profit,
+        multicall_address: args.multicall_address,
+        multicall_batch_size: args.multicall_batch_size,
+        profit_target: args.profit_target,
+        breaker_threshold: args.breaker_threshold,
+        breaker_cooldown_secs: args.breaker_cooldown_secs,
+        defer_on_startup: args.defer_on_startup.clone(),
+        flashloan: args.flashloan_provider.map(|provider| FlashloanConfig {
+            provider,
+            router: args.flashloan_router.unwrap_or_default(),
+            swap_router: args.swap_router.unwrap_or_default(),
+            fee_bps: args.flashloan_fee_bps,
+            max_slippage_bps: args.max_slippage_bps,
+        }),
+        monitor_margin_level_threshold: args.monitor_margin_level_threshold,
+        positions_per_tick: args.positions_per_tick,
+        rpc_max_retries: args.rpc_max_retries,
+        bid_percentage: args.bid_percentage.unwrap_or(0),
+        deployed_addresses_file: DEPLOYED_ADDRESSES.to_string(),
+        deployment_addresses: None,
+        state_cache_file: STATE_CACHE_FILE.to_string(),
+    }
+}
+
+/// Whether a refuel executor is set up, which needs the funding source and its key
+/// and a run that sends txs. The strategy only requests refuels then.
+fn refuels_enabled(args: &Args) -> bool {
+    args.funding_source.is_some() && args.funding_private_key.is_some() && !args.dry_run && !args.observe_only && !args.test_mode
+}
+
+fn new_strategy<T, P>(
+    client: Arc<P>,
+    args: &Args,
+    config: Config,
+    deployment: Deployment,
+    liquidator: Address,
+    new_pools: Option<broadcast::Sender<NewPool>>,
+    margin_calls: Option<broadcast::Sender<MarginCall>>,
+) -> Result<MmStrategy<T, P, Ethereum>>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum> + 'static,
+{
+    MmStrategy::new(
+        client,
         config,
-        args.deployment,
+        deployment,
         liquidator,
         args.last_block_number,
         args.total_profit,
@@ -126,25 +1255,179 @@ async fn main() -> Result<()> {
         args.update_all_pools_secs,
         args.activity_level_clean_secs,
         args.calc_all_positions_secs,
-    );
-    engine.add_strategy(Box::new(strategy));
+        Arc::new(DefaultProfitModel::new(args.total_profit)),
+        new_pools,
+        margin_calls,
+    )
+}
 
-    let executor = Box::new(
-        ProtectExecutor::new(
-            Arc::new(provider.clone()), 
-            Arc::new(provider.clone())
-        )
-    );
+fn new_executor<T, P>(
+    client: Arc<P>,
+    args: &Args,
+    outcomes: Option<broadcast::Sender<LiquidationOutcome>>,
+) -> ProtectExecutor<T, P, Ethereum>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum>,
+{
+    ProtectExecutor::new(
+        client.clone(),
+        client,
+        args.fallback_priority_fee_wei,
+        args.gas_mode.unwrap_or(GasMode::Eip1559),
+        args.estimate_with_overrides,
+        args.external_nonce_coordination,
+        args.simulate_before_send,
+        args.max_concurrent_simulations,
+        args.nonce_resync_idle_secs,
+        args.cascade_chunk_size,
+        args.cascade_chunk_delay_ms,
+        U256::from(args.min_native_reserve_wei),
+        fee_share(args),
+        args.dry_run,
+        args.receipt_timeout_secs,
+        args.gas_limit_multiplier,
+        args.confirmations,
+        args.tip_escalation_pct,
+        args.max_priority_fee,
+        args.tip_escalation_timeout_secs,
+        outcomes,
+    )
+}
 
-    let executor = ExecutorMap::new(executor, |action| match action {
-        Action::SubmitTx(tx) => Some(tx),
-    });
+fn fee_share(args: &Args) -> Option<FeeShare> {
+    if args.fee_share_bps == 0 {
+        return None;
+    }
+    Some(FeeShare {
+        bps: args.fee_share_bps,
+        recipient: args.fee_recipient?,
+        token: args.accounting_token?,
+        batch_min: U256::from(args.fee_batch_min),
+    })
+}
 
-    engine.add_executor(Box::new(executor));
-    // Start engine.
-    if let Ok(mut set) = engine.run().await {
-        while let Some(res) = set.join_next().await {
-            info!("res: {:?}", res);
+/// Fork the chain at head with anvil and run one full strategy and executor
+/// pass against the fork, never touching the real network.
+async fn validate_on_fork(args: &Args, mut config: Config, deployment: Deployment, signer: LiquidatorSigner) -> Result<bool> {
+    // state synced from the fork must never overwrite the live bot's cache
+    config.state_cache_file = format!("fork_{}", config.state_cache_file);
+    let anvil = Anvil::new()
+        .fork(args.rpc[0].clone())
+        .chain_id(args.chain_id)
+        .try_spawn()?;
+    info!("validating on fork {}", anvil.endpoint());
+
+    let fork_url = anvil.endpoint_url();
+    let liquidator = signer.address();
+    let read_provider = ProviderBuilder::new().on_http(fork_url.clone());
+    let provider = ProviderBuilder::new()
+        .wallet(signer.wallet())
+        .on_http(fork_url);
+
+    let mut strategy = new_strategy(Arc::new(read_provider), args, config, deployment, liquidator, None, None)?;
+    strategy.sync_state().await?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let actions = strategy.process_event(Event::NewTick(NewTick { timestamp })).await;
+    info!("fork validation produced {} actions", actions.len());
+
+    let executor = new_executor(Arc::new(provider), args, None);
+    let mut passed = true;
+    for action in actions {
+        let Action::SubmitTx(liquidation) = action else {
+            continue;
+        };
+        if liquidation.submit.tx.input().map_or(true, |input| input.is_empty()) {
+            error!("fork validation: empty calldata in tx {:?}", liquidation.submit.tx);
+            passed = false;
+            continue;
+        }
+        if let Err(e) = executor.execute(liquidation).await {
+            error!("fork validation: submission failed: {:#}", e);
+            passed = false;
+        }
+    }
+
+    info!("fork validation {}", if passed { "passed" } else { "failed" });
+    Ok(passed)
+}
+
+/// Run the strategy's scan every --backtest-step blocks from `from_block` to
+/// `to_block` with the head pinned there, reporting what it would liquidate.
+async fn backtest(
+    args: &Args,
+    transport: PinnedBlock<RateLimited<FailoverTransport>>,
+    mut config: Config,
+    deployment: Deployment,
+    from_block: u64,
+    to_block: u64,
+) -> Result<()> {
+    if from_block > to_block {
+        anyhow::bail!("--from-block {} is after --to-block {}", from_block, to_block);
+    }
+    // start from the deployment's first block, never from the live state cache
+    config.logs_from_collector = false;
+    config.state_cache_file = format!("backtest_{}", config.state_cache_file);
+    if std::fs::remove_file(&config.state_cache_file).is_ok() {
+        info!("removed previous backtest state {}", config.state_cache_file);
+    }
+    info!("backtesting blocks {} to {} every {} blocks", from_block, to_block, args.backtest_step);
+
+    let provider = ProviderBuilder::new().on_client(RpcClient::new(transport.clone(), false));
+    let mut strategy = new_strategy(Arc::new(provider), args, config, deployment, Address::ZERO, None, None)?;
+    strategy.sync_state().await?;
+    let mut report = BacktestReport::create(&args.backtest_output)?;
+    let mut block = from_block;
+    loop {
+        transport.pin(block);
+        let underwaters = strategy.scan_at(block).await?;
+        report.record(block, &underwaters)?;
+        if block >= to_block {
+            break;
+        }
+        block = std::cmp::min(block + args.backtest_step.max(1), to_block);
+    }
+    report.finish()
+}
+
+/// Run one full scan of `deployment` at the chain head and print the positions
+/// it would liquidate, most under water first.
+async fn scan<T, P>(client: Arc<P>, args: &Args, mut config: Config, deployment: Deployment, calldata: bool) -> Result<()>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, Ethereum> + 'static,
+{
+    // fetch the logs itself and keep a cache of its own beside a running bot's
+    config.logs_from_collector = false;
+    config.defer_on_startup.clear();
+    config.state_cache_file = format!("scan_{}", config.state_cache_file);
+
+    let head = client.get_block_number().await?;
+    let mut strategy = new_strategy(client, args, config, deployment.clone(), Address::ZERO, None, None)?;
+    strategy.sync_state().await?;
+    let mut underwaters = strategy.scan_at(head).await?;
+    underwaters.sort_by(|a, b| a.margin_level.cmp(&b.margin_level));
+
+    println!("{} at block {}: {} liquidatable positions", deployment.name(), head, underwaters.len());
+    if underwaters.is_empty() {
+        return Ok(());
+    }
+    println!("{:<42}  {:>12}  {:>14}  {:>24}", "ACCOUNT", "POSITION", "MARGIN LEVEL", "EXPECTED PROFIT");
+    for underwater in underwaters.iter() {
+        println!(
+            "{:<42}  {:>12}  {:>14}  {:>24}",
+            underwater.account.to_string(),
+            underwater.position_id.to_string(),
+            format_margin_level(underwater.margin_level),
+            format_amount(underwater.common_profit, Some(U256::from(COMMON_DECIMALS)), ""),
+        );
+        if calldata {
+            let tx = strategy.build_liquidation_tx(underwater)?;
+            println!(
+                "    to {:?} calldata {}",
+                tx.to(),
+                tx.input().map_or("0x".to_string(), |input| input.to_string())
+            );
         }
     }
     Ok(())
@@ -0,0 +1,250 @@
+use alloy::primitives::I256;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+/// Why a liquidation opportunity was not submitted.
+///
+/// Kept as a fixed enum so the exported label set stays bounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Unprofitable,
+    Cooldown,
+    AccountSkipped,
+    RepaySanity,
+    MinimumLiquidity,
+    GasCap,
+    SimulationRevert,
+    ZeroRepay,
+    NativeReserve,
+    Blacklisted,
+    KnownRevert,
+}
+
+impl SkipReason {
+    pub const ALL: [SkipReason; 11] = [
+        SkipReason::Unprofitable,
+        SkipReason::Cooldown,
+        SkipReason::AccountSkipped,
+        SkipReason::RepaySanity,
+        SkipReason::MinimumLiquidity,
+        SkipReason::GasCap,
+        SkipReason::SimulationRevert,
+        SkipReason::ZeroRepay,
+        SkipReason::NativeReserve,
+        SkipReason::Blacklisted,
+        SkipReason::KnownRevert,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            SkipReason::Unprofitable => "unprofitable",
+            SkipReason::Cooldown => "cooldown",
+            SkipReason::AccountSkipped => "account_skipped",
+            SkipReason::RepaySanity => "repay_sanity",
+            SkipReason::MinimumLiquidity => "minimum_liquidity",
+            SkipReason::GasCap => "gas_cap",
+            SkipReason::SimulationRevert => "simulation_revert",
+            SkipReason::ZeroRepay => "zero_repay",
+            SkipReason::NativeReserve => "native_reserve",
+            SkipReason::Blacklisted => "blacklisted",
+            SkipReason::KnownRevert => "known_revert",
+        }
+    }
+}
+
+/// Classification of a reverted liquidation, see `LiquidationFailure`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    SafeErc20FailedOperation,
+    SafeErc20FailedDecreaseAllowance,
+    Protocol,
+    Unknown,
+}
+
+impl FailureKind {
+    pub const ALL: [FailureKind; 4] = [
+        FailureKind::SafeErc20FailedOperation,
+        FailureKind::SafeErc20FailedDecreaseAllowance,
+        FailureKind::Protocol,
+        FailureKind::Unknown,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            FailureKind::SafeErc20FailedOperation => "safe_erc20_failed_operation",
+            FailureKind::SafeErc20FailedDecreaseAllowance => "safe_erc20_failed_decrease_allowance",
+            FailureKind::Protocol => "protocol",
+            FailureKind::Unknown => "unknown",
+        }
+    }
+}
+
+static SKIPPED_OPPORTUNITIES: [AtomicU64; SkipReason::ALL.len()] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+static LIQUIDATION_FAILURES: [AtomicU64; FailureKind::ALL.len()] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+static LIQUIDATIONS_ATTEMPTED: AtomicU64 = AtomicU64::new(0);
+static LIQUIDATIONS_SUCCEEDED: AtomicU64 = AtomicU64::new(0);
+static LIQUIDATIONS_REVERTED: AtomicU64 = AtomicU64::new(0);
+static LIQUIDATIONS_DROPPED: AtomicU64 = AtomicU64::new(0);
+static SIMULATIONS_IN_FLIGHT: AtomicU64 = AtomicU64::new(0);
+
+/// Upper bounds of the realized profit histogram buckets, in 18-decimal units of the profit token.
+/// Losses fall in every bucket.
+const PROFIT_BUCKETS: [f64; 7] = [1e14, 1e15, 1e16, 1e17, 1e18, 1e19, 1e20];
+static PROFIT_BUCKET_COUNTS: [AtomicU64; PROFIT_BUCKETS.len()] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+static PROFIT_COUNT: AtomicU64 = AtomicU64::new(0);
+// f64 bits, atomics have no float type
+static PROFIT_SUM: AtomicU64 = AtomicU64::new(0);
+
+/// Count positions sent to the executor for liquidation.
+pub fn record_attempted(positions: u64) {
+    LIQUIDATIONS_ATTEMPTED.fetch_add(positions, Ordering::Relaxed);
+}
+
+/// Count positions whose liquidation tx was confirmed.
+pub fn record_succeeded(positions: u64) {
+    LIQUIDATIONS_SUCCEEDED.fetch_add(positions, Ordering::Relaxed);
+}
+
+/// Count positions whose liquidation tx reverted on-chain.
+pub fn record_reverted(positions: u64) {
+    LIQUIDATIONS_REVERTED.fetch_add(positions, Ordering::Relaxed);
+}
+
+/// Count positions whose liquidation tx got no receipt in time.
+pub fn record_dropped(positions: u64) {
+    LIQUIDATIONS_DROPPED.fetch_add(positions, Ordering::Relaxed);
+}
+
+/// Count a pre-flight simulation or gas estimate starting, until [record_simulation_finished].
+pub fn record_simulation_started() {
+    SIMULATIONS_IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count a pre-flight simulation or gas estimate as no longer running.
+pub fn record_simulation_finished() {
+    SIMULATIONS_IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Observe the realized net profit of a confirmed liquidation tx, its token
+/// deltas net of the gas it paid, negative for a loss.
+pub fn record_profit(profit: I256) {
+    let profit: f64 = profit.to_string().parse().unwrap_or_default();
+    for (bound, count) in PROFIT_BUCKETS.iter().zip(PROFIT_BUCKET_COUNTS.iter()) {
+        if profit <= *bound {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    PROFIT_COUNT.fetch_add(1, Ordering::Relaxed);
+    let _ = PROFIT_SUM.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+        Some((f64::from_bits(bits) + profit).to_bits())
+    });
+}
+
+/// Count a reverted liquidation by the kind of its failure.
+pub fn record_failure(kind: FailureKind) {
+    LIQUIDATION_FAILURES[kind as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Increment the skipped opportunities counter for `reason`.
+pub fn record_skip(reason: SkipReason) {
+    SKIPPED_OPPORTUNITIES[reason as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Current skipped opportunities count per reason.
+pub fn skip_counts() -> Vec<(&'static str, u64)> {
+    SkipReason::ALL
+        .iter()
+        .map(|reason| (reason.as_str(), SKIPPED_OPPORTUNITIES[*reason as usize].load(Ordering::Relaxed)))
+        .collect()
+}
+
+/// Render all metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    out.push_str("# TYPE skipped_opportunities_total counter\n");
+    for (reason, count) in skip_counts() {
+        out.push_str(&format!("skipped_opportunities_total{{reason=\"{}\"}} {}\n", reason, count));
+    }
+    out.push_str("# TYPE liquidation_failures_total counter\n");
+    for kind in FailureKind::ALL.iter() {
+        out.push_str(&format!(
+            "liquidation_failures_total{{kind=\"{}\"}} {}\n",
+            kind.as_str(),
+            LIQUIDATION_FAILURES[*kind as usize].load(Ordering::Relaxed)
+        ));
+    }
+    for (name, counter) in [
+        ("liquidations_attempted_total", &LIQUIDATIONS_ATTEMPTED),
+        ("liquidations_succeeded_total", &LIQUIDATIONS_SUCCEEDED),
+        ("liquidations_reverted_total", &LIQUIDATIONS_REVERTED),
+        ("liquidations_dropped_total", &LIQUIDATIONS_DROPPED),
+    ] {
+        out.push_str(&format!("# TYPE {} counter\n{} {}\n", name, name, counter.load(Ordering::Relaxed)));
+    }
+    out.push_str(&format!(
+        "# TYPE simulations_in_flight gauge\nsimulations_in_flight {}\n",
+        SIMULATIONS_IN_FLIGHT.load(Ordering::Relaxed)
+    ));
+    out.push_str("# TYPE liquidation_profit histogram\n");
+    for (bound, count) in PROFIT_BUCKETS.iter().zip(PROFIT_BUCKET_COUNTS.iter()) {
+        out.push_str(&format!("liquidation_profit_bucket{{le=\"{:e}\"}} {}\n", bound, count.load(Ordering::Relaxed)));
+    }
+    let total = PROFIT_COUNT.load(Ordering::Relaxed);
+    out.push_str(&format!("liquidation_profit_bucket{{le=\"+Inf\"}} {}\n", total));
+    out.push_str(&format!("liquidation_profit_sum {}\n", f64::from_bits(PROFIT_SUM.load(Ordering::Relaxed))));
+    out.push_str(&format!("liquidation_profit_count {}\n", total));
+    out
+}
+
+/// Serve [render] to Prometheus scrapes on `port`, answering every request
+/// with the current metrics.
+pub async fn serve(port: u16) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    info!("metrics exporter listening on port {}", port);
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            // the request itself doesn't matter, drain what was sent before replying
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            let body = render();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                warn!("metrics scrape failed: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::time::{Duration, Instant};
+use tower::Service;
+use tracing::info;
+use alloy::{
+    rpc::json_rpc::{RequestPacket, ResponsePacket},
+    transports::{TransportError, TransportFut},
+};
+
+/// Token bucket refilled at `rate` tokens per second up to `rate` tokens,
+/// so bursts of up to one second of quota go through at once.
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    /// Take `n` tokens, or return how long to wait until they are available.
+    fn take(&mut self, n: f64) -> Option<Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+        if self.tokens >= n {
+            self.tokens -= n;
+            return None;
+        }
+        Some(Duration::from_secs_f64((n - self.tokens) / self.rate))
+    }
+}
+
+/// A transport that keeps requests to `inner` under a requests per second
+/// cap. Requests over the cap wait for the bucket to refill instead of
+/// failing, a batch counts as one request per call in it. Clones share the
+/// bucket, so every provider built on them draws from the same quota.
+#[derive(Clone)]
+pub struct RateLimited<S> {
+    inner: S,
+    bucket: Option<Arc<Mutex<Bucket>>>,
+}
+
+impl<S> RateLimited<S> {
+    /// `max_rps` of 0 disables the limit.
+    pub fn new(inner: S, max_rps: u64) -> Self {
+        let bucket = (max_rps > 0).then(|| {
+            info!("rpc rate limited to {} requests per second", max_rps);
+            Arc::new(Mutex::new(Bucket {
+                rate: max_rps as f64,
+                tokens: max_rps as f64,
+                last_refill: Instant::now(),
+            }))
+        });
+        Self { inner, bucket }
+    }
+}
+
+/// Wait until `n` tokens could be taken from the bucket.
+async fn acquire(bucket: &Mutex<Bucket>, n: usize) {
+    // a batch larger than the bucket drains it fully instead of waiting forever
+    let rate = bucket.lock().unwrap().rate;
+    let n = (n as f64).min(rate);
+    loop {
+        let wait = bucket.lock().unwrap().take(n);
+        match wait {
+            None => return,
+            Some(wait) => tokio::time::sleep(wait).await,
+        }
+    }
+}
+
+impl<S> Service<RequestPacket> for RateLimited<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError, Future = TransportFut<'static>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let Some(bucket) = self.bucket.clone() else {
+            return self.inner.call(request);
+        };
+        let mut inner = self.inner.clone();
+        Box::pin(async move {
+            let calls = match &request {
+                RequestPacket::Single(_) => 1,
+                RequestPacket::Batch(batch) => batch.len(),
+            };
+            acquire(&bucket, calls).await;
+            inner.call(request).await
+        })
+    }
+}
@@ -0,0 +1,87 @@
+use alloy::primitives::B256;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// How often the drain checks whether in-flight liquidations have settled.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+static SUBMISSIONS_IN_PROGRESS: AtomicUsize = AtomicUsize::new(0);
+/// Sent liquidation txs awaiting a receipt, with the opportunities they cover.
+static PENDING_TXS: Mutex<BTreeMap<B256, Vec<B256>>> = Mutex::new(BTreeMap::new());
+
+/// Whether a shutdown signal was received, no new liquidations are started after it.
+pub fn requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Marks a submission as in progress until dropped.
+pub struct Submission(());
+
+impl Submission {
+    pub fn start() -> Self {
+        SUBMISSIONS_IN_PROGRESS.fetch_add(1, Ordering::Relaxed);
+        Submission(())
+    }
+}
+
+impl Drop for Submission {
+    fn drop(&mut self) {
+        SUBMISSIONS_IN_PROGRESS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Track a sent liquidation tx until [settle_tx] is called with its hash.
+pub fn track_tx(tx_hash: B256, opportunity_ids: Vec<B256>) {
+    PENDING_TXS.lock().unwrap().insert(tx_hash, opportunity_ids);
+}
+
+/// Stop tracking a liquidation tx once its final status is known.
+pub fn settle_tx(tx_hash: B256) {
+    PENDING_TXS.lock().unwrap().remove(&tx_hash);
+}
+
+/// Wait for SIGINT or SIGTERM and mark the shutdown as requested.
+pub async fn wait_for_signal() -> anyhow::Result<()> {
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res?,
+        _ = sigterm.recv() => {}
+    }
+    SHUTDOWN_REQUESTED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Wait up to `timeout` for running submissions and sent txs to settle,
+/// then log whatever is still unresolved.
+pub async fn drain(timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    let mut announced = false;
+    loop {
+        let submissions = SUBMISSIONS_IN_PROGRESS.load(Ordering::Relaxed);
+        let pending = PENDING_TXS.lock().unwrap().len();
+        if submissions == 0 && pending == 0 {
+            info!("all in-flight liquidations settled");
+            return;
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+        if !announced {
+            info!("waiting up to {:?} for {} submissions and {} pending txs", timeout, submissions, pending);
+            announced = true;
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+
+    let submissions = SUBMISSIONS_IN_PROGRESS.load(Ordering::Relaxed);
+    if submissions > 0 {
+        warn!("{} submissions still in progress at shutdown, they may or may not have been sent", submissions);
+    }
+    for (tx_hash, opportunity_ids) in PENDING_TXS.lock().unwrap().iter() {
+        warn!("Liquidation {:?} still pending at shutdown, opportunities {:?}", tx_hash, opportunity_ids);
+    }
+}
@@ -0,0 +1,148 @@
+use alloy::{
+    contract as alloy_contract,
+    primitives::{Address, U256},
+    sol,
+};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+sol! {
+    #[sol(rpc)]
+    interface IERC20Metadata {
+        function decimals() external view returns (uint8);
+    }
+}
+
+/// Decimals profits are normalized to before they are summed or compared
+/// across tokens, e.g. against --min-profit or --profit-target.
+pub const COMMON_DECIMALS: u8 = 18;
+
+/// Decimals assumed for a token whose `decimals()` can't be read.
+const DEFAULT_DECIMALS: u8 = 18;
+
+/// ERC20 decimals by token, each queried once.
+#[derive(Debug, Clone, Default)]
+pub struct DecimalsCache {
+    decimals: HashMap<Address, u8>,
+}
+
+impl DecimalsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decimals of a token already resolved.
+    pub fn get(&self, token: Address) -> Option<u8> {
+        self.decimals.get(&token).copied()
+    }
+
+    /// Query `decimals()` of every token not cached yet. A token that doesn't
+    /// implement it is cached with 18 decimals and a warning.
+    pub async fn resolve<T, P, N>(&mut self, client: &P, tokens: impl IntoIterator<Item = Address>)
+    where
+        T: alloy_contract::private::Transport + ::core::clone::Clone,
+        P: alloy_contract::private::Provider<T, N>,
+        N: alloy_contract::private::Network,
+    {
+        for token in tokens {
+            if self.decimals.contains_key(&token) {
+                continue;
+            }
+            let decimals = match IERC20Metadata::new(token, client).decimals().call().await {
+                Ok(ret) => {
+                    info!("token {:?} has {} decimals", token, ret._0);
+                    ret._0
+                }
+                Err(e) => {
+                    warn!("can't read decimals of token {:?}, assuming {}: {}", token, DEFAULT_DECIMALS, e);
+                    DEFAULT_DECIMALS
+                }
+            };
+            self.decimals.insert(token, decimals);
+        }
+    }
+}
+
+/// Rescale `amount` from `decimals` to [COMMON_DECIMALS], rounding down.
+pub fn to_common_unit(amount: U256, decimals: u8) -> U256 {
+    rescale(amount, decimals, COMMON_DECIMALS)
+}
+
+/// Rescale `amount` from [COMMON_DECIMALS] to `decimals`, rounding down.
+pub fn from_common_unit(amount: U256, decimals: u8) -> U256 {
+    rescale(amount, COMMON_DECIMALS, decimals)
+}
+
+fn rescale(amount: U256, from: u8, to: u8) -> U256 {
+    if from <= to {
+        amount.saturating_mul(U256::from(10).pow(U256::from(to - from)))
+    } else {
+        amount / U256::from(10).pow(U256::from(from - to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::providers::ProviderBuilder;
+    use mockito::{Matcher, Server, ServerGuard};
+    use serde_json::{json, Value};
+
+    const USDC: Address = Address::repeat_byte(0x11);
+    const WETH: Address = Address::repeat_byte(0x22);
+
+    // answer decimals() of `token` with `decimals`, expecting exactly one call
+    async fn mock_decimals(server: &mut ServerGuard, token: Address, decimals: u8) -> mockito::Mock {
+        let result = format!("0x{:064x}", decimals);
+        server
+            .mock("POST", "/")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::PartialJson(json!({ "method": "eth_call" })),
+                Matcher::Regex(format!(r#""to":"{:?}""#, token)),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body_from_request(move |request| {
+                let id = request
+                    .body()
+                    .ok()
+                    .and_then(|body| serde_json::from_slice::<Value>(body).ok())
+                    .map_or(Value::Null, |body| body["id"].clone());
+                json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string().into_bytes()
+            })
+            .expect(1)
+            .create_async()
+            .await
+    }
+
+    #[tokio::test]
+    async fn resolves_each_token_once() {
+        let mut server = Server::new_async().await;
+        let usdc = mock_decimals(&mut server, USDC, 6).await;
+        let weth = mock_decimals(&mut server, WETH, 18).await;
+        let client = ProviderBuilder::new().on_http(server.url().parse().unwrap());
+
+        let mut cache = DecimalsCache::new();
+        cache.resolve(&client, [USDC, WETH]).await;
+        cache.resolve(&client, [USDC, WETH]).await;
+
+        assert_eq!(cache.get(USDC), Some(6));
+        assert_eq!(cache.get(WETH), Some(18));
+        usdc.assert_async().await;
+        weth.assert_async().await;
+    }
+
+    #[test]
+    fn rescales_to_and_from_common_units() {
+        // 1.5 of a 6 decimal token
+        let usdc = U256::from(1_500_000);
+        let common = U256::from(15) * U256::from(10).pow(U256::from(17));
+        assert_eq!(to_common_unit(usdc, 6), common);
+        assert_eq!(from_common_unit(common, 6), usdc);
+        // below the token's precision rounds down
+        assert_eq!(from_common_unit(common + U256::from(1), 6), usdc);
+
+        // an 18 decimal token is already in common units
+        assert_eq!(to_common_unit(common, 18), common);
+        assert_eq!(from_common_unit(common, 18), common);
+    }
+}
@@ -0,0 +1,51 @@
+use alloy::primitives::Address;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+
+/// Contracts every deployment must have, keyed as in `deployed_addresses.json`.
+pub const REQUIRED_CONTRACTS: [&str; 4] = [
+    "DataStore#DataStore",
+    "Reader#Reader",
+    "EventEmitter#EventEmitter",
+    "ExchangeRouter#ExchangeRouter",
+];
+
+/// Contract addresses of one deployment, keyed like `DataStore#DataStore`.
+pub type DeploymentAddresses = HashMap<String, Address>;
+
+/// Load a registry of deployments from a JSON or TOML file, picked by its
+/// extension, mapping each deployment name to its contract addresses in the
+/// `deployed_addresses.json` format, e.g. in TOML:
+///
+/// ```toml
+/// [arbitrum]
+/// "DataStore#DataStore" = "0x..."
+/// "Reader#Reader" = "0x..."
+/// ```
+///
+/// Fails when a deployment misses one of the [REQUIRED_CONTRACTS].
+pub fn load_registry(path: &str) -> Result<HashMap<String, DeploymentAddresses>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Error reading deployments file {}", path))?;
+    let registry: HashMap<String, DeploymentAddresses> = if path.ends_with(".toml") {
+        toml::from_str(&content).with_context(|| format!("Error parsing deployments file {}", path))?
+    } else {
+        serde_json::from_str(&content).with_context(|| format!("Error parsing deployments file {}", path))?
+    };
+    for (name, addresses) in registry.iter() {
+        validate(name, addresses)?;
+    }
+    Ok(registry)
+}
+
+/// Check that a deployment has every one of the [REQUIRED_CONTRACTS].
+pub fn validate(name: &str, addresses: &DeploymentAddresses) -> Result<()> {
+    let missing: Vec<&str> = REQUIRED_CONTRACTS
+        .into_iter()
+        .filter(|contract| !addresses.contains_key(*contract))
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!("deployment {} is missing addresses for {:?}", name, missing);
+    }
+    Ok(())
+}
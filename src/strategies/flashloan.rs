@@ -0,0 +1,117 @@
+use alloy::{
+    primitives::{Address, U256},
+    sol,
+};
+use std::collections::BTreeMap;
+
+// ABI of crates/liquidator-contract/src/FlashLiquidator.sol, the helper
+// --flashloan-router points at
+sol! {
+    #[sol(rpc)]
+    interface IFlashLiquidator {
+        struct LiquidationParams {
+            address account;
+            uint256 positionId;
+        }
+
+        struct Loan {
+            address token;
+            uint256 amount;
+        }
+
+        struct MinProfit {
+            address token;
+            uint256 amount;
+        }
+
+        /// Borrow `loans` from `lender`, liquidate `params` through
+        /// `exchangeRouter`, swap the seized collateral back into the loan
+        /// tokens through `swapRouter` and repay the loans plus fees, reverting
+        /// unless the sender is left with at least `minProfits` of each token.
+        /// `tokens` are every base and meme token of the batch's pools, swept
+        /// to the sender.
+        function flashLiquidate(
+            address lender,
+            address exchangeRouter,
+            LiquidationParams[] calldata params,
+            Loan[] calldata loans,
+            address swapRouter,
+            address[] calldata tokens,
+            MinProfit[] calldata minProfits
+        ) external;
+    }
+}
+
+/// Extra borrowed above the scanned debt, in bps, covering interest accrued
+/// until the liquidation lands.
+const LOAN_BUFFER_BPS: u64 = 10;
+
+/// Where flashloan-funded liquidations borrow the repay amounts.
+#[derive(Debug, Clone)]
+pub struct FlashloanConfig {
+    /// Lending pool the loans are taken from.
+    pub provider: Address,
+    /// Helper contract taking the loan, liquidating, swapping and repaying in one tx,
+    /// a deployment of crates/liquidator-contract/src/FlashLiquidator.sol.
+    pub router: Address,
+    /// DEX router the helper swaps the seized collateral through.
+    pub swap_router: Address,
+    /// The provider's flashloan premium, in bps.
+    pub fee_bps: u64,
+    /// Worst price the collateral swap may get below the pools' oracle price, in bps.
+    /// Only this path swaps, direct liquidations keep the seized collateral.
+    pub max_slippage_bps: u64,
+}
+
+impl FlashloanConfig {
+    /// Amount borrowed to repay `debt`.
+    pub fn loan_amount(&self, debt: U256) -> U256 {
+        debt.saturating_add(bps_ceil(debt, LOAN_BUFFER_BPS))
+    }
+
+    /// Premium paid to the provider for borrowing the repay amount of `debt`.
+    pub fn fee(&self, debt: U256) -> U256 {
+        bps_ceil(self.loan_amount(debt), self.fee_bps)
+    }
+
+    /// Value the collateral swap may lose to slippage when swapping `amount`.
+    pub fn slippage(&self, amount: U256) -> U256 {
+        bps_ceil(amount, self.max_slippage_bps)
+    }
+
+    /// One loan per token covering the summed `(token, debt)` repay amounts.
+    pub fn loans(&self, debts: impl IntoIterator<Item = (Address, U256)>) -> Vec<IFlashLiquidator::Loan> {
+        sum_by_token(debts)
+            .into_iter()
+            .map(|(token, debt)| IFlashLiquidator::Loan { token, amount: self.loan_amount(debt) })
+            .collect()
+    }
+
+    /// One minimum per token of the summed `(token, profit)` worst-case profits,
+    /// the helper reverts when the swap leaves less, so a sandwich can't take more
+    /// than the slippage.
+    pub fn min_profits(&self, profits: impl IntoIterator<Item = (Address, U256)>) -> Vec<IFlashLiquidator::MinProfit> {
+        sum_by_token(profits)
+            .into_iter()
+            .map(|(token, amount)| IFlashLiquidator::MinProfit { token, amount })
+            .collect()
+    }
+}
+
+// nonzero amounts summed per token
+fn sum_by_token(amounts: impl IntoIterator<Item = (Address, U256)>) -> BTreeMap<Address, U256> {
+    let mut totals: BTreeMap<Address, U256> = BTreeMap::new();
+    for (token, amount) in amounts {
+        if amount.is_zero() {
+            continue;
+        }
+        let total = totals.entry(token).or_default();
+        *total = total.saturating_add(amount);
+    }
+    totals
+}
+
+// `amount` times `bps`, rounded up so fees are never underestimated
+fn bps_ceil(amount: U256, bps: u64) -> U256 {
+    amount.saturating_mul(U256::from(bps)).div_ceil(U256::from(10_000))
+}
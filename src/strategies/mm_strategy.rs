@@ -1,11 +1,17 @@
 use super::types::Config;
+use crate::collectors::block_collector::REORG_DEPTH;
+use crate::collectors::control_collector::ControlCommand;
+use crate::collectors::margin_call_collector::MarginCall;
+use crate::collectors::pool_collector::NewPool;
 use crate::collectors::time_collector::NewTick;
-use anyhow::{Result};
+use anyhow::{anyhow, Context, Result};
 use artemis_core::executors::mempool_executor::{GasBidInfo, SubmitTxToMempool};
 use artemis_core::types::Strategy;
 use async_trait::async_trait;
 use bindings_mm::{
     reader::Reader,
+    poolutils::PoolUtils,
+    rolestore::RoleStore,
     eventemitter::{EventEmitter},
     exchangerouter::LiquidationUtils::LiquidationParams,
     exchangerouter::ExchangeRouter,
@@ -17,31 +23,51 @@ use bindings_mm::{
 //     liquidator::{Liquidator, LiquidationParams, Asset},
 // };
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error; 
 use std::fs::File;
 use std::iter::zip;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::{Instant};
-use tracing::{error, info};
+use tokio::sync::broadcast;
+use std::time::{Instant, SystemTime};
+use tracing::{debug, error, info};
 use chrono::{DateTime, Duration, Utc};
-use clap::{Parser, ValueEnum};
-use super::types::{Action, Event};
+use super::types::{Action, Event, PeriodicTask};
+use super::profit_model::ProfitModel;
+use super::decimals::{DecimalsCache, COMMON_DECIMALS};
+use super::flashloan::{FlashloanConfig, IFlashLiquidator};
+use super::deployments::{self, DeploymentAddresses};
+use super::multicall;
+use super::retry::retry_rpc;
+use crate::executors::protect_executor::{LiquidationConfirmed, LiquidationOutcome, SubmitLiquidation, Valuation};
+use crate::executors::revert::LiquidationFailure;
+use crate::alerts;
+use crate::backfill::{is_range_limit_error, LogChunker};
+use crate::executors::refuel_executor::Refuel;
+use crate::format::{format_amount, format_margin_level};
+use crate::health::{self, DeploymentStatus};
+use crate::metrics::{self, SkipReason};
 use sha3::{Digest, Keccak256};
+use futures::stream::{self, StreamExt};
 
 use tracing::warn;
 
 use alloy::{
+    consensus::Transaction as _,
     contract as alloy_contract,
+    eips::BlockId,
     network::{ Network, TransactionBuilder},
     sol_types::private::{Address},
-    primitives::{FixedBytes, U256, U512},
+    primitives::{FixedBytes, I256, U256, U512},
+    rpc::types::{Filter, Log},
 };
 
-use alloy::sol_types::SolValue;
+use alloy::sol_types::{SolEvent, SolValue};
 
 type Bytes32 = FixedBytes<32>;
+// a fetched log's block, log index and, for logs without an index, the order it was fetched in
+type LogKey = (u64, Option<u64>, usize);
 
 #[derive(Debug)]
 struct DeploymentConfig {
@@ -49,6 +75,8 @@ struct DeploymentConfig {
     reader: Address,
     event_emitter: Address,
     exchange_router: Address,
+    pool_utils: Option<Address>,
+    role_store: Option<Address>,
     last_block_number: u64,
     total_profit: u128,
     update_all_pools_ticks: u64,
@@ -58,10 +86,36 @@ struct DeploymentConfig {
     //monitor_margin_level_thresold: u128,
 }
 
-#[derive(Debug, Clone, Parser, ValueEnum )]
+/// A deployment to liquidate on, the built-in ones or any name in the
+/// `--deployments-file` registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Deployment {
     LOCALNET,
     BASE,
+    Custom(String),
+}
+
+impl Deployment {
+    pub fn name(&self) -> &str {
+        match self {
+            Deployment::LOCALNET => "localnet",
+            Deployment::BASE => "base",
+            Deployment::Custom(name) => name,
+        }
+    }
+}
+
+impl FromStr for Deployment {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "" => Err("empty deployment name".to_string()),
+            "localnet" => Ok(Deployment::LOCALNET),
+            "base" => Ok(Deployment::BASE),
+            _ => Ok(Deployment::Custom(name.to_string())),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -105,7 +159,7 @@ impl ActionType {
 // admin stuff
 pub const DEPLOYED_ADDRESSES: &str = "deployments/deployed_addresses.json";
 pub const STATE_CACHE_FILE: &str = "borrowers.json";
-pub const LOG_BLOCK_RANGE: u64 = 1024;
+pub const LOG_BOUNDARY_OVERLAP: u64 = 2;
 pub const MULTICALL_CHUNK_SIZE: usize = 1000;
 pub const RETRY_DURATION_IN_SECS: i64 = 600;
 pub const POLL_POOL_CHUNK_SIZE: u64 = 100;
@@ -113,6 +167,16 @@ pub const LIQUIDATIONL_CHUNK_SIZE: u64 = 100;
 pub const LIQUIDATIONL_BATCH_SIZE: u64 = 20;
 pub const ACTIVITY_LEVEL_INIT: u64 = 100;
 pub const ACTIVITY_LEVEL_INIT_TEST: u64 = 5;
+/// Events whose activity a position accumulates at most, so a burst of events
+/// keeps it scanned every tick for at most this many decay periods.
+pub const ACTIVITY_LEVEL_MAX_EVENTS: u64 = 4;
+pub const REFUEL_COOLDOWN_IN_SECS: i64 = 600;
+pub const IMPLEMENTATION_CHECK_SECS: i64 = 300;
+/// Failed submissions further back than this don't count towards tripping the circuit breaker.
+pub const BREAKER_WINDOW_SECS: i64 = 600;
+pub const RPC_RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(200);
+/// EIP-1967 implementation slot, bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1).
+pub const EIP1967_IMPLEMENTATION_SLOT: &str = "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
 
 //production
 // self.config.update_all_pools_ticks: u64 = 16000;//about 2days poll all pools 
@@ -126,8 +190,15 @@ pub const ACTIVITY_LEVEL_INIT_TEST: u64 = 5;
 // self.config.calc_all_positions_ticks: u64 = 5;//about 50 seconds
 // self.config.monitor_margin_level_thresold: u128 = 150;
 
+// ticks between runs of a task due every `secs`, at least 1 so a period shorter
+// than a tick runs every tick rather than dividing by zero
+fn interval_ticks(secs: u64, tick_secs: u64) -> u64 {
+    (secs / tick_secs.max(1)).max(1)
+}
+
 fn get_deployment_config(
     deployment: Deployment, 
+    mm_contracts: &DeploymentAddresses,
     last_block_number: u64,
     total_profit: u128,
     pool_interval_secs: u64,
@@ -137,34 +208,35 @@ fn get_deployment_config(
     //monitor_margin_level_thresold: u128,
 ) -> DeploymentConfig {
 
-    let file = File::open(DEPLOYED_ADDRESSES).unwrap();
-    let mm_contracts: HashMap<String, Address> = serde_json::from_reader(file).unwrap();
-
     match deployment {
         Deployment::LOCALNET => DeploymentConfig {
             data_store: *mm_contracts.get("DataStore#DataStore").unwrap(),
             reader: *mm_contracts.get("Reader#Reader").unwrap(),
             event_emitter: *mm_contracts.get("EventEmitter#EventEmitter").unwrap(),
             exchange_router: *mm_contracts.get("ExchangeRouter#ExchangeRouter").unwrap(),
+            pool_utils: mm_contracts.get("PoolUtils#PoolUtils").copied(),
+            role_store: mm_contracts.get("RoleStore#RoleStore").copied(),
             last_block_number: last_block_number,
             total_profit: total_profit,
-            update_all_pools_ticks: update_all_pools_secs/pool_interval_secs,
-            activity_level_decrease_ticks: activity_level_clean_secs/(pool_interval_secs*ACTIVITY_LEVEL_INIT_TEST),
+            update_all_pools_ticks: interval_ticks(update_all_pools_secs, pool_interval_secs),
+            activity_level_decrease_ticks: interval_ticks(activity_level_clean_secs, pool_interval_secs*ACTIVITY_LEVEL_INIT_TEST),
             activity_level_init: ACTIVITY_LEVEL_INIT_TEST,
-            calc_all_positions_ticks: calc_all_positions_secs/pool_interval_secs,
+            calc_all_positions_ticks: interval_ticks(calc_all_positions_secs, pool_interval_secs),
             //monitor_margin_level_thresold: monitor_margin_level_thresold,
         },
-        Deployment::BASE => DeploymentConfig {
+        Deployment::BASE | Deployment::Custom(_) => DeploymentConfig {
             data_store: *mm_contracts.get("DataStore#DataStore").unwrap(),
             reader: *mm_contracts.get("Reader#Reader").unwrap(),
             event_emitter: *mm_contracts.get("EventEmitter#EventEmitter").unwrap(),
             exchange_router: *mm_contracts.get("ExchangeRouter#ExchangeRouter").unwrap(),
+            pool_utils: mm_contracts.get("PoolUtils#PoolUtils").copied(),
+            role_store: mm_contracts.get("RoleStore#RoleStore").copied(),
             last_block_number: last_block_number,
             total_profit: total_profit,
-            update_all_pools_ticks: update_all_pools_secs/pool_interval_secs,
-            activity_level_decrease_ticks: activity_level_clean_secs/(pool_interval_secs*ACTIVITY_LEVEL_INIT),
+            update_all_pools_ticks: interval_ticks(update_all_pools_secs, pool_interval_secs),
+            activity_level_decrease_ticks: interval_ticks(activity_level_clean_secs, pool_interval_secs*ACTIVITY_LEVEL_INIT),
             activity_level_init: ACTIVITY_LEVEL_INIT,
-            calc_all_positions_ticks: calc_all_positions_secs/pool_interval_secs,
+            calc_all_positions_ticks: interval_ticks(calc_all_positions_secs, pool_interval_secs),
             //monitor_margin_level_thresold: monitor_margin_level_thresold,
         },
     }
@@ -176,34 +248,117 @@ pub struct StateCache {
     pools: HashMap<Bytes32, Pool>,
     positions: HashMap<Bytes32, Position>,
     sents: HashMap<Bytes32, DateTime<Utc>>,
+    #[serde(default)]
+    position_attempts: HashMap<Bytes32, (Address, u64)>,
+    #[serde(default)]
+    skipped_accounts: HashSet<Address>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Position {
-    account: Address,
-    position_id: U256,
-    pool: Bytes32,
-    meme_symbol: String,
-    base_collateral: U256,
-    base_debt_scaled: U256,
-    meme_collateral: U256,
-    meme_debt_scaled: U256,
-    margin_level: U256,
+    pub account: Address,
+    pub position_id: U256,
+    pub pool: Bytes32,
+    pub meme_symbol: String,
+    pub base_collateral: U256,
+    pub base_debt_scaled: U256,
+    pub meme_collateral: U256,
+    pub meme_debt_scaled: U256,
+    pub margin_level: U256,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Pool {
+    pub price: U256,
+    pub price_decimals: U256,
+    pub base_token: Address,
+    pub base_symbol: String,
+    pub base_token_decimals: U256,
+    pub base_borrow_index: U256,
+    pub meme_token: Address,
+    pub meme_symbol: String,
+    pub meme_token_decimals: U256,
+    pub meme_borrow_index: U256,
+    pub activity_level: u64,
+}
+
+/// A tracked position with its health as of the last scan.
+#[derive(Clone, Debug, Serialize)]
+pub struct PositionSnapshot {
+    pub key: Bytes32,
+    pub position: Position,
+    /// Below the margin level threshold at the last scan, None if not yet scanned.
+    pub liquidatable: Option<bool>,
+}
+
+/// A point-in-time copy of the strategy's view, see [MmStrategy::snapshot].
+#[derive(Clone, Debug, Serialize)]
+pub struct StrategySnapshot {
+    pub deployment: String,
+    pub last_block: u64,
+    pub margin_level_threshold: U256,
+    pub pools: HashMap<Bytes32, Pool>,
+    /// Cached price of each pool, also found in `pools`.
+    pub prices: HashMap<Bytes32, U256>,
+    pub positions: Vec<PositionSnapshot>,
+    /// Net profit of confirmed liquidations in [COMMON_DECIMALS] units of the accounting token.
+    pub realized_profit: I256,
+    pub profit_target_reached: bool,
+}
+
+/// A position found below the margin level threshold during a scan.
+#[derive(Clone, Debug)]
+pub struct UnderwaterPosition {
+    pub account: Address,
+    pub position_id: U256,
+    pub pool: Bytes32,
+    pub margin_level: U256,
+    pub collateral_usd: U256,
+    pub debt_usd: U256,
+    pub base_debt: U256,
+    pub meme_debt: U256,
+    pub expected_profit: U256,
+    /// Expected profit in ray precision of the accounting token, if convertible.
+    pub accounting_profit: Option<U256>,
+    /// Expected profit in [COMMON_DECIMALS] units of the accounting token, or
+    /// of the pool's base token if not convertible.
+    pub common_profit: U256,
+    /// Deterministic id of this opportunity, see [opportunity_id].
+    pub opportunity_id: Bytes32,
+}
+
+/// Immutable PoolUtils constants, fetched once at startup. IGNORE_CALC_AVAILABLE
+/// and IGNORE_CALC_LOAN are left out, no check here depends on them.
+#[derive(Clone, Debug)]
+pub struct PoolUtilsConstants {
+    pub minimum_liquidity: U256,
+}
+
+/// The position and pool state a liquidation reverted against, it reverts
+/// the same way until any of it changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct RevertedState {
+    base_collateral: U256,
+    base_debt_scaled: U256,
+    meme_collateral: U256,
+    meme_debt_scaled: U256,
     price: U256,
-    price_decimals: U256,
-    base_token: Address,
-    base_symbol: String,
-    base_token_decimals: U256,
     base_borrow_index: U256,
-    meme_token: Address,
-    meme_symbol: String,
-    meme_token_decimals: U256,
     meme_borrow_index: U256,
-    activity_level: u64,
+}
+
+impl RevertedState {
+    fn of(position: &Position, pool: &Pool) -> Self {
+        Self {
+            base_collateral: position.base_collateral,
+            base_debt_scaled: position.base_debt_scaled,
+            meme_collateral: position.meme_collateral,
+            meme_debt_scaled: position.meme_debt_scaled,
+            price: pool.price,
+            base_borrow_index: pool.base_borrow_index,
+            meme_borrow_index: pool.meme_borrow_index,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -218,11 +373,65 @@ pub struct MmStrategy<T, P, N = alloy_contract::private::Ethereum>
     positions_active: Vec<Position>,
     positions_all: Vec<Position>,
     sents: HashMap<Bytes32, DateTime<Utc>>,
+    // liquidation attempts and account per position key, see record_position_attempt
+    position_attempts: HashMap<Bytes32, (Address, u64)>,
+    skipped_accounts: HashSet<Address>,
     chain_id: u64,
+    strategy_config: Config,
     config: DeploymentConfig,
+    deployment: Deployment,
     liquidator: Address,
     margin_level_threshold: U256,
     tick_counter: u64,
+    last_pool_refresh_block: u64,
+    fast_watch: HashSet<Bytes32>,
+    // last position key scanned by the round robin between full scans
+    scan_cursor: Option<Bytes32>,
+    // activity level of positions with recent events, scanned every tick until it decays to 0
+    position_activity: HashMap<Bytes32, u64>,
+    pool_utils_constants: Option<PoolUtilsConstants>,
+    priority_accounts: HashSet<Address>,
+    priority_accounts_modified: Option<SystemTime>,
+    last_refuel: Option<DateTime<Utc>>,
+    profit_model: Arc<dyn ProfitModel>,
+    implementations: HashMap<Address, Address>,
+    last_implementation_check: Option<DateTime<Utc>>,
+    paused_on_upgrade: bool,
+    head_block_number: Option<u64>,
+    pending_watch: HashSet<Address>,
+    price_watch: HashSet<Bytes32>,
+    // positions whose liquidation reverted deterministically, until their expiry
+    blacklist: HashMap<Bytes32, DateTime<Utc>>,
+    // positions whose liquidation reverted in simulation with a protocol error, not
+    // simulated again while their state stays the one it reverted against
+    known_reverts: HashMap<Bytes32, (String, RevertedState)>,
+    // the last liquidation sent for each position, to simulate it alone when its batch fails
+    submitted: HashMap<Bytes32, UnderwaterPosition>,
+    // net token gains of confirmed liquidations, in COMMON_DECIMALS units of the accounting token
+    realized_profit: I256,
+    profit_target_reached: bool,
+    // token profits are valued in, --accounting-token or the pools' main quote token
+    profit_token: Option<Address>,
+    // Multicall3 batching reads, when deployed at --multicall-address
+    multicall: Option<Address>,
+    // previous state of the positions changed by recent logs, undone on a reorg
+    position_journal: Vec<(u64, Bytes32, Option<Position>)>,
+    // realized profit of recent confirmed liquidations by block, taken back on a reorg
+    recent_profits: Vec<(u64, I256)>,
+    // ERC20 decimals of the pools' tokens and the accounting token
+    decimals: DecimalsCache,
+    // where pools found after startup are announced, fed back as Event::NewPool
+    new_pools: Option<broadcast::Sender<NewPool>>,
+    // where positions nearing the threshold are announced, fed back as Event::MarginCall
+    margin_calls: Option<broadcast::Sender<MarginCall>>,
+    // positions with a margin call raised, until they recover above the buffer
+    margin_called: HashSet<Bytes32>,
+    // times of the failed submissions since the last confirmed one
+    failure_streak: Vec<DateTime<Utc>>,
+    // when the circuit breaker tripped, no liquidations are sent while set
+    breaker_tripped_at: Option<DateTime<Utc>>,
+    // the breaker's clock, swapped in tests to move time
+    clock: fn() -> DateTime<Utc>,
      _network_transport: ::core::marker::PhantomData<(N, T)>,
 }
 
@@ -231,6 +440,100 @@ impl<
     P: alloy_contract::private::Provider<T, N> + 'static,
     N: alloy_contract::private::Network,
 > MmStrategy<T, P, N> {
+    pub fn reader(&self) -> Address {
+        self.config.reader
+    }
+
+    pub fn data_store(&self) -> Address {
+        self.config.data_store
+    }
+
+    /// Copy of the known pools, tracked positions with their last computed
+    /// health, cached prices and realized profit.
+    pub fn snapshot(&self) -> StrategySnapshot {
+        let mut positions: Vec<PositionSnapshot> = self
+            .positions
+            .iter()
+            .map(|(key, position)| PositionSnapshot {
+                key: *key,
+                position: position.clone(),
+                // a scanned position has a margin level, U256::MAX for no debt
+                liquidatable: (position.margin_level > U256::ZERO).then(|| position.margin_level < self.margin_level_threshold),
+            })
+            .collect();
+        positions.sort_by(|a, b| a.key.cmp(&b.key));
+        StrategySnapshot {
+            deployment: self.deployment.name().to_string(),
+            last_block: self.last_block_number,
+            margin_level_threshold: self.margin_level_threshold,
+            pools: self.pools.clone(),
+            prices: self.pools.iter().map(|(key, pool)| (*key, pool.price)).collect(),
+            positions,
+            realized_profit: self.realized_profit,
+            profit_target_reached: self.profit_target_reached,
+        }
+    }
+
+    /// Bring pools and positions up to `block_number` and return the positions
+    /// that would be liquidated there, without sending anything. For a client
+    /// whose head is pinned to that block, see [PinnedBlock](crate::backtest::PinnedBlock).
+    pub async fn scan_at(&mut self, block_number: u64) -> Result<Vec<UnderwaterPosition>> {
+        self.head_block_number = Some(block_number);
+        self.update_pools().await?;
+        self.update_state().await?;
+        let underwaters = self.get_underwater_positions(None).await.unwrap_or_default();
+        self.pending_watch.clear();
+        self.price_watch.clear();
+        let underwaters = self.check_repay_sanity(underwaters).await;
+        let underwaters = self.check_pool_liquidity(underwaters).await;
+        self.tick_counter = self.tick_counter + 1;
+        Ok(underwaters)
+    }
+
+    /// Filter matching this deployment's position events.
+    pub fn position_log_filter(&self) -> Filter {
+        Filter::new()
+            .address(self.config.event_emitter)
+            .event_signature(EventEmitter::Position::SIGNATURE_HASH)
+    }
+
+    /// Block to resume position logs from, read from the state cache.
+    pub fn resume_block(&mut self) -> Result<u64> {
+        self.load_cache()?;
+        Ok(self.last_block_number)
+    }
+
+    /// Fail when the protocol gates liquidations by role and the liquidator
+    /// doesn't hold it. Permissionless deployments, with no role members, pass.
+    /// With a flashloan helper it's the helper that calls the ExchangeRouter
+    /// and must hold the role.
+    pub async fn check_liquidator_authorized(&self) -> Result<()> {
+        let Some(role_store) = self.config.role_store else {
+            info!("no RoleStore in deployment, skipping liquidator authorization check");
+            return Ok(());
+        };
+        let role_store = RoleStore::new(role_store, self.client.clone());
+        let role_key: Bytes32 = FixedBytes::from_slice(&keccak256_hash(&"LIQUIDATION_KEEPER".to_string().abi_encode()));
+
+        let members = role_store.getRoleMemberCount(role_key).call().await?._0;
+        if members.is_zero() {
+            info!("LIQUIDATION_KEEPER has no members, liquidations are permissionless");
+            return Ok(());
+        }
+        let keeper = match &self.strategy_config.flashloan {
+            Some(flashloan) => flashloan.router,
+            None => self.liquidator,
+        };
+        if !role_store.hasRole(keeper, role_key).call().await?._0 {
+            anyhow::bail!(
+                "liquidator {:?} is not an authorized LIQUIDATION_KEEPER, every liquidation would revert",
+                keeper
+            );
+        }
+        info!("liquidator {:?} is an authorized LIQUIDATION_KEEPER", keeper);
+        Ok(())
+    }
+
     pub fn new(
         client: Arc<P>,
         config: Config,
@@ -242,9 +545,24 @@ impl<
         update_all_pools_secs: u64,
         activity_level_clean_secs: u64,
         calc_all_positions_secs: u64,
-    ) -> Self {
+        profit_model: Arc<dyn ProfitModel>,
+        new_pools: Option<broadcast::Sender<NewPool>>,
+        margin_calls: Option<broadcast::Sender<MarginCall>>,
+    ) -> Result<Self> {
+        let mm_contracts = match config.deployment_addresses.clone() {
+            Some(mm_contracts) => mm_contracts,
+            None => {
+                let file = File::open(&config.deployed_addresses_file)
+                    .with_context(|| format!("Error opening {}", config.deployed_addresses_file))?;
+                serde_json::from_reader(file)
+                    .with_context(|| format!("Error parsing {}", config.deployed_addresses_file))?
+            }
+        };
+        // get_deployment_config reads the required contracts unchecked
+        deployments::validate(deployment.name(), &mm_contracts)?;
         let deployment_config = get_deployment_config(
-            deployment, 
+            deployment.clone(), 
+            &mm_contracts,
             last_block_number, 
             total_profit,
             pool_interval_secs,
@@ -252,7 +570,7 @@ impl<
             activity_level_clean_secs,
             calc_all_positions_secs,         
         );
-        Self {
+        Ok(Self {
             client,
             last_block_number: last_block_number,
             positions: HashMap::new(),
@@ -261,13 +579,48 @@ impl<
             positions_all: Vec::new(),
             pools: HashMap::new(),
             sents: HashMap::new(),
+            position_attempts: HashMap::new(),
+            skipped_accounts: HashSet::new(),
             chain_id: config.chain_id,
+            strategy_config: config,
             config: deployment_config,
+            deployment,
             liquidator: liquidator_address,
             margin_level_threshold: U256::ZERO,
             tick_counter: 0,
+            last_pool_refresh_block: 0,
+            fast_watch: HashSet::new(),
+            scan_cursor: None,
+            position_activity: HashMap::new(),
+            pool_utils_constants: None,
+            priority_accounts: HashSet::new(),
+            priority_accounts_modified: None,
+            last_refuel: None,
+            profit_model,
+            implementations: HashMap::new(),
+            last_implementation_check: None,
+            paused_on_upgrade: false,
+            head_block_number: None,
+            pending_watch: HashSet::new(),
+            price_watch: HashSet::new(),
+            blacklist: HashMap::new(),
+            known_reverts: HashMap::new(),
+            submitted: HashMap::new(),
+            realized_profit: I256::ZERO,
+            profit_target_reached: false,
+            profit_token: None,
+            multicall: None,
+            position_journal: Vec::new(),
+            recent_profits: Vec::new(),
+            decimals: DecimalsCache::new(),
+            new_pools,
+            margin_calls,
+            margin_called: HashSet::new(),
+            failure_streak: Vec::new(),
+            breaker_tripped_at: None,
+            clock: Utc::now,
             _network_transport: ::core::marker::PhantomData,
-        }
+        })
     }
 }
 
@@ -284,9 +637,16 @@ impl<
         info!("self.config.event_emitter {:?}", self.config.event_emitter);
                 
         self.load_cache()?;
+        self.load_pool_utils_constants().await?;
+        if let Some(multicall_address) = self.strategy_config.multicall_address {
+            self.multicall = multicall::resolve(self.client.as_ref(), multicall_address).await?;
+        }
+        self.check_implementations().await?;
         self.update_pools().await?;
         self.update_state().await?;
+        self.log_target_borrowers();
 
+        self.report_status();
         info!("done syncing state");
         Ok(())
     }
@@ -301,7 +661,7 @@ impl<
     // }
 
     async fn process_event(&mut self, event: Event) -> Vec<Action<N>> {
-        match event {
+        let actions = match event {
             Event::NewTick(block) => {
                 let actions = self.process_new_tick_event(block).await;
                 match actions {
@@ -317,7 +677,149 @@ impl<
                     }
                 }
             }
-        }
+            Event::PendingTx(tx) => {
+                // a pending call into the protocol may push the sender's
+                // positions under water, scan them on the next tick
+                if tx.to() == Some(self.config.exchange_router) && self.pending_watch.insert(tx.from) {
+                    info!("pending tx {:?} from {:?}, watching its positions", tx.inner.tx_hash(), tx.from);
+                }
+                Vec::new()
+            }
+            Event::PriceUpdate(update) => {
+                info!("received price update: {:?}", update);
+                let mut repriced = HashSet::new();
+                for (pool_key, pool) in self.pools.iter_mut() {
+                    if pool.meme_token == update.token {
+                        pool.price = update.price;
+                        repriced.insert(*pool_key);
+                    }
+                }
+                if repriced.is_empty() {
+                    return Vec::new();
+                }
+                self.scan_pools(repriced).await
+            }
+            Event::Log(log) => {
+                // every deployment's strategy sees every log collector's logs
+                if log.address() != self.config.event_emitter {
+                    return Vec::new();
+                }
+                let block_number = log.block_number.unwrap_or(self.last_block_number);
+                if block_number < self.strategy_config.skip_positions_inactive_since {
+                    return Vec::new();
+                }
+                match log.log_decode::<EventEmitter::Position>() {
+                    Ok(position) => {
+                        self.apply_position_log(position.inner.data, block_number);
+                        self.last_block_number = std::cmp::max(self.last_block_number, block_number);
+                    }
+                    Err(e) => warn!("failed to decode position log at block {}: {}", block_number, e),
+                }
+                Vec::new()
+            }
+            Event::LiquidationOutcome(LiquidationOutcome::Failed(failed)) => {
+                if failed.tag.deployment.name() != self.deployment.name() {
+                    return Vec::new();
+                }
+                self.record_submission_failure(&failed.failure.to_string());
+                let culprits = if failed.failure.is_transient() {
+                    Vec::new()
+                } else {
+                    self.attribute_failure(&failed.tag.positions).await
+                };
+                for position_key in failed.tag.positions {
+                    if failed.failure.is_transient() {
+                        // lift the resend cooldown so the next tick retries it
+                        self.sents.remove(&position_key);
+                        info!("retrying position {:?} after {}", position_key, failed.failure);
+                        continue;
+                    }
+                    if !culprits.contains(&position_key) {
+                        self.sents.remove(&position_key);
+                        info!("retrying position {:?}, its batch failed with {} on another position", position_key, failed.failure);
+                        continue;
+                    }
+                    let state = self.positions.get(&position_key).and_then(|position| {
+                        self.pools.get(&position.pool).map(|pool| RevertedState::of(position, pool))
+                    });
+                    if let Some(state) = state {
+                        info!("not simulating position {:?} again until it changes, it reverted with {}", position_key, failed.failure);
+                        self.known_reverts.insert(position_key, (failed.failure.to_string(), state));
+                    }
+                    if self.strategy_config.blacklist_ttl_secs > 0 {
+                        let ttl = Duration::seconds(self.strategy_config.blacklist_ttl_secs as i64);
+                        self.blacklist.insert(position_key, Utc::now() + ttl);
+                        warn!("blacklisting position {:?} for {}s after {}", position_key, ttl.num_seconds(), failed.failure);
+                    }
+                }
+                Vec::new()
+            }
+            Event::LiquidationOutcome(LiquidationOutcome::Confirmed(confirmed)) => {
+                if confirmed.tag.deployment.name() == self.deployment.name() {
+                    self.failure_streak.clear();
+                    self.record_realized_profit(&confirmed);
+                }
+                Vec::new()
+            }
+            Event::LiquidationOutcome(LiquidationOutcome::Reverted(reverted)) => {
+                if reverted.tag.deployment.name() == self.deployment.name() {
+                    self.record_submission_failure(&format!("revert of {:?}", reverted.tx_hash));
+                }
+                Vec::new()
+            }
+            Event::LiquidationOutcome(LiquidationOutcome::Dropped(dropped)) => {
+                if dropped.tag.deployment.name() != self.deployment.name() {
+                    return Vec::new();
+                }
+                for position_key in dropped.tag.positions {
+                    // lift the resend cooldown so the next tick resubmits it
+                    self.sents.remove(&position_key);
+                    info!("resubmitting position {:?} after {:?} was dropped", position_key, dropped.tx_hash);
+                }
+                Vec::new()
+            }
+            Event::Reorg(reorg) => {
+                self.handle_reorg(reorg.from_block);
+                Vec::new()
+            }
+            Event::NewPool(new_pool) => {
+                if new_pool.deployment != self.deployment.name() {
+                    return Vec::new();
+                }
+                info!(
+                    "new pool {}/{} ({:?}/{:?}), checking its positions now",
+                    new_pool.meme_symbol, new_pool.base_symbol, new_pool.meme_token, new_pool.base_token
+                );
+                // scan the new pool's positions now instead of on the next full scan
+                self.scan_pools(HashSet::from([new_pool.pool])).await
+            }
+            Event::Control(ControlCommand::ClearSkippedAccounts(account)) => {
+                self.clear_skipped_accounts(account);
+                Vec::new()
+            }
+            Event::MarginCall(margin_call) => {
+                if margin_call.deployment != self.deployment.name() {
+                    return Vec::new();
+                }
+                let message = format!(
+                    "margin call on {}: {:?} position_id:{} at margin level {}, liquidatable below {}",
+                    margin_call.deployment, margin_call.account, margin_call.position_id,
+                    format_margin_level(margin_call.health), format_margin_level(margin_call.threshold)
+                );
+                warn!("{}", message);
+                alerts::send(message);
+                Vec::new()
+            }
+            Event::NewBlock(block) => {
+                info!("received new block: {:?}", block);
+                self.head_block_number = Some(block.number);
+                self.process_new_tick_event(NewTick { timestamp: block.timestamp })
+                    .await
+                    .unwrap_or_default()
+            }
+        };
+        self.report_status();
+        actions
     }
 }
 
@@ -391,44 +893,234 @@ impl<
             error!("Update State error: {}", e);
         }
 
+        if let Err(e) = self.reload_priority_accounts() {
+            error!("Reload priority accounts error: {}", e);
+        }
+
+        if let Err(e) = self.check_implementations().await {
+            error!("Check implementations error: {}", e);
+        }
+
         info!("Total position count: {}", self.positions.len());
-        let underwaters = self.get_underwater_positions().await?;
-        let underwaters_chunks = underwaters.chunks(LIQUIDATIONL_BATCH_SIZE as usize);
+        let underwaters = self.get_underwater_positions(None).await?;
+        self.pending_watch.clear();
+        self.price_watch.clear();
 
         self.tick_counter = self.tick_counter + 1;
 
+        Some(self.liquidation_actions(underwaters).await)
+    }
+
+    // scan the positions of `pools` right away, between ticks, without the
+    // tick's pool and state refreshes and without advancing its periodic tasks
+    async fn scan_pools(&mut self, pools: HashSet<Bytes32>) -> Vec<Action<N>> {
+        info!("scanning the positions of {} pools between ticks", pools.len());
+        let underwaters = self.get_underwater_positions(Some(&pools)).await.unwrap_or_default();
+        self.liquidation_actions(underwaters).await
+    }
+
+    // check a scan's underwater positions and turn them into liquidation batches
+    async fn liquidation_actions(&mut self, underwaters: Vec<UnderwaterPosition>) -> Vec<Action<N>> {
+        // a halted bot sends nothing, so it reads nothing for the liquidations either
+        if self.paused_on_upgrade {
+            warn!(
+                "paused after a proxy upgrade, holding {} liquidations until restart",
+                underwaters.len()
+            );
+            return Vec::new();
+        }
+
+        if self.profit_target_reached {
+            info!("profit target reached, holding {} liquidations", underwaters.len());
+            return Vec::new();
+        }
+
+        if self.breaker_open() {
+            warn!("circuit breaker open, holding {} liquidations", underwaters.len());
+            return Vec::new();
+        }
+
+        let underwaters = self.check_repay_sanity(underwaters).await;
+        let underwaters = self.check_pool_liquidity(underwaters).await;
+        let underwaters_chunks = underwaters.chunks(LIQUIDATIONL_BATCH_SIZE as usize);
+
+        if self.strategy_config.observe_only {
+            for underwater in underwaters.iter() {
+                info!(
+                    "observe only, would liquidate opportunity {} {:?} position_id:{} margin_level:{}",
+                    underwater.opportunity_id, underwater.account, underwater.position_id, underwater.margin_level
+                );
+            }
+            return Vec::new();
+        }
+
         let mut actions: Vec<Action<N>> = Vec::new();
+        match self.check_native_balance().await {
+            Ok(Some(refuel)) => actions.push(Action::Refuel(refuel)),
+            Ok(None) => {}
+            Err(e) => error!("Check native balance error: {}", e),
+        }
+
         for chunk in underwaters_chunks {
             //info!("underwater: {:?} position_id:{} ", account, position_id);
-            let mut positions_batch_to_liquidation: Vec<LiquidationParams> = Vec::new();
-            for (account, position_id, _margin_level, _collateral, _debt) in chunk {
+            if chunk.is_empty() {
+                continue;
+            }
+            let profits: Vec<U256> = chunk.iter().map(|underwater| underwater.common_profit).collect();
+            let batch_profit: u128 = self.profit_model.batch_profit(&profits).try_into().unwrap_or(u128::MAX);
+
+            let tx = match self.build_batch_liquidation_tx(chunk) {
+                Ok(tx) => tx,
+                Err(e) => {
+                    error!("Error building liquidation: {}", e);
+                    continue;
+                }
+            };
+            let valuation = self.valuation(chunk);
+            if !self.clears_min_profit(&tx, chunk, batch_profit, valuation.as_ref()).await {
+                continue;
+            }
+
+            let mut opportunity_ids: Vec<Bytes32> = Vec::new();
+            let mut position_keys: Vec<Bytes32> = Vec::new();
+            for underwater in chunk {
                 let now: DateTime<Utc> = Utc::now();
-                self.sents.insert(hash_position_key(account.clone(), *position_id), now);
-                positions_batch_to_liquidation.push(LiquidationParams{account:account.clone(), positionId: *position_id});
+                self.sents.insert(hash_position_key(underwater.account, underwater.position_id), now);
+                self.record_position_attempt(underwater);
+                opportunity_ids.push(underwater.opportunity_id);
+                position_keys.push(hash_position_key(underwater.account, underwater.position_id));
+                self.submitted.insert(hash_position_key(underwater.account, underwater.position_id), underwater.clone());
+                info!(
+                    deployment = self.deployment.name(),
+                    position = ?hash_position_key(underwater.account, underwater.position_id),
+                    profit = %underwater.common_profit,
+                    "opportunity {} liquidating {:?} position_id:{}",
+                    underwater.opportunity_id, underwater.account, underwater.position_id
+                );
             }
+            metrics::record_attempted(chunk.len() as u64);
+
+            actions.push(Action::SubmitTx(SubmitLiquidation {
+                deployment: self.deployment.clone(),
+                opportunity_ids,
+                positions: position_keys,
+                valuation,
+                submit: SubmitTxToMempool {
+                    tx,
+                    gas_bid_info: Some(GasBidInfo{
+                        total_profit: batch_profit,
+                        bid_percentage: self.strategy_config.bid_percentage,
+                    }),
+                },
+            }));
+        }
 
-            let action = async {
-                self.build_liquidation_tx(&positions_batch_to_liquidation)
-                    .await
-                    .map_err(|e| {
-                        error!("Error building liquidation: {}", e);
-                        e
-                    })
-                    .ok()
-                    .map(|tx| Action::SubmitTx(SubmitTxToMempool {
-                        tx,
-                        gas_bid_info: Some(GasBidInfo{total_profit:self.config.total_profit, bid_percentage:0}),
-                    }))
+        actions
+    }
+
+    // the positions of a batch that failed deterministically whose liquidation
+    // also reverts on its own, so one bad position doesn't take the others of its
+    // batch down with it; a lone position is its own culprit
+    async fn attribute_failure(&self, positions: &[Bytes32]) -> Vec<Bytes32> {
+        if positions.len() == 1 {
+            return positions.to_vec();
+        }
+        let mut culprits = Vec::new();
+        for position_key in positions {
+            let Some(underwater) = self.submitted.get(position_key) else {
+                continue;
+            };
+            let tx = match self.build_liquidation_tx(underwater) {
+                Ok(tx) => tx,
+                Err(e) => {
+                    warn!("Can't rebuild the liquidation of {:?} to attribute its batch's failure: {}", position_key, e);
+                    continue;
+                }
+            };
+            let Err(e) = self.client.call(&tx).block(BlockId::pending()).await else {
+                continue;
+            };
+            let failure = e
+                .as_error_resp()
+                .and_then(|resp| resp.as_revert_data())
+                .map_or(LiquidationFailure::Unknown, |data| LiquidationFailure::classify(&data));
+            if !failure.is_transient() {
+                info!("position {:?} reverts on its own with {}", position_key, failure);
+                culprits.push(*position_key);
             }
-            .await;
+        }
+        culprits
+    }
 
-            if let Some(action) = action {
-                actions.push(action);
+    // prices of the batch's tokens and of gas in the profit token, so the executor
+    // values gas and realized token deltas in the unit of the expected profit
+    fn valuation(&self, batch: &[UnderwaterPosition]) -> Option<Valuation> {
+        let profit_token = self.profit_token?;
+        let (profit_decimals, _) = self.price_in_profit_token(profit_token, profit_token)?;
+        let mut token_prices = BTreeMap::new();
+        token_prices.insert(profit_token, (profit_decimals, U256::from(10).pow(U256::from(COMMON_DECIMALS))));
+        for underwater in batch {
+            let Some(pool) = self.pools.get(&underwater.pool) else {
+                continue;
+            };
+            for token in [pool.base_token, pool.meme_token] {
+                if let Some(price) = self.price_in_profit_token(token, profit_token) {
+                    token_prices.insert(token, price);
+                }
             }
         }
+        let native_price = self
+            .strategy_config
+            .native_token
+            .and_then(|native_token| value_in_token(&self.pools, native_token, ray(), profit_token))
+            .map(|value| from_ray(value, U256::from(COMMON_DECIMALS)));
+        Some(Valuation { profit_decimals, native_price, token_prices })
+    }
+
+    // decimals of `token` and the value of one whole token in COMMON_DECIMALS units of the profit token
+    fn price_in_profit_token(&self, token: Address, profit_token: Address) -> Option<(u8, U256)> {
+        let decimals: u8 = self.token_decimals(token)?.try_into().ok()?;
+        let value = value_in_token(&self.pools, token, ray(), profit_token)?;
+        Some((decimals, from_ray(value, U256::from(COMMON_DECIMALS))))
+    }
 
-        Some(actions)
-        //None
+    // whether a batch's expected profit net of its estimated gas cost, priced in
+    // the profit token, reaches --min-profit; a batch whose gas can't be estimated
+    // or priced is left to the executor
+    async fn clears_min_profit(
+        &self,
+        tx: &N::TransactionRequest,
+        batch: &[UnderwaterPosition],
+        batch_profit: u128,
+        valuation: Option<&Valuation>,
+    ) -> bool {
+        let min_profit = self.strategy_config.min_profit;
+        if min_profit == 0 {
+            return true;
+        }
+        let gas_wei = match (self.client.estimate_gas(tx).await, self.client.get_gas_price().await) {
+            (Ok(gas), Ok(gas_price)) => gas as u128 * gas_price,
+            (Err(e), _) | (_, Err(e)) => {
+                warn!("Can't estimate gas for the min profit check, leaving it to the executor: {}", e);
+                return true;
+            }
+        };
+        let Some(gas_cost) = valuation.and_then(|valuation| valuation.gas_cost(gas_wei)) else {
+            warn!("Can't price gas in the profit token for the min profit check, leaving it to the executor");
+            return true;
+        };
+        let net_profit = batch_profit.saturating_sub(gas_cost);
+        if net_profit >= min_profit {
+            return true;
+        }
+        for underwater in batch {
+            metrics::record_skip(SkipReason::Unprofitable);
+            debug!(
+                "Skipping dust {:?} position_id:{}, batch profit {} net of gas {} is {}, below min profit {}",
+                underwater.account, underwater.position_id, batch_profit, gas_cost, net_profit, min_profit
+            );
+        }
+        false
     }
 
     // async fn build_liquidation_tx(&self, account: &Address, position_id: U256) -> Result<<N as Network>::TransactionRequest> {
@@ -445,18 +1137,276 @@ impl<
     //     Ok(tx)
     // }
 
-    async fn build_liquidation_tx(&self, positions_batch: &Vec<LiquidationParams>) -> Result<<N as Network>::TransactionRequest> {
-        let exchange_router = ExchangeRouter::new(self.config.exchange_router, self.client.clone());
-        let call_build = exchange_router.executeLiquidationBatch(positions_batch.clone());
-        
-        let mut tx = call_build.into_transaction_request();
+    /// The exact tx the strategy would submit to liquidate `position` on its
+    /// own, without sending it or touching any state.
+    pub fn build_liquidation_tx(&self, position: &UnderwaterPosition) -> Result<<N as Network>::TransactionRequest> {
+        self.build_batch_liquidation_tx(std::slice::from_ref(position))
+    }
+
+    // the tx liquidating `batch` in one call, directly on the exchange router
+    // or through the flashloan helper
+    fn build_batch_liquidation_tx(&self, batch: &[UnderwaterPosition]) -> Result<<N as Network>::TransactionRequest> {
+        let positions_batch: Vec<LiquidationParams> = batch
+            .iter()
+            .map(|underwater| LiquidationParams{account:underwater.account, positionId: underwater.position_id})
+            .collect();
+        let mut tx = match &self.strategy_config.flashloan {
+            Some(flashloan) => self.build_flashloan_call(flashloan, &positions_batch, batch)?,
+            None => {
+                let exchange_router = ExchangeRouter::new(self.config.exchange_router, self.client.clone());
+                exchange_router.executeLiquidationBatch(positions_batch).into_transaction_request()
+            }
+        };
         tx.set_chain_id(self.chain_id);
         tx.set_from(self.liquidator);
 
         Ok(tx)
     }
 
-     async fn get_underwater_positions(&mut self) -> Option<Vec<(Address, U256, U256, U256, U256)>> {
+    // a flashLiquidate call on the helper contract, borrowing the batch's
+    // repay amounts so the liquidator needs no balance of the debt tokens
+    fn build_flashloan_call(
+        &self,
+        flashloan: &FlashloanConfig,
+        positions_batch: &[LiquidationParams],
+        batch: &[UnderwaterPosition],
+    ) -> Result<<N as Network>::TransactionRequest> {
+        let mut debts = Vec::new();
+        let mut profits = Vec::new();
+        let mut tokens = Vec::new();
+        for underwater in batch {
+            let pool = self.pools.get(&underwater.pool)
+                .ok_or_else(|| anyhow!("unknown pool {:?} of {:?}", underwater.pool, underwater.account))?;
+            for token in [pool.base_token, pool.meme_token] {
+                if !tokens.contains(&token) {
+                    tokens.push(token);
+                }
+            }
+            debts.push((pool.base_token, underwater.base_debt));
+            debts.push((pool.meme_token, underwater.meme_debt));
+            // already net of the flashloan fee and the worst-case slippage
+            profits.push((pool.base_token, underwater.expected_profit));
+        }
+        let params = positions_batch
+            .iter()
+            .map(|params| IFlashLiquidator::LiquidationParams { account: params.account, positionId: params.positionId })
+            .collect();
+        let helper = IFlashLiquidator::new(flashloan.router, self.client.clone());
+        Ok(helper
+            .flashLiquidate(
+                flashloan.provider,
+                self.config.exchange_router,
+                params,
+                flashloan.loans(debts),
+                flashloan.swap_router,
+                tokens,
+                flashloan.min_profits(profits),
+            )
+            .into_transaction_request())
+    }
+
+    // drop positions whose computed debt to repay disagrees with a fresh on-chain read
+    async fn check_repay_sanity(&self, underwaters: Vec<UnderwaterPosition>) -> Vec<UnderwaterPosition> {
+        let reader = Reader::new(self.config.reader, self.client.clone());
+        let data_store = self.config.data_store;
+        let tolerance_bps = self.strategy_config.repay_sanity_tolerance_bps;
+
+        let chunks: Vec<&[UnderwaterPosition]> = underwaters.chunks(MULTICALL_CHUNK_SIZE).collect();
+        let reads: Vec<(usize, Result<Reader::getPositions2Return>)> = if let Some(multicall) = self.multicall {
+            // one eth_call per batch of chunks
+            let calls: Vec<Reader::getPositions2Call> = chunks
+                .iter()
+                .map(|chunk| Reader::getPositions2Call {
+                    dataStore: data_store,
+                    positionKeys: chunk
+                        .iter()
+                        .map(|underwater| hash_position_key(underwater.account, underwater.position_id))
+                        .collect(),
+                })
+                .collect();
+            multicall::aggregate(self.client.as_ref(), multicall, self.config.reader, &calls, self.strategy_config.multicall_batch_size)
+                .await
+                .into_iter()
+                .enumerate()
+                .collect()
+        } else {
+            // read the chunks concurrently, then put them back in scan order
+            let mut reads: Vec<_> = stream::iter(chunks.iter().enumerate())
+                .map(|(index, chunk)| {
+                    let reader = &reader;
+                    let position_keys: Vec<Bytes32> = chunk
+                        .iter()
+                        .map(|underwater| hash_position_key(underwater.account, underwater.position_id))
+                        .collect();
+                    async move {
+                        let fresh_positions = retry_rpc(
+                            || async { reader.getPositions2(data_store, position_keys.clone()).call().await },
+                            self.strategy_config.rpc_max_retries,
+                            RPC_RETRY_BASE_DELAY,
+                        ).await;
+                        (index, fresh_positions)
+                    }
+                })
+                .buffer_unordered(self.strategy_config.scan_concurrency.max(1))
+                .collect()
+                .await;
+            reads.sort_by_key(|(index, _)| *index);
+            reads
+        };
+
+        let mut checked = Vec::new();
+        for (index, fresh_positions) in reads {
+            let fresh_positions = match fresh_positions {
+                Ok(ret) => ret._0,
+                Err(e) => {
+                    error!("Error fetching positions for repay sanity check: {:?}", e);
+                    continue;
+                }
+            };
+
+            for (underwater, fresh) in zip(chunks[index], fresh_positions.iter()) {
+                match check_repay(underwater, (fresh.assets[0].debt, fresh.assets[1].debt), tolerance_bps) {
+                    Ok(()) => checked.push(underwater.clone()),
+                    Err(SkipReason::ZeroRepay) => {
+                        metrics::record_skip(SkipReason::ZeroRepay);
+                        info!("Skipping {:?} position_id:{} with zero repay", underwater.account, underwater.position_id);
+                    }
+                    Err(reason) => {
+                        metrics::record_skip(reason);
+                        error!(
+                            "Aborting liquidation of {:?} position_id:{}, computed debt ({}, {}) disagrees with on-chain debt ({}, {})",
+                            underwater.account, underwater.position_id,
+                            underwater.base_debt, underwater.meme_debt,
+                            fresh.assets[0].debt, fresh.assets[1].debt
+                        );
+                    }
+                }
+            }
+        }
+        checked
+    }
+
+    // fetch the immutable PoolUtils constants once, they are the same for every pool
+    async fn load_pool_utils_constants(&mut self) -> Result<()> {
+        if self.pool_utils_constants.is_some() {
+            return Ok(());
+        }
+        let Some(pool_utils) = self.config.pool_utils else {
+            match self.strategy_config.minimum_liquidity {
+                Some(minimum_liquidity) => {
+                    info!("no PoolUtils address for {}, using --minimum-liquidity {}", self.deployment.name(), minimum_liquidity);
+                    self.pool_utils_constants = Some(PoolUtilsConstants { minimum_liquidity });
+                }
+                None => warn!(
+                    "no PoolUtils address for {} and no --minimum-liquidity, liquidations aren't checked against MINIMUM_LIQUIDITY",
+                    self.deployment.name()
+                ),
+            }
+            return Ok(());
+        };
+        let pool_utils = PoolUtils::new(pool_utils, self.client.clone());
+        let constants = PoolUtilsConstants {
+            minimum_liquidity: pool_utils.MINIMUM_LIQUIDITY().call().await?._0,
+        };
+        info!("PoolUtils constants: MINIMUM_LIQUIDITY {}", constants.minimum_liquidity);
+        self.pool_utils_constants = Some(constants);
+        Ok(())
+    }
+
+    // drop liquidations whose swap legs would draw a pool below MINIMUM_LIQUIDITY
+    async fn check_pool_liquidity(&mut self, underwaters: Vec<UnderwaterPosition>) -> Vec<UnderwaterPosition> {
+        let Some(minimum) = self.pool_utils_constants.as_ref().map(|constants| constants.minimum_liquidity) else {
+            return underwaters;
+        };
+
+        let pool_keys: Vec<Bytes32> = underwaters
+            .iter()
+            .map(|underwater| underwater.pool)
+            .collect::<HashSet<Bytes32>>()
+            .into_iter()
+            .collect();
+        if pool_keys.is_empty() {
+            return underwaters;
+        }
+
+        let reader = Reader::new(self.config.reader, self.client.clone());
+        let chunks: Vec<&[Bytes32]> = pool_keys.chunks(POLL_POOL_CHUNK_SIZE as usize).collect();
+        let reads: Vec<Result<Reader::getPools2Return>> = if let Some(multicall) = self.multicall {
+            let calls: Vec<Reader::getPools2Call> = chunks
+                .iter()
+                .map(|chunk| Reader::getPools2Call { dataStore: self.config.data_store, poolKeys: chunk.to_vec() })
+                .collect();
+            multicall::aggregate(self.client.as_ref(), multicall, self.config.reader, &calls, self.strategy_config.multicall_batch_size).await
+        } else {
+            let mut reads = Vec::new();
+            for chunk in chunks.iter() {
+                reads.push(reader.getPools2(self.config.data_store, chunk.to_vec()).call().await.map_err(anyhow::Error::from));
+            }
+            reads
+        };
+        let mut pool_balances: HashMap<Bytes32, (U256, U256)> = HashMap::new();
+        for (chunk, read) in zip(chunks, reads) {
+            match read {
+                Ok(ret) => {
+                    for (pool_key, pool) in zip(chunk, ret._0.iter()) {
+                        pool_balances.insert(*pool_key, (pool.assets[0].poolBalance, pool.assets[1].poolBalance));
+                    }
+                }
+                Err(e) => {
+                    error!("Error fetching pools by getPools2: {:?}", e);
+                    return underwaters;
+                }
+            }
+        }
+
+        underwaters
+            .into_iter()
+            .filter(|underwater| {
+                let Some((base_balance, meme_balance)) = pool_balances.get(&underwater.pool) else {
+                    return true;
+                };
+                let fits = fits_pool_liquidity(underwater, (*base_balance, *meme_balance), minimum);
+                if !fits {
+                    metrics::record_skip(SkipReason::MinimumLiquidity);
+                    info!(
+                        "Skipping {:?} position_id:{}, debt ({}, {}) would breach MINIMUM_LIQUIDITY {} of pool balances ({}, {})",
+                        underwater.account, underwater.position_id, underwater.base_debt, underwater.meme_debt,
+                        minimum, base_balance, meme_balance
+                    );
+                }
+                fits
+            })
+            .collect()
+    }
+
+    // how many positions the --target-borrower allowlist matches, warning about borrowers without any
+    fn log_target_borrowers(&self) {
+        let target_borrowers = &self.strategy_config.target_borrowers;
+        if target_borrowers.is_empty() {
+            return;
+        }
+        let matching = self.positions.values().filter(|pos| target_borrowers.contains(&pos.account)).count();
+        info!("{} of {} positions belong to the {} target borrowers", matching, self.positions.len(), target_borrowers.len());
+        for borrower in target_borrowers {
+            if !self.positions.values().any(|pos| pos.account == *borrower) {
+                warn!("target borrower {:?} has no open position", borrower);
+            }
+        }
+    }
+
+    // the next `budget` position keys after the round robin cursor, wrapping around
+    fn next_round_robin(&mut self, budget: usize) -> Vec<Bytes32> {
+        let mut keys: Vec<Bytes32> = self.positions.keys().copied().collect();
+        keys.sort_unstable();
+        let start = self.scan_cursor.map_or(0, |cursor| keys.partition_point(|key| *key <= cursor));
+        let slice: Vec<Bytes32> = keys.iter().cycle().skip(start).take(budget.min(keys.len())).copied().collect();
+        self.scan_cursor = slice.last().copied();
+        slice
+    }
+
+     // positions under the liquidation threshold among the tick's selection, or
+     // among the positions of `pools` only for a scan between ticks
+     async fn get_underwater_positions(&mut self, pools: Option<&HashSet<Bytes32>>) -> Option<Vec<UnderwaterPosition>> {
 
             let start = Instant::now();  // Record the start time
 
@@ -468,17 +1418,30 @@ impl<
             //     &mut self.positions_critical
             // };
 
-            let positions : &mut Vec<Position> = if self.tick_counter % self.config.calc_all_positions_ticks == 0 {
+            // between full scans, positions last seen above the monitor level are skipped
+            let monitor_level = match self.strategy_config.monitor_margin_level_threshold {
+                0 => U256::MAX,
+                threshold => U256::from(threshold) * U256::from(10).pow(U256::from(25)),
+            };
+
+            // taken out of self so the scan below can call its methods
+            let mut positions: Vec<Position> = if let Some(pools) = pools {
+                self.positions.values().filter(|pos| pools.contains(&pos.pool)).cloned().collect()
+            } else if self.periodic_task_due(PeriodicTask::PositionScan, self.config.calc_all_positions_ticks) {
                 self.positions_all = self.positions.iter().map(|(_, pos)| pos.clone()).collect::<Vec<Position>>();
-                &mut self.positions_all
+                std::mem::take(&mut self.positions_all)
             } else {
                 self.positions_active = self.positions.iter()
-                    .filter_map(|(_, pos)| {
-                        if let Some(pool) = self.pools.get(&pos.pool) {
-                            if pool.activity_level > 0 {
-                                Some(pos.clone()) // Include the position if the pool has activity_level > 0
+                    .filter_map(|(position_key, pos)| {
+                        if self.pools.contains_key(&pos.pool) {
+                            if (self.position_activity.contains_key(position_key) && pos.margin_level < monitor_level)
+                                || self.price_watch.contains(&pos.pool)
+                                || self.fast_watch.contains(position_key)
+                                || self.priority_accounts.contains(&pos.account)
+                                || self.pending_watch.contains(&pos.account) {
+                                Some(pos.clone()) // Include the position if it had recent events
                             } else {
-                                None // Skip this position if the pool's activity_level is not greater than 0
+                                None // Skip dormant positions until the next full scan
                             }
                         } else {
                             info!("No pool found for position {:?}", pos.pool);
@@ -486,17 +1449,38 @@ impl<
                         }
                     })
                     .collect();
-                &mut self.positions_active
+                // a slice of the rest in turn, so no dormant position waits for the full scan
+                if self.strategy_config.positions_per_tick > 0 {
+                    let included: HashSet<Bytes32> = self.positions_active
+                        .iter()
+                        .map(|pos| hash_position_key(pos.account, pos.position_id))
+                        .collect();
+                    for position_key in self.next_round_robin(self.strategy_config.positions_per_tick) {
+                        if included.contains(&position_key) {
+                            continue;
+                        }
+                        if let Some(pos) = self.positions.get(&position_key) {
+                            self.positions_active.push(pos.clone());
+                        }
+                    }
+                }
+                std::mem::take(&mut self.positions_active)
             };
 
             //self.positions_all = self.positions.iter().map(|(_, pos)| pos.clone()).collect::<Vec<Position>>();
             //let positions : &mut Vec<Position> = &mut self.positions_all;
             // dbg!(&positions);
 
-            for position in positions.iter_mut() {
-                let mut user_total_collateral_usd = U256::ZERO;
-                let mut user_total_debt_usd = U256::ZERO;
+            // positions within this margin above the threshold are scanned every tick
+            let fast_watch_level = self.margin_level_threshold
+                .saturating_mul(U256::from(10_000 + self.strategy_config.fast_watch_margin_bps))
+                / U256::from(10_000);
+            let (mut promoted, mut demoted) = (0, 0);
+            let margin_call_level = self.margin_level_threshold
+                .saturating_mul(U256::from(10_000 + self.strategy_config.margin_call_buffer_bps))
+                / U256::from(10_000);
 
+            for position in positions.iter_mut() {
                 // Check if the pool exists before processing
                 let pool = match self.pools.get(&position.pool) {
                     Some(pool) => pool,
@@ -506,53 +1490,183 @@ impl<
                     }
                 };
 
-                //if didn't get price
-                if pool.price <= U256::ZERO  {
+                let target_borrowers = &self.strategy_config.target_borrowers;
+                if !target_borrowers.is_empty() && !target_borrowers.contains(&position.account) {
                     continue;
                 }
 
-                let price = pool.price;
-                let base_borrow_index = pool.base_borrow_index;
-                let base_debt = ray_mul(position.base_debt_scaled, base_borrow_index);
-                let base_decimals = pool.base_token_decimals;
-                user_total_collateral_usd += adjust_precision(position.base_collateral, base_decimals);
-                user_total_debt_usd += adjust_precision(base_debt, base_decimals);
-
-                let meme_borrow_index = pool.meme_borrow_index;
-                let meme_debt = ray_mul(position.meme_debt_scaled, meme_borrow_index);
-                let meme_decimals = pool.meme_token_decimals;
-                user_total_collateral_usd += ray_mul(price, adjust_precision(position.meme_collateral, meme_decimals));
-                user_total_debt_usd += ray_mul(price, adjust_precision(meme_debt, meme_decimals));
-
-                let margin_level = if user_total_debt_usd == U256::ZERO {
-                    U256::MAX
-                } else {
-                    ray_div(user_total_collateral_usd, user_total_debt_usd)
+                let Some((user_total_collateral_usd, user_total_debt_usd, margin_level)) = position_margin(position, pool) else {
+                    // no price yet for a position holding both tokens
+                    continue;
                 };
+                let base_debt = ray_mul(position.base_debt_scaled, pool.base_borrow_index);
+                let meme_debt = ray_mul(position.meme_debt_scaled, pool.meme_borrow_index);
 
                 // Update the margin_level in position
                 position.margin_level = margin_level;
+                if let Some(stored) = self.positions.get_mut(&hash_position_key(position.account, position.position_id)) {
+                    stored.margin_level = margin_level;
+                }
 
-                if margin_level < self.margin_level_threshold {
-                    //prevent resend tx
-                    let now: DateTime<Utc> = Utc::now();
-                    match self.sents.get(&hash_position_key(position.account.clone(), position.position_id)) {
-                        Some(&sent_time) => {
-                            let duration: Duration = now - sent_time;
-                            if duration.num_seconds() < RETRY_DURATION_IN_SECS {
-                                continue;
-                            }
-                        },
-                        None => {}
+                if self.strategy_config.fast_watch_margin_bps > 0 {
+                    let position_key = hash_position_key(position.account, position.position_id);
+                    if margin_level < fast_watch_level {
+                        if self.fast_watch.insert(position_key) {
+                            promoted += 1;
+                        }
+                    } else if self.fast_watch.remove(&position_key) {
+                        demoted += 1;
                     }
+                }
 
-                    underwater_positions.push((
-                        position.account,
-                        position.position_id,
-                        margin_level,
+                let position_key = hash_position_key(position.account, position.position_id);
+                // announce a position entering the margin call buffer, once until it recovers above it
+                if self.strategy_config.margin_call_buffer_bps > 0 {
+                    if margin_level >= margin_call_level {
+                        self.margin_called.remove(&position_key);
+                    } else if margin_level >= self.margin_level_threshold && self.margin_called.insert(position_key) {
+                        if let Some(margin_calls) = &self.margin_calls {
+                            let _ = margin_calls.send(MarginCall {
+                                deployment: self.deployment.name().to_string(),
+                                position: position_key,
+                                account: position.account,
+                                position_id: position.position_id,
+                                health: margin_level,
+                                threshold: self.margin_level_threshold,
+                            });
+                        }
+                    }
+                }
+
+                if let Some(&expiry) = self.blacklist.get(&position_key) {
+                    if margin_level >= self.margin_level_threshold {
+                        // healthy again, a later drop under water starts without the blacklist
+                        self.blacklist.remove(&position_key);
+                    } else if expiry > Utc::now() {
+                        metrics::record_skip(SkipReason::Blacklisted);
+                        continue;
+                    } else {
+                        info!("Blacklist of {:?} position_id:{} expired", position.account, position.position_id);
+                        self.blacklist.remove(&position_key);
+                    }
+                }
+
+                if let Some((reason, state)) = self.known_reverts.get(&position_key) {
+                    if *state == RevertedState::of(position, pool) {
+                        metrics::record_skip(SkipReason::KnownRevert);
+                        continue;
+                    }
+                    debug!("{:?} position_id:{} changed since it reverted with {}, simulating it again", position.account, position.position_id, reason);
+                    self.known_reverts.remove(&position_key);
+                }
+
+                if margin_level < self.margin_level_threshold {
+                    if self.skipped_accounts.contains(&position.account) {
+                        metrics::record_skip(SkipReason::AccountSkipped);
+                        continue;
+                    }
+
+                    //prevent resend tx
+                    let now: DateTime<Utc> = Utc::now();
+                    match self.sents.get(&hash_position_key(position.account.clone(), position.position_id)) {
+                        Some(&sent_time) => {
+                            let duration: Duration = now - sent_time;
+                            if duration.num_seconds() < RETRY_DURATION_IN_SECS {
+                                metrics::record_skip(SkipReason::Cooldown);
+                                continue;
+                            }
+                        },
+                        None => {}
+                    }
+
+                    let accounting_token = self.profit_token.unwrap_or(pool.base_token);
+                    let profit_token_price = value_in_token(&self.pools, accounting_token, ray(), pool.base_token);
+                    let expected_profit = match self.profit_model.expected_profit(
+                        position,
+                        pool,
                         user_total_collateral_usd,
                         user_total_debt_usd,
-                    ));
+                        profit_token_price,
+                    ) {
+                        Some(profit) => profit,
+                        None => {
+                            info!("Profit model skipped {:?} position_id:{}", position.account, position.position_id);
+                            metrics::record_skip(SkipReason::Unprofitable);
+                            continue;
+                        }
+                    };
+
+                    let base_decimals = self.token_decimals(pool.base_token).unwrap_or(pool.base_token_decimals);
+                    let expected_profit = match &self.strategy_config.flashloan {
+                        Some(flashloan) => {
+                            // the meme loan and its premium are in meme, priced in base like the profit
+                            let meme_decimals = self.token_decimals(pool.meme_token).unwrap_or(pool.meme_token_decimals);
+                            let in_base = |amount: U256| {
+                                value_in_token(&self.pools, pool.meme_token, adjust_precision(amount, meme_decimals), pool.base_token)
+                                    .map(|value| from_ray(value, base_decimals))
+                            };
+                            let (Some(meme_fee), Some(meme_loan)) =
+                                (in_base(flashloan.fee(meme_debt)), in_base(flashloan.loan_amount(meme_debt)))
+                            else {
+                                info!("Skipping {:?} position_id:{}, can't price its flashloan", position.account, position.position_id);
+                                metrics::record_skip(SkipReason::Unprofitable);
+                                continue;
+                            };
+                            let fee = flashloan.fee(base_debt).saturating_add(meme_fee);
+                            // the swapped collateral covers the loans and the profit, all of it exposed to slippage
+                            let slippage = flashloan.slippage(
+                                flashloan.loan_amount(base_debt).saturating_add(meme_loan).saturating_add(expected_profit),
+                            );
+                            if fee.saturating_add(slippage) >= expected_profit {
+                                info!(
+                                    "Skipping {:?} position_id:{}, flashloan fee {} and slippage {} exceed profit {}",
+                                    position.account, position.position_id,
+                                    format_amount(fee, Some(pool.base_token_decimals), &pool.base_symbol),
+                                    format_amount(slippage, Some(pool.base_token_decimals), &pool.base_symbol),
+                                    format_amount(expected_profit, Some(pool.base_token_decimals), &pool.base_symbol)
+                                );
+                                metrics::record_skip(SkipReason::Unprofitable);
+                                continue;
+                            }
+                            expected_profit - fee - slippage
+                        }
+                        None => expected_profit,
+                    };
+
+                    let accounting_profit = value_in_token(
+                        &self.pools,
+                        pool.base_token,
+                        adjust_precision(expected_profit, base_decimals),
+                        accounting_token,
+                    );
+                    // batches mix pools whose tokens differ in decimals, sum them in a common unit
+                    let common_profit = from_ray(
+                        accounting_profit.unwrap_or_else(|| adjust_precision(expected_profit, base_decimals)),
+                        U256::from(COMMON_DECIMALS),
+                    );
+                    info!(
+                        "underwater {:?} position_id:{} base debt {}, meme debt {}, expected profit {}, in accounting token {:?}",
+                        position.account, position.position_id,
+                        format_amount(base_debt, Some(pool.base_token_decimals), &pool.base_symbol),
+                        format_amount(meme_debt, Some(pool.meme_token_decimals), &pool.meme_symbol),
+                        format_amount(expected_profit, Some(pool.base_token_decimals), &pool.base_symbol),
+                        accounting_profit
+                    );
+
+                    underwater_positions.push(UnderwaterPosition {
+                        account: position.account,
+                        position_id: position.position_id,
+                        pool: position.pool,
+                        margin_level,
+                        collateral_usd: user_total_collateral_usd,
+                        debt_usd: user_total_debt_usd,
+                        base_debt,
+                        meme_debt,
+                        expected_profit,
+                        accounting_profit,
+                        common_profit,
+                        opportunity_id: opportunity_id(position.account, position.pool, self.last_block_number),
+                    });
                 }
             }
             
@@ -570,8 +1684,16 @@ impl<
             //     self.positions_critical.sort_by(|a, b| a.margin_level.cmp(&b.margin_level));
             // }
 
+            if self.strategy_config.fast_watch_margin_bps > 0 {
+                info!(
+                    "Fast watch: {} positions, {} promoted, {} demoted",
+                    self.fast_watch.len(), promoted, demoted
+                );
+            }
+
             info!("Underwater count: {}", underwater_positions.len());
-            underwater_positions.sort_by(|a, b| a.1.cmp(&b.1));
+            info!("Skipped opportunities: {:?}", metrics::skip_counts());
+            rank_by_profit(&mut underwater_positions, &self.priority_accounts);
             let top_underwater_positions = underwater_positions
                 .iter()
                 .take((LIQUIDATIONL_CHUNK_SIZE * LIQUIDATIONL_BATCH_SIZE) as usize)
@@ -584,9 +1706,120 @@ impl<
             Some(top_underwater_positions)  
         }
 
+    // request a refuel when the liquidator's gas balance runs low, at most once per cooldown
+    async fn check_native_balance(&mut self) -> Result<Option<Refuel>> {
+        let min_balance = self.strategy_config.min_native_balance_wei;
+        if min_balance.is_zero() {
+            return Ok(None);
+        }
+        if let Some(last_refuel) = self.last_refuel {
+            if (Utc::now() - last_refuel).num_seconds() < REFUEL_COOLDOWN_IN_SECS {
+                return Ok(None);
+            }
+        }
+
+        let balance = self.client.get_balance(self.liquidator).await?;
+        if balance >= min_balance {
+            return Ok(None);
+        }
+
+        let amount = self.strategy_config.refuel_amount_wei;
+        warn!(
+            "Liquidator balance {} below minimum {}, requesting refuel of {}",
+            format_amount(balance, Some(U256::from(18)), "ETH"),
+            format_amount(min_balance, Some(U256::from(18)), "ETH"),
+            format_amount(amount, Some(U256::from(18)), "ETH")
+        );
+        self.last_refuel = Some(Utc::now());
+        Ok(Some(Refuel { amount }))
+    }
+
+    /// Read the EIP-1967 implementation of every deployment contract on a slow
+    /// cadence and warn when one changes, since an upgrade may change behavior
+    /// or ABI under the bot. Contracts that aren't proxies read as zero.
+    async fn check_implementations(&mut self) -> Result<()> {
+        if let Some(last_check) = self.last_implementation_check {
+            if (Utc::now() - last_check).num_seconds() < IMPLEMENTATION_CHECK_SECS {
+                return Ok(());
+            }
+        }
+        self.last_implementation_check = Some(Utc::now());
+
+        let slot = U256::from_str(EIP1967_IMPLEMENTATION_SLOT)?;
+        let contracts = [
+            ("DataStore", Some(self.config.data_store)),
+            ("Reader", Some(self.config.reader)),
+            ("EventEmitter", Some(self.config.event_emitter)),
+            ("ExchangeRouter", Some(self.config.exchange_router)),
+            ("PoolUtils", self.config.pool_utils),
+            ("RoleStore", self.config.role_store),
+        ];
+        for (name, address) in contracts {
+            let Some(address) = address else {
+                continue;
+            };
+            let value = self.client.get_storage_at(address, slot).await?;
+            let implementation = Address::from_word(value.into());
+            if implementation.is_zero() {
+                continue;
+            }
+            match self.implementations.insert(address, implementation) {
+                None => info!("{} {:?} is a proxy, implementation {:?}", name, address, implementation),
+                Some(previous) if previous != implementation => {
+                    warn!(
+                        "{} {:?} upgraded from {:?} to {:?}, the bot may need an update",
+                        name, address, previous, implementation
+                    );
+                    if self.strategy_config.pause_on_upgrade {
+                        warn!("pausing liquidations after {} upgrade (--pause-on-upgrade)", name);
+                        self.paused_on_upgrade = true;
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    // reload the priority accounts file when it changed, one address per line
+    fn reload_priority_accounts(&mut self) -> Result<()> {
+        let Some(path) = self.strategy_config.priority_accounts_file.clone() else {
+            return Ok(());
+        };
+        let modified = std::fs::metadata(&path)?.modified()?;
+        if self.priority_accounts_modified == Some(modified) {
+            return Ok(());
+        }
+
+        let tracked: HashSet<Address> = self.positions.values().map(|pos| pos.account).collect();
+        let mut ignored = 0;
+        // parse the whole file before swapping it in, a bad edit keeps the last good set
+        let mut priority_accounts = HashSet::new();
+        for line in std::fs::read_to_string(&path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let account = Address::from_str(line)
+                .with_context(|| format!("invalid priority account {:?} in {}", line, path))?;
+            if tracked.contains(&account) {
+                priority_accounts.insert(account);
+            } else {
+                ignored += 1;
+            }
+        }
+        self.priority_accounts = priority_accounts;
+        self.priority_accounts_modified = Some(modified);
+        info!(
+            "reloaded priority accounts from {}: {} tracked, {} ignored",
+            path, self.priority_accounts.len(), ignored
+        );
+        Ok(())
+    }
+
     // load borrower state cache from file if exists
     fn load_cache(&mut self) -> Result<()> {
-        match File::open(STATE_CACHE_FILE) {
+        match File::open(&self.strategy_config.state_cache_file) {
             Ok(file) => {
                 let cache: StateCache = serde_json::from_reader(file)?;
                 info!("read state cache from file");
@@ -594,6 +1827,14 @@ impl<
                 self.positions = cache.positions;
                 self.pools = cache.pools;
                 self.sents = cache.sents;
+                self.position_attempts = cache.position_attempts;
+                self.skipped_accounts = cache.skipped_accounts;
+                if self.strategy_config.clear_skipped_accounts {
+                    self.clear_skipped_accounts(None);
+                }
+                if !self.skipped_accounts.is_empty() {
+                    warn!("permanently skipped accounts: {:?}", self.skipped_accounts);
+                }
             }
             Err(_) => {
                 info!("no state cache file found, creating new one");
@@ -607,119 +1848,250 @@ impl<
 
     // update known borrower state from last block to latest block
     async fn update_state(&mut self) -> Result<()> {
-        let latest_block = self.client.get_block_number().await?;
+        // follow the block collector's head when it runs, saving a round trip
+        let latest_block = match self.head_block_number {
+            Some(head_block_number) => head_block_number,
+            None => self.client.get_block_number().await?,
+        };
         let mut start_block = self.last_block_number;
         if start_block > latest_block {
             start_block = latest_block;
         }
+        self.update_margin_levle_threshold().await?;
+        self.prune_reorg_journal(latest_block);
+
+        // the log collector delivers position logs as events and tracks the block itself
+        if self.strategy_config.logs_from_collector {
+            return self.write_cache();
+        }
+
         info!(
             "Updating state from block {} to {}",
             start_block, latest_block
         );
 
-        self.update_margin_levle_threshold().await?;
-
-        self.get_position_logs(start_block.into(), latest_block)
-            .await?
-            .into_iter()
-            .for_each(|log| {
-                let pool_key = hash_pool_key(log.baseToken, log.memeToken);
+        let logs = self.get_position_logs(start_block.into(), latest_block).await?;
 
-                // Check if the pool exists, and set meme_symbol accordingly
-                let meme_symbol = if let Some(pool) = self.pools.get(&pool_key) {
-                    pool.meme_symbol.clone()  // If the pool exists, get the meme_symbol
-                } else {
-                    "".to_string()  // If the pool doesn't exist, set meme_symbol to an empty string
-                };
+        // skip positions with no activity since the configured block
+        let skip_since = self.strategy_config.skip_positions_inactive_since;
+        let mut last_activity: HashMap<Bytes32, u64> = HashMap::new();
+        for (log, block_number) in logs.iter() {
+            let position_key = hash_position_key(log.account, log.positionId);
+            let last = last_activity.entry(position_key).or_insert(0);
+            *last = std::cmp::max(*last, *block_number);
+        }
+        let inactive: HashSet<Bytes32> = last_activity
+            .iter()
+            .filter(|(_, block_number)| **block_number < skip_since)
+            .map(|(position_key, _)| *position_key)
+            .collect();
+        if !inactive.is_empty() {
+            info!("Skipped {} positions inactive since block {}", inactive.len(), skip_since);
+        }
 
-                info!("{} {:?} {} {} {} {} {}", 
-                    ActionType::from_u256(log.actionType).map_or("Unknown".to_string(), |action| action.to_string().into()), 
-                    log.account, meme_symbol, 
-                    log.baseCollateral, log.baseDebtScaled, log.memeCollateral, log.memeDebtScaled
-                ); 
+        for (log, block_number) in logs {
+            if !inactive.contains(&hash_position_key(log.account, log.positionId)) {
+                self.apply_position_log(log, block_number);
+            }
+        }
 
-                // Insert or update the pool's activity_level to 100
-                if let Some(existing_pool) = self.pools.get_mut(&pool_key) {
-                    existing_pool.activity_level = self.config.activity_level_init;  // Update existing pool's activity level
-                } else {
-                    let new_pool = Pool {
-                        price: U256::ZERO,  // Set to a default value, update later as needed
-                        price_decimals: U256::ZERO,  // Set to a default value
-                        base_token: log.baseToken,
-                        base_symbol: "".to_string(),  // Replace with actual base symbol
-                        base_token_decimals: U256::ZERO,  // Set to a default value
-                        base_borrow_index: U256::ZERO,  // Set to a default value
-                        meme_token: log.memeToken,
-                        meme_symbol: "".to_string(),
-                        meme_token_decimals: U256::ZERO,  // Set to a default value
-                        meme_borrow_index: U256::ZERO,  // Set to a default value
-                        activity_level: self.config.activity_level_init,  // Set activity_level to self.config.activity_level_init when the pool is new
-                    };
-                    self.pools.insert(pool_key, new_pool);
-                } 
-
-                let user = log.account; 
-                if ActionType::from_u256(log.actionType).map_or(false, |action| action == ActionType::Liquidation) || 
-                   ActionType::from_u256(log.actionType).map_or(false, |action| action == ActionType::Closed) {
-                    self.positions.remove(&hash_position_key(user, log.positionId)); 
-                    self.sents.remove(&hash_position_key(user, log.positionId));
-                    return;
-                }     
-                self.update_position(
-                    hash_position_key(user, log.positionId),
-                    user, 
-                    log.positionId,
-                    hash_pool_key(log.baseToken, log.memeToken), 
-                    meme_symbol,
-                    log.baseCollateral, 
-                    log.baseDebtScaled, 
-                    log.memeCollateral, 
-                    log.memeDebtScaled
-                );
-                return;
-            });
+        self.last_block_number = latest_block;
+        self.write_cache()
+    }
 
-        // write state cache to file
+    // write state cache to file
+    fn write_cache(&self) -> Result<()> {
         let cache = StateCache {
-            last_block_number: latest_block,
+            last_block_number: self.last_block_number,
             pools: self.pools.clone(),
             positions: self.positions.clone(),
             sents: self.sents.clone(),
+            position_attempts: self.position_attempts.clone(),
+            skipped_accounts: self.skipped_accounts.clone(),
         };
 
-        self.last_block_number = latest_block;
-        let file = File::create(STATE_CACHE_FILE)?;
+        let file = File::create(&self.strategy_config.state_cache_file)?;
         serde_json::to_writer_pretty(file, &cache)?;
 
         Ok(())
     }
 
+    // apply a position event to the tracked pools and positions
+    fn apply_position_log(&mut self, log: EventEmitter::Position, block_number: u64) {
+        let pool_key = hash_pool_key(log.baseToken, log.memeToken);
+        let position_key = hash_position_key(log.account, log.positionId);
+        self.position_journal.push((block_number, position_key, self.positions.get(&position_key).cloned()));
+
+        // Check if the pool exists, and set meme_symbol accordingly
+        let meme_symbol = if let Some(pool) = self.pools.get(&pool_key) {
+            pool.meme_symbol.clone()  // If the pool exists, get the meme_symbol
+        } else {
+            "".to_string()  // If the pool doesn't exist, set meme_symbol to an empty string
+        };
+        let (base_collateral, meme_collateral) = match self.pools.get(&pool_key) {
+            Some(pool) => (
+                format_amount(log.baseCollateral, Some(pool.base_token_decimals), &pool.base_symbol),
+                format_amount(log.memeCollateral, Some(pool.meme_token_decimals), &pool.meme_symbol),
+            ),
+            None => (
+                format_amount(log.baseCollateral, None, ""),
+                format_amount(log.memeCollateral, None, ""),
+            ),
+        };
+
+        info!("{} {:?} {} {} {} {} {}", 
+            ActionType::from_u256(log.actionType).map_or("Unknown".to_string(), |action| action.to_string().into()), 
+            log.account, meme_symbol, 
+            base_collateral, log.baseDebtScaled, meme_collateral, log.memeDebtScaled
+        ); 
+
+        // Insert or update the pool's activity_level to 100
+        if let Some(existing_pool) = self.pools.get_mut(&pool_key) {
+            existing_pool.activity_level = self.config.activity_level_init;  // Update existing pool's activity level
+        } else {
+            let new_pool = Pool {
+                price: U256::ZERO,  // Set to a default value, update later as needed
+                price_decimals: U256::ZERO,  // Set to a default value
+                base_token: log.baseToken,
+                base_symbol: "".to_string(),  // Replace with actual base symbol
+                base_token_decimals: U256::ZERO,  // Set to a default value
+                base_borrow_index: U256::ZERO,  // Set to a default value
+                meme_token: log.memeToken,
+                meme_symbol: "".to_string(),
+                meme_token_decimals: U256::ZERO,  // Set to a default value
+                meme_borrow_index: U256::ZERO,  // Set to a default value
+                activity_level: self.config.activity_level_init,  // Set activity_level to self.config.activity_level_init when the pool is new
+            };
+            self.pools.insert(pool_key, new_pool);
+        } 
+
+        // every event of the position adds to its activity, busier positions stay active longer
+        let max_activity = self.config.activity_level_init * ACTIVITY_LEVEL_MAX_EVENTS;
+        let activity = self.position_activity.entry(position_key).or_insert(0);
+        *activity = std::cmp::min(*activity + self.config.activity_level_init, max_activity);
+        // whatever the event changed, a cached simulation revert no longer holds
+        self.known_reverts.remove(&position_key);
+
+        let user = log.account; 
+        if ActionType::from_u256(log.actionType).map_or(false, |action| action == ActionType::Liquidation) || 
+           ActionType::from_u256(log.actionType).map_or(false, |action| action == ActionType::Closed) {
+            self.positions.remove(&hash_position_key(user, log.positionId)); 
+            self.sents.remove(&hash_position_key(user, log.positionId));
+            self.blacklist.remove(&hash_position_key(user, log.positionId));
+            self.position_activity.remove(&position_key);
+            self.margin_called.remove(&position_key);
+            self.position_attempts.remove(&position_key);
+            return;
+        }     
+        self.update_position(
+            hash_position_key(user, log.positionId),
+            user, 
+            log.positionId,
+            hash_pool_key(log.baseToken, log.memeToken), 
+            meme_symbol,
+            log.baseCollateral, 
+            log.baseDebtScaled, 
+            log.memeCollateral, 
+            log.memeDebtScaled
+        );
+    }
+
         // fetch all position events from the from_block to to_block
-    async fn get_position_logs(&self, from_block: u64, to_block: u64) -> Result<Vec<EventEmitter::Position>> {
+    async fn get_position_logs(&self, from_block: u64, to_block: u64) -> Result<Vec<(EventEmitter::Position, u64)>> {
         let event_emitter = EventEmitter::new(self.config.event_emitter, self.client.clone());
-        let mut res = Vec::new();
-        for start_block in
-            (from_block..to_block).step_by(LOG_BLOCK_RANGE as usize)
-        {
-            let end_block = std::cmp::min(start_block + LOG_BLOCK_RANGE - 1, to_block);
-            event_emitter.Position_filter()  
+        // keyed by block and log index so overlapping re-fetches dedupe and iterate in chain order
+        let mut res: BTreeMap<LogKey, EventEmitter::Position> = BTreeMap::new();
+        let mut boundaries = Vec::new();
+        let mut chunker = LogChunker::new(from_block, to_block, self.strategy_config.backfill_chunk_size);
+        while let Some((start_block, end_block)) = chunker.next_range() {
+            let logs = match event_emitter.Position_filter()
                 .from_block(start_block)
                 .to_block(end_block)
                 .address(self.config.event_emitter)
                 .query()
-                .await?
-                .into_iter()
-                .for_each(|(log, _)| {
-                    res.push(log);
-                });
+                .await
+            {
+                Ok(logs) => logs,
+                Err(e) if is_range_limit_error(&e) && chunker.shrink() => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if start_block > from_block {
+                boundaries.push(start_block);
+            }
+            Self::insert_position_logs(&mut res, logs, start_block, end_block);
+            chunker.advance(end_block);
+        }
+
+        if self.strategy_config.verify_log_boundaries {
+            // re-fetch around every chunk boundary to fill logs providers drop there
+            for boundary in boundaries {
+                let start_block = boundary.saturating_sub(LOG_BOUNDARY_OVERLAP);
+                let end_block = std::cmp::min(boundary + LOG_BOUNDARY_OVERLAP - 1, to_block);
+                let logs = event_emitter.Position_filter()
+                    .from_block(start_block)
+                    .to_block(end_block)
+                    .address(self.config.event_emitter)
+                    .query()
+                    .await?;
+                let added = Self::insert_position_logs(&mut res, logs, start_block, end_block);
+                if added > 0 {
+                    warn!("Boundary re-fetch of blocks {}..={} recovered {} missing logs", start_block, end_block, added);
+                }
+            }
         }
 
-        Ok(res)
+        Ok(res.into_iter().map(|((block_number, _, _), log)| (log, block_number)).collect())
+    }
+
+    /// Add logs fetched for blocks `start_block..=end_block`, returning how many were new.
+    /// Logs are deduplicated by block and log index, ones without an index are all kept.
+    fn insert_position_logs(
+        res: &mut BTreeMap<LogKey, EventEmitter::Position>,
+        logs: Vec<(EventEmitter::Position, Log)>,
+        start_block: u64,
+        end_block: u64,
+    ) -> usize {
+        let mut previous = None;
+        let mut added = 0;
+        for (log, raw_log) in logs {
+            let block_number = raw_log.block_number.unwrap_or(end_block);
+            let key = match raw_log.log_index {
+                Some(log_index) => (block_number, Some(log_index), 0),
+                // nothing tells two of them apart, keep each in the order fetched
+                None => (block_number, None, res.len()),
+            };
+            if previous.map_or(false, |previous| key < previous) {
+                warn!("Out of order logs in blocks {}..={}, at {:?}", start_block, end_block, key);
+            }
+            previous = Some(key);
+            if res.insert(key, log).is_none() {
+                added += 1;
+            }
+        }
+        added
     }
 
     fn insert_or_update_pool(&mut self, pool: Pool) {
         let pool_id = hash_pool_key(pool.base_token, pool.meme_token);
 
+        // a pool only known from a position log has no symbols or price yet
+        let discovered = self.pools
+            .get(&pool_id)
+            .map_or(true, |known| known.price == U256::ZERO && known.base_symbol.is_empty());
+        // pools found by the startup refresh are scanned anyway
+        if discovered && self.tick_counter > 0 {
+            if let Some(new_pools) = &self.new_pools {
+                let _ = new_pools.send(NewPool {
+                    deployment: self.deployment.name().to_string(),
+                    pool: pool_id,
+                    base_token: pool.base_token,
+                    base_symbol: pool.base_symbol.clone(),
+                    meme_token: pool.meme_token,
+                    meme_symbol: pool.meme_symbol.clone(),
+                });
+            }
+        }
+
         // If the pool doesn't exist, insert it
         if !self.pools.contains_key(&pool_id) {
             info!("Inserting new pool: {:?}", pool_id);
@@ -747,13 +2119,29 @@ impl<
         info!("tick_counter: {:?}", self.tick_counter);
         let start = Instant::now();  // Record the start time
 
-        if self.tick_counter % self.config.update_all_pools_ticks == 0 {  // Every 50 seconds (5p) update all pools
+        // without cached pools there is nothing to refresh incrementally, even when deferred
+        let mut refresh_all = self.periodic_task_due(PeriodicTask::PoolRefresh, self.config.update_all_pools_ticks)
+            || (self.tick_counter == 0 && self.pools.is_empty());
+        let min_refresh_blocks = self.strategy_config.min_pool_refresh_blocks;
+        if refresh_all && self.last_pool_refresh_block > 0
+            && self.last_block_number < self.last_pool_refresh_block + min_refresh_blocks {
+            info!(
+                "min pool refresh blocks {} clamps update_all_pools interval, last refresh at block {}",
+                min_refresh_blocks, self.last_pool_refresh_block
+            );
+            refresh_all = false;
+        }
+
+        if refresh_all {  // Every 50 seconds (5p) update all pools
+            self.last_pool_refresh_block = self.last_block_number;
             info!("getPoolsInfo_1");
             //1.get pool account
             let mut pools_count :u64 = 0;
-            let pools_count_ret = reader.getPoolsCount(
-                self.config.data_store.clone(), 
-            ).call().await;
+            let pools_count_ret = retry_rpc(
+                || async { reader.getPoolsCount(self.config.data_store.clone()).call().await },
+                self.strategy_config.rpc_max_retries,
+                RPC_RETRY_BASE_DELAY,
+            ).await;
             match pools_count_ret {
                 Ok(ret) => {
                     //all_pools.extend(pools._0);
@@ -769,11 +2157,17 @@ impl<
             let mut all_pools: Vec<_> = Vec::new();
             for i in 0..(pools_count / POLL_POOL_CHUNK_SIZE) {
 
-                let pools_result = reader.getPoolsInfo_1(
-                    self.config.data_store.clone(), 
-                    U256::from(i * POLL_POOL_CHUNK_SIZE), 
-                    U256::from((i + 1) * POLL_POOL_CHUNK_SIZE)
-                ).call().await;
+                let pools_result = retry_rpc(
+                    || async {
+                        reader.getPoolsInfo_1(
+                            self.config.data_store.clone(), 
+                            U256::from(i * POLL_POOL_CHUNK_SIZE), 
+                            U256::from((i + 1) * POLL_POOL_CHUNK_SIZE)
+                        ).call().await
+                    },
+                    self.strategy_config.rpc_max_retries,
+                    RPC_RETRY_BASE_DELAY,
+                ).await;
 
                 match pools_result {
                     Ok(pools) => {
@@ -788,11 +2182,17 @@ impl<
 
             // 3.Get remaining pools if pools_count is not divisible by POLL_POOL_CHUNK_SIZE
             if pools_count % POLL_POOL_CHUNK_SIZE != 0 {
-                let pools_result = reader.getPoolsInfo_1(
-                    self.config.data_store.clone(), 
-                    U256::from((pools_count / POLL_POOL_CHUNK_SIZE) * POLL_POOL_CHUNK_SIZE), 
-                    U256::from(pools_count),
-                ).call().await;
+                let pools_result = retry_rpc(
+                    || async {
+                        reader.getPoolsInfo_1(
+                            self.config.data_store.clone(), 
+                            U256::from((pools_count / POLL_POOL_CHUNK_SIZE) * POLL_POOL_CHUNK_SIZE), 
+                            U256::from(pools_count),
+                        ).call().await
+                    },
+                    self.strategy_config.rpc_max_retries,
+                    RPC_RETRY_BASE_DELAY,
+                ).await;
 
                 match pools_result {
                     Ok(pools) => {
@@ -846,11 +2246,16 @@ impl<
             let pool_chunks = active_pools_ids.chunks(POLL_POOL_CHUNK_SIZE as usize);
             let mut active_pools = Vec::new(); 
             for chunk in pool_chunks {
-                let chunk_clone = chunk.to_vec();
-                let pools_result = reader.getPoolsInfo_2(
-                    self.config.data_store.clone(), 
-                    chunk_clone, 
-                ).call().await;
+                let pools_result = retry_rpc(
+                    || async {
+                        reader.getPoolsInfo_2(
+                            self.config.data_store.clone(), 
+                            chunk.to_vec(), 
+                        ).call().await
+                    },
+                    self.strategy_config.rpc_max_retries,
+                    RPC_RETRY_BASE_DELAY,
+                ).await;
 
                 match pools_result {
                     Ok(pools) => {
@@ -888,13 +2293,37 @@ impl<
         }
 
         // Decrease activity_level every ACTIVITY_LEVEL_DECREASE_PER_TIMES ticks
-        if self.tick_counter % self.config.activity_level_decrease_ticks == 0 {
+        if self.periodic_task_due(PeriodicTask::ActivityDecay, self.config.activity_level_decrease_ticks) {
             for pool in self.pools.values_mut() {
                 if pool.activity_level > 0 {
                     pool.activity_level -= 1;
                     //info!("Decreased activity_level for pool: {:?}", pool);
                 }
             }
+            // positions whose activity decayed to 0 are left to the full scan
+            let active = self.position_activity.len();
+            self.position_activity.retain(|_, activity| {
+                *activity -= 1;
+                *activity > 0
+            });
+            debug!("{} of {} active positions still active", self.position_activity.len(), active);
+        }
+
+        // query decimals() of tokens seen for the first time
+        let tokens: Vec<Address> = self.pools
+            .values()
+            .flat_map(|pool| [pool.base_token, pool.meme_token])
+            .chain(self.strategy_config.accounting_token)
+            .collect();
+        self.decimals.resolve(self.client.as_ref(), tokens).await;
+
+        // pinned once resolved, so realized and recent profits never mix tokens
+        // when another quote token later takes the most pools
+        if self.profit_token.is_none() {
+            self.profit_token = self.strategy_config.accounting_token.or_else(|| main_quote_token(&self.pools));
+            if let Some(profit_token) = self.profit_token {
+                info!("valuing profits in {:?}", profit_token);
+            }
         }
 
         let duration = start.elapsed();  // Calculate elapsed time
@@ -903,9 +2332,228 @@ impl<
         Ok(())
     }
 
+    // add a confirmed liquidation's token deltas to the realized profit and
+    // stop liquidating once it reaches the profit target
+    fn record_realized_profit(&mut self, confirmed: &LiquidationConfirmed) {
+        let Some(accounting_token) = self.profit_token else {
+            warn!("No profit token yet, not counting the profit of {:?}", confirmed.tx_hash);
+            return;
+        };
+        let mut profit = I256::ZERO;
+        for (token, delta) in confirmed.token_deltas.iter() {
+            let value = self
+                .token_decimals(*token)
+                .and_then(|decimals| value_in_token(&self.pools, *token, adjust_precision(delta.unsigned_abs(), decimals), accounting_token));
+            let Some(value) = value else {
+                warn!("Can't value {} of token {:?} from {:?} in the accounting token", delta, token, confirmed.tx_hash);
+                continue;
+            };
+            let value = I256::try_from(from_ray(value, U256::from(COMMON_DECIMALS))).unwrap_or(I256::MAX);
+            profit = if delta.is_negative() {
+                profit.saturating_sub(value)
+            } else {
+                profit.saturating_add(value)
+            };
+        }
+        self.realized_profit = self.realized_profit.saturating_add(profit);
+        if let Some(block_number) = confirmed.block_number {
+            self.recent_profits.push((block_number, profit));
+        }
+        info!("Realized profit {} after {:?}", self.realized_profit, confirmed.tx_hash);
+        self.check_profit_target();
+    }
+
+    // count a failed or reverted submission, tripping the circuit breaker once
+    // --breaker-threshold of them happened in a row within BREAKER_WINDOW_SECS
+    fn record_submission_failure(&mut self, failure: &str) {
+        let threshold = self.strategy_config.breaker_threshold;
+        if threshold == 0 {
+            return;
+        }
+        let now = (self.clock)();
+        self.failure_streak.push(now);
+        self.failure_streak.retain(|failed_at| now - *failed_at < Duration::seconds(BREAKER_WINDOW_SECS));
+        if self.breaker_tripped_at.is_some() || (self.failure_streak.len() as u64) < threshold {
+            return;
+        }
+        self.breaker_tripped_at = Some(now);
+        let message = format!(
+            "circuit breaker tripped for {} after {} failed liquidations in a row, last: {}, {}",
+            self.deployment.name(),
+            self.failure_streak.len(),
+            failure,
+            match self.strategy_config.breaker_cooldown_secs {
+                0 => "holding liquidations until restart".to_string(),
+                cooldown => format!("holding liquidations for {}s", cooldown),
+            }
+        );
+        error!("{}", message);
+        alerts::send(message);
+    }
+
+    // whether the circuit breaker holds liquidations, re-arming it once the
+    // cooldown passed
+    fn breaker_open(&mut self) -> bool {
+        let Some(tripped_at) = self.breaker_tripped_at else {
+            return false;
+        };
+        let cooldown = self.strategy_config.breaker_cooldown_secs;
+        if cooldown == 0 || (self.clock)() - tripped_at < Duration::seconds(cooldown as i64) {
+            return true;
+        }
+        info!("circuit breaker re-armed after {}s, resuming liquidations", cooldown);
+        self.breaker_tripped_at = None;
+        self.failure_streak.clear();
+        false
+    }
+
+    // stop liquidating once the realized profit reaches the target, and
+    // resume if a reorg takes it back below
+    fn check_profit_target(&mut self) {
+        let target = self.strategy_config.profit_target;
+        if target == 0 {
+            return;
+        }
+        let reached = self.realized_profit >= I256::try_from(target).unwrap_or(I256::MAX);
+        if reached && !self.profit_target_reached {
+            info!(
+                "Profit target {} reached with {} realized, no further liquidations will be sent",
+                target, self.realized_profit
+            );
+        } else if !reached && self.profit_target_reached {
+            warn!("Realized profit {} fell below target {}, resuming liquidations", self.realized_profit, target);
+        }
+        self.profit_target_reached = reached;
+    }
+
+    // undo the position changes and realized profit of blocks replaced by a
+    // reorg, then fetch position logs again from the fork
+    fn handle_reorg(&mut self, from_block: u64) {
+        let (undone, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.position_journal)
+            .into_iter()
+            .partition(|(block_number, _, _)| *block_number >= from_block);
+        self.position_journal = kept;
+        for (_, position_key, previous) in undone.iter().rev() {
+            self.sents.remove(position_key);
+            match previous {
+                Some(position) => {
+                    // re-evaluate the restored position on the next tick
+                    self.pending_watch.insert(position.account);
+                    self.positions.insert(*position_key, position.clone());
+                }
+                None => {
+                    self.positions.remove(position_key);
+                }
+            }
+        }
+
+        let (reverted, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.recent_profits)
+            .into_iter()
+            .partition(|(block_number, _)| *block_number >= from_block);
+        self.recent_profits = kept;
+        for (block_number, profit) in reverted {
+            self.realized_profit = self.realized_profit.saturating_sub(profit);
+            warn!("Liquidation profit {} of block {} reverted by reorg", profit, block_number);
+        }
+        self.check_profit_target();
+
+        self.last_block_number = std::cmp::min(self.last_block_number, from_block.saturating_sub(1));
+        warn!(
+            "Reorg from block {}: undid {} position changes, refetching logs from block {}",
+            from_block, undone.len(), self.last_block_number
+        );
+    }
+
+    // forget journal entries too deep to be reorged
+    fn prune_reorg_journal(&mut self, head_block: u64) {
+        let oldest = head_block.saturating_sub(REORG_DEPTH as u64);
+        self.position_journal.retain(|(block_number, _, _)| *block_number >= oldest);
+        self.recent_profits.retain(|(block_number, _)| *block_number >= oldest);
+    }
+
+    // publish a snapshot of the state for the health server's /status
+    fn report_status(&self) {
+        health::report(
+            self.deployment.name(),
+            DeploymentStatus {
+                last_block: self.last_block_number,
+                pools: self.pools.len(),
+                positions: self.positions.len(),
+                realized_profit: self.realized_profit.to_string(),
+                profit_target_reached: self.profit_target_reached,
+            },
+        );
+    }
+
+    // whether a task run every `interval_ticks` ticks is due on this tick, the
+    // ticks before the first count as startup, where it runs unless deferred
+    fn periodic_task_due(&self, task: PeriodicTask, interval_ticks: u64) -> bool {
+        if self.tick_counter == 0 {
+            return !self.strategy_config.defer_on_startup.contains(&task);
+        }
+        self.tick_counter % interval_ticks == 0
+    }
+
+    // decimals of a token from its ERC20 decimals(), or as the pools report it
+    // for a token not resolved yet
+    fn token_decimals(&self, token: Address) -> Option<U256> {
+        if let Some(decimals) = self.decimals.get(token) {
+            return Some(U256::from(decimals));
+        }
+        self.pools.values().find_map(|pool| {
+            if pool.base_token == token {
+                Some(pool.base_token_decimals)
+            } else if pool.meme_token == token {
+                Some(pool.meme_token_decimals)
+            } else {
+                None
+            }
+        })
+    }
+
+    // count a liquidation attempt of the position and permanently skip its account once
+    // the position hits the cap, however many of the account's positions shared its batches
+    fn record_position_attempt(&mut self, underwater: &UnderwaterPosition) {
+        let max_attempts = self.strategy_config.max_account_attempts;
+        if max_attempts == 0 {
+            return;
+        }
+
+        let (_, attempts) = self.position_attempts
+            .entry(hash_position_key(underwater.account, underwater.position_id))
+            .or_insert((underwater.account, 0));
+        *attempts += 1;
+        if *attempts >= max_attempts && self.skipped_accounts.insert(underwater.account) {
+            error!(
+                "Account {:?} position_id:{} reached {} liquidation attempts, skipping the account permanently until cleared with --clear-skipped-accounts or POST /skipped-accounts/clear",
+                underwater.account, underwater.position_id, attempts
+            );
+        }
+    }
+
+    // lift the permanent skip of `account`, or of every account, resetting their attempts
+    fn clear_skipped_accounts(&mut self, account: Option<Address>) {
+        let cleared: Vec<Address> = match account {
+            Some(account) => {
+                self.position_attempts.retain(|_, (attempted, _)| *attempted != account);
+                self.skipped_accounts.take(&account).into_iter().collect()
+            }
+            None => {
+                self.position_attempts.clear();
+                self.skipped_accounts.drain().collect()
+            }
+        };
+        info!("clearing {} skipped accounts: {:?}", cleared.len(), cleared);
+    }
+
     async fn update_margin_levle_threshold(&mut self) -> Result<()> {
         let reader = Reader::new(self.config.reader, self.client.clone());
-        self.margin_level_threshold = reader.getMarginLevelThreshold(self.config.data_store).call().await.unwrap()._0;
+        let data_store = self.config.data_store;
+        self.margin_level_threshold = retry_rpc(
+            || async { reader.getMarginLevelThreshold(data_store).call().await },
+            self.strategy_config.rpc_max_retries,
+            RPC_RETRY_BASE_DELAY,
+        ).await?._0;
         info!("margin_levle_threshold {:?}", self.margin_level_threshold);
         Ok(())
     }
@@ -1015,6 +2663,62 @@ fn hash_position_key(account: Address, position_id: U256) -> Bytes32 {
 
 }
 
+/// Deterministic id of a liquidation opportunity: the hash of the account,
+/// the pool and the block it was discovered at. Logged by the strategy and the
+/// executor so one token traces an opportunity from discovery to confirmation.
+pub fn opportunity_id(account: Address, pool: Bytes32, block_number: u64) -> Bytes32 {
+    FixedBytes::from_slice(&keccak256_hash(&(account, pool, U256::from(block_number)).abi_encode()))
+}
+
+// a position's collateral and debt valued in base at the pool's price, and its
+// margin level; None when it needs the price and the pool has none yet. Collateral
+// and debt in the same token need no price conversion.
+fn position_margin(position: &Position, pool: &Pool) -> Option<(U256, U256, U256)> {
+    let base_only = position.meme_collateral == U256::ZERO && position.meme_debt_scaled == U256::ZERO;
+    let meme_only = position.base_collateral == U256::ZERO && position.base_debt_scaled == U256::ZERO;
+    if pool.price <= U256::ZERO && !base_only && !meme_only {
+        return None;
+    }
+
+    let price = pool.price;
+    let base_decimals = pool.base_token_decimals;
+    let base_debt = ray_mul(position.base_debt_scaled, pool.base_borrow_index);
+    let meme_decimals = pool.meme_token_decimals;
+    let meme_debt = ray_mul(position.meme_debt_scaled, pool.meme_borrow_index);
+    let collateral = adjust_precision(position.base_collateral, base_decimals)
+        + ray_mul(price, adjust_precision(position.meme_collateral, meme_decimals));
+    let debt = adjust_precision(base_debt, base_decimals) + ray_mul(price, adjust_precision(meme_debt, meme_decimals));
+
+    let margin_level = if (base_debt == U256::ZERO && meme_debt == U256::ZERO) || (!meme_only && debt == U256::ZERO) {
+        U256::MAX
+    } else if meme_only {
+        // price cancels out, compare meme amounts directly
+        ray_div(adjust_precision(position.meme_collateral, meme_decimals), adjust_precision(meme_debt, meme_decimals))
+    } else {
+        ray_div(collateral, debt)
+    };
+    Some((collateral, debt, margin_level))
+}
+
+// whether drawing a position's debt amounts from its pool leaves both balances at
+// MINIMUM_LIQUIDITY or more. A liquidation takes the whole position, it can't be resized to fit.
+fn fits_pool_liquidity(underwater: &UnderwaterPosition, (base_balance, meme_balance): (U256, U256), minimum: U256) -> bool {
+    underwater.base_debt.saturating_add(minimum) <= base_balance
+        && underwater.meme_debt.saturating_add(minimum) <= meme_balance
+}
+
+// most profitable first so it gets the earliest nonce, unconvertible profits last;
+// profits are in the accounting token, or in ray precision of the pool base token before the pools are known.
+// Priority accounts go first, keeping the order within each group.
+fn rank_by_profit(underwaters: &mut [UnderwaterPosition], priority_accounts: &HashSet<Address>) {
+    underwaters.sort_by(|a, b| {
+        b.accounting_profit.cmp(&a.accounting_profit)
+            .then_with(|| b.expected_profit.cmp(&a.expected_profit))
+            .then_with(|| a.position_id.cmp(&b.position_id))
+    });
+    underwaters.sort_by_key(|underwater| !priority_accounts.contains(&underwater.account));
+}
+
 fn ray_mul(a: U256, b: U256) -> U256 {
     let precision: U512 = U512::from(10).pow(U512::from(27));
     let half_precision: U512 = U512::from(5)*U512::from(10).pow(U512::from(26));
@@ -1032,9 +2736,588 @@ fn ray_div(a: U256, b: U256) -> U256 {
     return U256::from_str(&((a512*precision+ b512/two)/b512).to_string()).unwrap();
 }
 
+// whether `underwater` may go on to be built against its fresh on-chain debts: the
+// last read before the tx, so a position repaid since the scan is caught here and
+// never reaches the builder with nothing to repay, which reverts on some tokens
+fn check_repay(underwater: &UnderwaterPosition, (base_debt, meme_debt): (U256, U256), tolerance_bps: u64) -> Result<(), SkipReason> {
+    if base_debt.is_zero() && meme_debt.is_zero() {
+        return Err(SkipReason::ZeroRepay);
+    }
+    if repay_within_bounds(underwater.base_debt, base_debt, tolerance_bps)
+        && repay_within_bounds(underwater.meme_debt, meme_debt, tolerance_bps)
+    {
+        Ok(())
+    } else {
+        Err(SkipReason::RepaySanity)
+    }
+}
+
+// a repay amount is sane if it is not negative-equivalent and within tolerance of the actual debt
+fn repay_within_bounds(computed: U256, actual: U256, tolerance_bps: u64) -> bool {
+    if computed.bit(255) {
+        return false;
+    }
+    let computed512 = U512::from(computed) * U512::from(10_000u64);
+    let upper = U512::from(actual) * U512::from(10_000u64 + tolerance_bps);
+    let lower = U512::from(actual) * U512::from(10_000u64.saturating_sub(tolerance_bps));
+    computed512 <= upper && computed512 >= lower
+}
+
+// one whole token in ray precision
+fn ray() -> U256 {
+    U256::from(10).pow(U256::from(27))
+}
+
+// the base token the most pools quote in, the lowest address on a tie
+fn main_quote_token(pools: &HashMap<Bytes32, Pool>) -> Option<Address> {
+    let mut counts: BTreeMap<Address, usize> = BTreeMap::new();
+    for pool in pools.values() {
+        *counts.entry(pool.base_token).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)))
+        .map(|(token, _)| token)
+}
+
+// convert a ray precision amount of token into target through the pool pairing them
+fn value_in_token(pools: &HashMap<Bytes32, Pool>, token: Address, amount: U256, target: Address) -> Option<U256> {
+    if token == target {
+        return Some(amount);
+    }
+    let pool = pools.get(&hash_pool_key(token, target))?;
+    if pool.price == U256::ZERO {
+        return None;
+    }
+    if pool.meme_token == token && pool.base_token == target {
+        Some(ray_mul(pool.price, amount))
+    } else if pool.base_token == token && pool.meme_token == target {
+        Some(ray_div(amount, pool.price))
+    } else {
+        None
+    }
+}
+
+// inverse of adjust_precision, from ray back to the token's decimals
+fn from_ray(a: U256, decimals: U256) -> U256 {
+    let precision: U512 = U512::from(10).pow(U512::from(27));
+    let a512 : U512 = U512::from(a);
+    return U256::from_str(&(a512*U512::from(10).pow(U512::from(decimals))/precision).to_string()).unwrap();
+}
+
 fn adjust_precision(a: U256, decimals: U256) -> U256 {
     let precision: U512 = U512::from(10).pow(U512::from(27));
     let a512 : U512 = U512::from(a);
     return U256::from_str(&(a512*precision/U512::from(10).pow(U512::from(decimals))).to_string()).unwrap();
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::profit_model::DefaultProfitModel;
+    use alloy::{
+        network::Ethereum,
+        providers::{ProviderBuilder, RootProvider},
+        transports::http::{Client, Http},
+    };
+    use alloy::sol_types::SolCall;
+
+    type TestStrategy = MmStrategy<Http<Client>, RootProvider<Http<Client>>, Ethereum>;
+
+    // the command line defaults, with every feature that defaults to off left off
+    fn config() -> Config {
+        Config {
+            chain_id: 31337,
+            skip_positions_inactive_since: 0,
+            max_account_attempts: 0,
+            clear_skipped_accounts: false,
+            repay_sanity_tolerance_bps: 100,
+            observe_only: false,
+            min_pool_refresh_blocks: 0,
+            accounting_token: None,
+            native_token: None,
+            fast_watch_margin_bps: 0,
+            margin_call_buffer_bps: 0,
+            priority_accounts_file: None,
+            target_borrowers: Vec::new(),
+            verify_log_boundaries: false,
+            backfill_chunk_size: 1024,
+            min_native_balance_wei: U256::ZERO,
+            refuel_amount_wei: U256::ZERO,
+            minimum_liquidity: None,
+            monitor_margin_level_threshold: 130,
+            positions_per_tick: 0,
+            bid_percentage: 0,
+            deployed_addresses_file: DEPLOYED_ADDRESSES.to_string(),
+            deployment_addresses: Some(
+                deployments::REQUIRED_CONTRACTS
+                    .iter()
+                    .zip(1u8..)
+                    .map(|(contract, byte)| (contract.to_string(), Address::repeat_byte(byte)))
+                    .collect(),
+            ),
+            state_cache_file: STATE_CACHE_FILE.to_string(),
+            rpc_max_retries: 3,
+            pause_on_upgrade: false,
+            logs_from_collector: false,
+            blacklist_ttl_secs: 3600,
+            scan_concurrency: 4,
+            min_profit: 0,
+            multicall_address: None,
+            multicall_batch_size: 10,
+            profit_target: 0,
+            breaker_threshold: 0,
+            breaker_cooldown_secs: 0,
+            defer_on_startup: Vec::new(),
+            flashloan: None,
+        }
+    }
+
+    // a localnet strategy on the node at `rpc`, its config adjusted by `configure`
+    fn strategy_on(rpc: &str, configure: impl FnOnce(&mut Config)) -> TestStrategy {
+        let mut config = config();
+        configure(&mut config);
+        let client = Arc::new(ProviderBuilder::new().on_http(rpc.parse().unwrap()));
+        MmStrategy::new(
+            client,
+            config,
+            Deployment::LOCALNET,
+            Address::repeat_byte(9),
+            0,
+            0,
+            10,
+            60 * 60 * 24 * 2,
+            60 * 60 * 24 * 7,
+            60 * 60 * 24,
+            Arc::new(DefaultProfitModel::new(0)),
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    // a localnet strategy on a node that's never called
+    fn strategy(configure: impl FnOnce(&mut Config)) -> TestStrategy {
+        strategy_on("http://127.0.0.1:1", configure)
+    }
+
+    #[test]
+    fn interval_ticks_are_never_zero() {
+        assert_eq!(interval_ticks(100, 10), 10);
+        // a period shorter than a tick runs every tick
+        assert_eq!(interval_ticks(5, 10), 1);
+        assert_eq!(interval_ticks(0, 10), 1);
+        assert_eq!(interval_ticks(100, 0), 100);
+    }
+
+    fn unit(decimals: u8) -> U256 {
+        U256::from(10).pow(U256::from(decimals))
+    }
+
+    // a pool with both borrow indexes at 1 ray and no price yet
+    fn unpriced_pool() -> Pool {
+        Pool {
+            price: U256::ZERO,
+            price_decimals: U256::from(27),
+            base_token: Address::repeat_byte(1),
+            base_symbol: "USDC".to_string(),
+            base_token_decimals: U256::from(6),
+            base_borrow_index: ray(),
+            meme_token: Address::repeat_byte(2),
+            meme_symbol: "MEME".to_string(),
+            meme_token_decimals: U256::from(18),
+            meme_borrow_index: ray(),
+            activity_level: 0,
+        }
+    }
+
+    fn position(base_collateral: U256, base_debt: U256, meme_collateral: U256, meme_debt: U256) -> Position {
+        Position {
+            account: Address::repeat_byte(3),
+            position_id: U256::from(1),
+            pool: Bytes32::repeat_byte(4),
+            meme_symbol: "MEME".to_string(),
+            base_collateral,
+            base_debt_scaled: base_debt,
+            meme_collateral,
+            meme_debt_scaled: meme_debt,
+            margin_level: U256::ZERO,
+        }
+    }
+
+    fn underwater(base_debt: U256, meme_debt: U256) -> UnderwaterPosition {
+        UnderwaterPosition {
+            account: Address::repeat_byte(3),
+            position_id: U256::from(1),
+            pool: Bytes32::repeat_byte(4),
+            margin_level: ray() / U256::from(2),
+            collateral_usd: U256::ZERO,
+            debt_usd: U256::ZERO,
+            base_debt,
+            meme_debt,
+            expected_profit: U256::ZERO,
+            accounting_profit: None,
+            common_profit: U256::ZERO,
+            opportunity_id: Bytes32::ZERO,
+        }
+    }
+
+    #[test]
+    fn liquidations_may_not_draw_pools_below_minimum_liquidity() {
+        let minimum = U256::from(1000);
+        let balances = (U256::from(10_000), U256::from(5_000));
+        // leaves exactly the minimum in both pools
+        assert!(fits_pool_liquidity(&underwater(U256::from(9_000), U256::from(4_000)), balances, minimum));
+        // one unit more of either repay breaches it
+        assert!(!fits_pool_liquidity(&underwater(U256::from(9_001), U256::from(4_000)), balances, minimum));
+        assert!(!fits_pool_liquidity(&underwater(U256::from(9_000), U256::from(4_001)), balances, minimum));
+    }
+
+    #[test]
+    fn zero_repays_never_reach_the_builder() {
+        // repaid on-chain since the scan, a full tolerance would let the debts through
+        let repaid = underwater(U256::ZERO, U256::ZERO);
+        assert_eq!(check_repay(&repaid, (U256::ZERO, U256::ZERO), 10_000), Err(SkipReason::ZeroRepay));
+        let scanned = underwater(U256::from(100), U256::ZERO);
+        assert_eq!(check_repay(&scanned, (U256::ZERO, U256::ZERO), 10_000), Err(SkipReason::ZeroRepay));
+        assert_eq!(check_repay(&scanned, (U256::from(100), U256::ZERO), 0), Ok(()));
+        assert_eq!(check_repay(&scanned, (U256::from(200), U256::ZERO), 0), Err(SkipReason::RepaySanity));
+    }
+
+    #[test]
+    fn over_large_and_negative_equivalent_repays_are_insane() {
+        let actual = U256::from(10_000);
+        // 1% either way of the actual debt at 100 bps
+        assert!(repay_within_bounds(U256::from(10_100), actual, 100));
+        assert!(repay_within_bounds(U256::from(9_900), actual, 100));
+        assert!(!repay_within_bounds(U256::from(10_101), actual, 100));
+        assert!(!repay_within_bounds(U256::from(9_899), actual, 100));
+        // a negative amount wrapped to U256 is never sane, not even with the widest tolerance
+        let minus_one = U256::MAX;
+        assert!(!repay_within_bounds(minus_one, U256::MAX, 10_000));
+        assert!(!repay_within_bounds(U256::ONE << 255, U256::MAX, 10_000));
+        assert_eq!(
+            check_repay(&underwater(minus_one, U256::ZERO), (actual, U256::ZERO), 10_000),
+            Err(SkipReason::RepaySanity)
+        );
+    }
+
+    #[test]
+    fn liquidations_are_ranked_by_expected_profit() {
+        let ranked = |id: u64, accounting_profit: Option<u64>, expected_profit: u64, account: u8| {
+            let mut underwater = underwater(U256::from(1), U256::ZERO);
+            underwater.position_id = U256::from(id);
+            underwater.accounting_profit = accounting_profit.map(U256::from);
+            underwater.expected_profit = U256::from(expected_profit);
+            underwater.account = Address::repeat_byte(account);
+            underwater
+        };
+        let mut underwaters = vec![
+            ranked(1, Some(10), 10, 3),
+            ranked(2, None, 1_000, 3),
+            ranked(3, Some(30), 5, 3),
+            ranked(4, Some(10), 20, 3),
+            ranked(5, Some(10), 20, 3),
+            ranked(6, Some(1), 1, 7),
+        ];
+
+        rank_by_profit(&mut underwaters, &HashSet::new());
+        let order: Vec<U256> = underwaters.iter().map(|underwater| underwater.position_id).collect();
+        // ties on the accounting profit break on the raw profit, then the position id
+        assert_eq!(order, [3u64, 4, 5, 1, 6, 2].map(U256::from));
+
+        rank_by_profit(&mut underwaters, &HashSet::from([Address::repeat_byte(7)]));
+        assert_eq!(underwaters[0].position_id, U256::from(6));
+        assert_eq!(underwaters[1].position_id, U256::from(3));
+    }
+
+    #[test]
+    fn a_liquidation_values_both_legs_of_the_position() {
+        // one meme is worth half a base
+        let pool = Pool { price: ray() / U256::from(2), ..unpriced_pool() };
+        // 100 base and 40 meme of collateral against 50 base and 100 meme of debt
+        let position = position(unit(6) * U256::from(100), unit(6) * U256::from(50), unit(18) * U256::from(40), unit(18) * U256::from(100));
+        let (collateral, debt, margin_level) = position_margin(&position, &pool).unwrap();
+        assert_eq!(collateral, ray() * U256::from(120));
+        assert_eq!(debt, ray() * U256::from(100));
+        assert_eq!(margin_level, ray() * U256::from(12) / U256::from(10));
+    }
+
+    #[test]
+    fn single_token_positions_need_no_price() {
+        let pool = unpriced_pool();
+        // 110 meme against 100 meme of debt
+        let meme_only = position(U256::ZERO, U256::ZERO, unit(18) * U256::from(110), unit(18) * U256::from(100));
+        let (_, _, margin_level) = position_margin(&meme_only, &pool).unwrap();
+        assert_eq!(margin_level, ray() * U256::from(11) / U256::from(10));
+
+        let base_only = position(unit(6) * U256::from(120), unit(6) * U256::from(100), U256::ZERO, U256::ZERO);
+        let (_, _, margin_level) = position_margin(&base_only, &pool).unwrap();
+        assert_eq!(margin_level, ray() * U256::from(12) / U256::from(10));
+
+        // meme collateral against base debt can't be valued without a price
+        let mixed = position(U256::ZERO, unit(6) * U256::from(100), unit(18) * U256::from(110), U256::ZERO);
+        assert!(position_margin(&mixed, &pool).is_none());
+    }
+
+    fn word(value: impl Into<U256>) -> [u8; 32] {
+        value.into().to_be_bytes()
+    }
+
+    #[test]
+    fn a_direct_liquidation_calls_the_exchange_router() {
+        let strategy = strategy(|_| {});
+        let tx = strategy.build_liquidation_tx(&underwater(U256::from(10_000), U256::ZERO)).unwrap();
+
+        let mut expected = ExchangeRouter::executeLiquidationBatchCall::SELECTOR.to_vec();
+        // offset and length of the params array, then its one (account, positionId)
+        expected.extend(word(U256::from(0x20)));
+        expected.extend(word(U256::from(1)));
+        expected.extend(Address::repeat_byte(3).into_word().0);
+        expected.extend(word(U256::from(1)));
+        assert_eq!(tx.input().unwrap().as_ref(), expected.as_slice());
+        assert_eq!(tx.to(), Some(strategy.config.exchange_router));
+        assert_eq!(tx.from, Some(Address::repeat_byte(9)));
+    }
+
+    #[test]
+    fn a_flashloan_liquidation_borrows_the_repay_amounts() {
+        let (provider, router, swap_router) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb), Address::repeat_byte(0xc));
+        let mut strategy = strategy(|config| {
+            config.flashloan = Some(FlashloanConfig { provider, router, swap_router, fee_bps: 5, max_slippage_bps: 50 });
+        });
+        let mut underwater = underwater(U256::from(10_000), U256::ZERO);
+        underwater.expected_profit = U256::from(500);
+
+        // the pool must be known to look up the tokens to borrow
+        assert!(strategy.build_liquidation_tx(&underwater).is_err());
+        strategy.pools.insert(Bytes32::repeat_byte(4), unpriced_pool());
+        let tx = strategy.build_liquidation_tx(&underwater).unwrap();
+
+        let expected = IFlashLiquidator::flashLiquidateCall {
+            lender: provider,
+            exchangeRouter: strategy.config.exchange_router,
+            params: vec![IFlashLiquidator::LiquidationParams { account: Address::repeat_byte(3), positionId: U256::from(1) }],
+            // the base debt plus the 10 bps interest buffer, nothing of the meme token
+            loans: vec![IFlashLiquidator::Loan { token: Address::repeat_byte(1), amount: U256::from(10_010) }],
+            swapRouter: swap_router,
+            tokens: vec![Address::repeat_byte(1), Address::repeat_byte(2)],
+            minProfits: vec![IFlashLiquidator::MinProfit { token: Address::repeat_byte(1), amount: U256::from(500) }],
+        }
+        .abi_encode();
+        assert_eq!(tx.input().unwrap().as_ref(), expected.as_slice());
+        assert_eq!(tx.to(), Some(router));
+    }
+
+    #[test]
+    fn logs_without_an_index_are_never_deduplicated() {
+        let fetched = |position_id: u64, block_number: u64, log_index: Option<u64>| {
+            let event = EventEmitter::Position {
+                account: Address::repeat_byte(3),
+                actionType: U256::ZERO,
+                baseToken: Address::repeat_byte(1),
+                memeToken: Address::repeat_byte(2),
+                positionId: U256::from(position_id),
+                baseCollateral: U256::ZERO,
+                baseDebtScaled: U256::ZERO,
+                memeCollateral: U256::ZERO,
+                memeDebtScaled: U256::ZERO,
+            };
+            (event, Log { block_number: Some(block_number), log_index, ..Default::default() })
+        };
+        let mut res = BTreeMap::new();
+        let added = TestStrategy::insert_position_logs(
+            &mut res,
+            vec![fetched(1, 10, Some(0)), fetched(2, 10, None), fetched(3, 10, None)],
+            10,
+            10,
+        );
+        assert_eq!(added, 3);
+        // a re-fetch dedupes the indexed log only
+        let added = TestStrategy::insert_position_logs(&mut res, vec![fetched(1, 10, Some(0)), fetched(4, 10, None)], 10, 10);
+        assert_eq!(added, 1);
+        let position_ids: Vec<U256> = res.values().map(|log| log.positionId).collect();
+        assert_eq!(position_ids, [2u64, 3, 4, 1].map(U256::from));
+    }
+
+    #[test]
+    fn a_reorg_undoes_the_position_changes_and_profits_it_replaced() {
+        let mut strategy = strategy(|_| {});
+        let changed = hash_position_key(Address::repeat_byte(1), U256::from(1));
+        let opened = hash_position_key(Address::repeat_byte(2), U256::from(1));
+        let kept = hash_position_key(Address::repeat_byte(3), U256::from(1));
+        let before = position(U256::from(100), U256::ZERO, U256::ZERO, U256::ZERO);
+        let after = position(U256::from(50), U256::ZERO, U256::ZERO, U256::ZERO);
+        strategy.positions = HashMap::from([(changed, after.clone()), (opened, after.clone()), (kept, after.clone())]);
+        strategy.position_journal = vec![
+            (8, kept, Some(before.clone())),
+            (10, changed, Some(before.clone())),
+            (11, opened, None),
+        ];
+        strategy.sents.insert(changed, Utc::now());
+        strategy.recent_profits = vec![(9, I256::try_from(5).unwrap()), (11, I256::try_from(7).unwrap())];
+        strategy.realized_profit = I256::try_from(12).unwrap();
+        strategy.last_block_number = 12;
+
+        strategy.handle_reorg(10);
+
+        assert_eq!(strategy.positions[&changed].base_collateral, U256::from(100));
+        assert!(!strategy.positions.contains_key(&opened));
+        assert_eq!(strategy.positions[&kept].base_collateral, U256::from(50));
+        assert!(!strategy.sents.contains_key(&changed));
+        assert!(strategy.pending_watch.contains(&before.account));
+        assert_eq!(strategy.position_journal.len(), 1);
+        assert_eq!(strategy.realized_profit, I256::try_from(5).unwrap());
+        assert_eq!(strategy.last_block_number, 9);
+    }
+
+    #[test]
+    fn an_account_is_skipped_once_a_position_hits_the_attempt_cap() {
+        let mut strategy = strategy(|config| config.max_account_attempts = 2);
+        let first = underwater(U256::from(1), U256::ZERO);
+        let second = UnderwaterPosition { position_id: U256::from(2), ..first.clone() };
+        let other = UnderwaterPosition { account: Address::repeat_byte(5), ..first.clone() };
+
+        // two positions of the account batched together are one attempt each
+        strategy.record_position_attempt(&first);
+        strategy.record_position_attempt(&second);
+        strategy.record_position_attempt(&other);
+        assert!(strategy.skipped_accounts.is_empty());
+
+        strategy.record_position_attempt(&first);
+        assert_eq!(strategy.skipped_accounts, HashSet::from([first.account]));
+
+        strategy.record_position_attempt(&other);
+        strategy.clear_skipped_accounts(Some(first.account));
+        assert_eq!(strategy.skipped_accounts, HashSet::from([other.account]));
+        // its attempts start over
+        strategy.record_position_attempt(&first);
+        assert!(!strategy.skipped_accounts.contains(&first.account));
+        strategy.clear_skipped_accounts(None);
+        assert!(strategy.skipped_accounts.is_empty());
+        assert!(strategy.position_attempts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuels_only_with_a_funding_source_and_a_low_balance() {
+        // without a funding source the threshold is left at 0 and the node is never
+        // asked for the balance
+        let mut unfunded = strategy(|config| config.refuel_amount_wei = U256::from(10));
+        assert!(unfunded.check_native_balance().await.unwrap().is_none());
+
+        let mut server = mockito::Server::new_async().await;
+        let balance = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({ "method": "eth_getBalance" })))
+            .with_header("content-type", "application/json")
+            .with_body_from_request(|request| {
+                let request: serde_json::Value = serde_json::from_slice(request.body().unwrap()).unwrap();
+                serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": "0x10" }).to_string().into_bytes()
+            })
+            .create_async()
+            .await;
+        let mut funded = strategy_on(&server.url(), |config| {
+            config.min_native_balance_wei = U256::from(100);
+            config.refuel_amount_wei = U256::from(10);
+        });
+        let refuel = funded.check_native_balance().await.unwrap();
+        assert_eq!(refuel.map(|refuel| refuel.amount), Some(U256::from(10)));
+        // one refuel per cooldown, the node isn't asked again
+        assert!(funded.check_native_balance().await.unwrap().is_none());
+        balance.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_halted_strategy_reads_nothing_for_its_liquidations() {
+        let mut server = mockito::Server::new_async().await;
+        let node = server.mock("POST", "/").expect(0).create_async().await;
+        let mut strategy = strategy_on(&server.url(), |_| {});
+        strategy.paused_on_upgrade = true;
+
+        let actions = strategy.liquidation_actions(vec![underwater(U256::from(1), U256::ZERO)]).await;
+
+        assert!(actions.is_empty());
+        node.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_flashloan_liquidator_checks_the_helpers_keeper_role() {
+        // the role has members and only the flashloan helper holds it
+        let router = Address::repeat_byte(0xb);
+        let mut server = mockito::Server::new_async().await;
+        let role_store = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({ "method": "eth_call" })))
+            .with_header("content-type", "application/json")
+            .with_body_from_request(move |request| {
+                let request: serde_json::Value = serde_json::from_slice(request.body().unwrap()).unwrap();
+                let call = &request["params"][0];
+                let input = call["input"].as_str().or(call["data"].as_str()).unwrap_or_default().to_string();
+                let holds = input.starts_with(&format!("0x{}", hex::encode(RoleStore::getRoleMemberCountCall::SELECTOR)))
+                    || input.contains(&hex::encode(router));
+                let result = format!("0x{}", hex::encode(U256::from(holds as u8).to_be_bytes::<32>()));
+                serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }).to_string().into_bytes()
+            })
+            .expect_at_least(1)
+            .create_async()
+            .await;
+
+        let mut direct = strategy_on(&server.url(), |_| {});
+        direct.config.role_store = Some(Address::repeat_byte(0xd));
+        assert!(direct.check_liquidator_authorized().await.is_err());
+
+        let mut flashloan = strategy_on(&server.url(), |config| {
+            config.flashloan = Some(FlashloanConfig {
+                provider: Address::repeat_byte(0xa),
+                router,
+                swap_router: Address::repeat_byte(0xc),
+                fee_bps: 5,
+                max_slippage_bps: 50,
+            });
+        });
+        flashloan.config.role_store = Some(Address::repeat_byte(0xd));
+        flashloan.check_liquidator_authorized().await.unwrap();
+        role_store.assert_async().await;
+    }
+
+    thread_local! {
+        static NOW: std::cell::Cell<i64> = const { std::cell::Cell::new(1_700_000_000) };
+    }
+
+    fn test_clock() -> DateTime<Utc> {
+        DateTime::from_timestamp(NOW.with(|now| now.get()), 0).unwrap()
+    }
+
+    fn advance(secs: i64) {
+        NOW.with(|now| now.set(now.get() + secs));
+    }
+
+    #[test]
+    fn the_breaker_trips_on_a_failure_streak_and_re_arms_after_the_cooldown() {
+        let mut strategy = strategy(|config| {
+            config.breaker_threshold = 3;
+            config.breaker_cooldown_secs = 60;
+        });
+        strategy.clock = test_clock;
+
+        strategy.record_submission_failure("reverted");
+        strategy.record_submission_failure("reverted");
+        assert!(!strategy.breaker_open());
+        strategy.record_submission_failure("reverted");
+        assert!(strategy.breaker_open());
+
+        advance(59);
+        assert!(strategy.breaker_open());
+        advance(1);
+        assert!(!strategy.breaker_open());
+        assert!(strategy.failure_streak.is_empty());
+
+        // failures spread wider than the window never trip it
+        for _ in 0..3 {
+            strategy.record_submission_failure("reverted");
+            advance(BREAKER_WINDOW_SECS);
+        }
+        assert!(!strategy.breaker_open());
+    }
+}
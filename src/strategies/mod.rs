@@ -1,2 +1,9 @@
+pub mod decimals;
+pub mod deployments;
+pub mod flashloan;
 pub mod mm_strategy;
+pub mod multicall;
+pub mod profit_model;
+pub mod retry;
+pub mod supervised_strategy;
 pub mod types;
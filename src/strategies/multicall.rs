@@ -0,0 +1,88 @@
+use anyhow::Result;
+use alloy::{
+    contract as alloy_contract,
+    primitives::{Address, Bytes},
+    sol,
+    sol_types::SolCall,
+};
+use tracing::{info, warn};
+
+sol! {
+    #[sol(rpc)]
+    interface IMulticall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        struct Result {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (Result[] memory returnData);
+    }
+}
+
+/// The Multicall3 address when a contract is deployed there, None otherwise
+/// so callers fall back to individual calls.
+pub async fn resolve<T, P, N>(client: &P, multicall: Address) -> Result<Option<Address>>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, N>,
+    N: alloy_contract::private::Network,
+{
+    if client.get_code_at(multicall).await?.is_empty() {
+        warn!("no multicall contract at {:?}, reading with individual calls", multicall);
+        return Ok(None);
+    }
+    info!("batching reads through multicall {:?}", multicall);
+    Ok(Some(multicall))
+}
+
+/// Run `calls` to `target` through Multicall3 `aggregate3`, `batch_size`
+/// calls per eth_call. Results are in call order, a reverted call fails
+/// alone while a failed eth_call fails its whole batch.
+pub async fn aggregate<T, P, N, C>(
+    client: &P,
+    multicall: Address,
+    target: Address,
+    calls: &[C],
+    batch_size: usize,
+) -> Vec<Result<C::Return>>
+where
+    T: alloy_contract::private::Transport + ::core::clone::Clone,
+    P: alloy_contract::private::Provider<T, N>,
+    N: alloy_contract::private::Network,
+    C: SolCall,
+{
+    let multicall = IMulticall3::new(multicall, client);
+    let mut results = Vec::with_capacity(calls.len());
+    for batch in calls.chunks(batch_size.max(1)) {
+        let call3s: Vec<IMulticall3::Call3> = batch
+            .iter()
+            .map(|call| IMulticall3::Call3 {
+                target,
+                allowFailure: true,
+                callData: Bytes::from(call.abi_encode()),
+            })
+            .collect();
+        match multicall.aggregate3(call3s).call().await {
+            Ok(ret) => {
+                for result in ret.returnData {
+                    results.push(if result.success {
+                        C::abi_decode_returns(&result.returnData, true).map_err(anyhow::Error::from)
+                    } else {
+                        Err(anyhow::anyhow!("multicall subcall to {:?} reverted", target))
+                    });
+                }
+            }
+            Err(e) => {
+                let message = e.to_string();
+                results.extend(batch.iter().map(|_| Err(anyhow::anyhow!("multicall failed: {}", message))));
+            }
+        }
+    }
+    results
+}
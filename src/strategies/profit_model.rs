@@ -0,0 +1,166 @@
+use super::decimals::{from_common_unit, COMMON_DECIMALS};
+use super::mm_strategy::{Pool, Position};
+use alloy::primitives::U256;
+use std::fmt::Debug;
+
+/// Estimates the expected net profit of liquidating a position.
+///
+/// Implement this to plug custom economics (builder tips, inventory carry, ...)
+/// into [MmStrategy](super::mm_strategy::MmStrategy) without touching the scan loop.
+/// `collateral_usd` and `debt_usd` are the position's totals in ray precision,
+/// as computed by the strategy for the margin level check. `profit_token_price`
+/// is one profit token (--accounting-token) in the pool's base token, in ray,
+/// None when the pools don't price it.
+pub trait ProfitModel: Debug + Send + Sync {
+    /// Returns the expected net profit in the pool's base token, or `None` to
+    /// skip the opportunity.
+    fn expected_profit(
+        &self,
+        position: &Position,
+        pool: &Pool,
+        collateral_usd: U256,
+        debt_usd: U256,
+        profit_token_price: Option<U256>,
+    ) -> Option<U256>;
+
+    /// Expected profit of liquidating positions worth `profits` in one tx, the
+    /// sum of them unless the model's profits don't add up.
+    fn batch_profit(&self, profits: &[U256]) -> U256 {
+        profits.iter().fold(U256::ZERO, |total, profit| total.saturating_add(*profit))
+    }
+}
+
+/// Default model: every underwater position is worth `total_profit`, given
+/// in [COMMON_DECIMALS] units of the profit token and converted to the pool's
+/// base token at its price, or taken as base token when it has none.
+#[derive(Debug, Clone)]
+pub struct DefaultProfitModel {
+    pub total_profit: u128,
+}
+
+impl DefaultProfitModel {
+    pub fn new(total_profit: u128) -> Self {
+        Self { total_profit }
+    }
+}
+
+impl ProfitModel for DefaultProfitModel {
+    fn expected_profit(
+        &self,
+        _position: &Position,
+        pool: &Pool,
+        _collateral_usd: U256,
+        _debt_usd: U256,
+        profit_token_price: Option<U256>,
+    ) -> Option<U256> {
+        let decimals = pool.base_token_decimals.try_into().unwrap_or(COMMON_DECIMALS);
+        let total_profit = match profit_token_price {
+            Some(price) => U256::from(self.total_profit).saturating_mul(price) / U256::from(10).pow(U256::from(27)),
+            None => U256::from(self.total_profit),
+        };
+        Some(from_common_unit(total_profit, decimals))
+    }
+
+    /// `total_profit` is a flat estimate per liquidation, a batch doesn't
+    /// multiply it, or the gas bid would grow with the batch size.
+    fn batch_profit(&self, profits: &[U256]) -> U256 {
+        profits.iter().copied().max().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{Address, FixedBytes};
+
+    /// Only liquidates positions whose collateral covers their debt by a margin.
+    #[derive(Debug)]
+    struct SpreadModel {
+        min_spread: U256,
+    }
+
+    impl ProfitModel for SpreadModel {
+        fn expected_profit(
+            &self,
+            _position: &Position,
+            _pool: &Pool,
+            collateral_usd: U256,
+            debt_usd: U256,
+            _profit_token_price: Option<U256>,
+        ) -> Option<U256> {
+            let spread = collateral_usd.checked_sub(debt_usd)?;
+            (spread >= self.min_spread).then_some(spread)
+        }
+    }
+
+    fn position() -> Position {
+        Position {
+            account: Address::repeat_byte(1),
+            position_id: U256::from(1),
+            pool: FixedBytes::repeat_byte(2),
+            meme_symbol: "MEME".to_string(),
+            base_collateral: U256::from(100),
+            base_debt_scaled: U256::from(90),
+            meme_collateral: U256::ZERO,
+            meme_debt_scaled: U256::ZERO,
+            margin_level: U256::ZERO,
+        }
+    }
+
+    fn pool(base_token_decimals: u64) -> Pool {
+        Pool {
+            price: U256::ZERO,
+            price_decimals: U256::from(27),
+            base_token: Address::repeat_byte(3),
+            base_symbol: "USDC".to_string(),
+            base_token_decimals: U256::from(base_token_decimals),
+            base_borrow_index: U256::from(10).pow(U256::from(27)),
+            meme_token: Address::repeat_byte(4),
+            meme_symbol: "MEME".to_string(),
+            meme_token_decimals: U256::from(18),
+            meme_borrow_index: U256::from(10).pow(U256::from(27)),
+            activity_level: 0,
+        }
+    }
+
+    fn ray() -> U256 {
+        U256::from(10).pow(U256::from(27))
+    }
+
+    #[test]
+    fn custom_model_overrides_the_decision() {
+        let model: Box<dyn ProfitModel> = Box::new(SpreadModel { min_spread: U256::from(10) });
+        // the default model liquidates whatever is under water
+        let default = DefaultProfitModel::new(1);
+        assert!(default.expected_profit(&position(), &pool(18), U256::from(100), U256::from(95), None).is_some());
+        // the custom one skips a thin spread and takes a wide one
+        assert_eq!(model.expected_profit(&position(), &pool(18), U256::from(100), U256::from(95), None), None);
+        assert_eq!(
+            model.expected_profit(&position(), &pool(18), U256::from(100), U256::from(80), None),
+            Some(U256::from(20))
+        );
+    }
+
+    #[test]
+    fn default_model_converts_to_the_base_token() {
+        let model = DefaultProfitModel::new(5 * 10u128.pow(18));
+        // no price, taken as base token and rescaled to its 6 decimals
+        assert_eq!(
+            model.expected_profit(&position(), &pool(6), U256::ZERO, U256::ZERO, None),
+            Some(U256::from(5_000_000))
+        );
+        // one profit token is worth 2 base tokens
+        assert_eq!(
+            model.expected_profit(&position(), &pool(6), U256::ZERO, U256::ZERO, Some(ray() * U256::from(2))),
+            Some(U256::from(10_000_000))
+        );
+    }
+
+    #[test]
+    fn batch_profit_sums_unless_flat() {
+        let profits = [U256::from(3), U256::from(5)];
+        assert_eq!(SpreadModel { min_spread: U256::ZERO }.batch_profit(&profits), U256::from(8));
+        // the flat default estimate doesn't grow with the batch
+        assert_eq!(DefaultProfitModel::new(5).batch_profit(&profits), U256::from(5));
+    }
+}
@@ -0,0 +1,52 @@
+use alloy::{contract::Error as ContractError, transports::{RpcError, TransportError}};
+use anyhow::Result;
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Run `f` until it succeeds, retrying up to `max_retries` times on transient
+/// RPC failures (connection errors, timeouts, rate limits) with exponential
+/// backoff and jitter starting at `base_delay`. Contract reverts and other
+/// deterministic errors are returned right away.
+pub async fn retry_rpc<F, Fut, T, E>(mut f: F, max_retries: u32, base_delay: Duration) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+    E: Into<anyhow::Error>,
+{
+    let mut attempt = 0;
+    loop {
+        let error = match f().await {
+            Ok(value) => return Ok(value),
+            Err(e) => e.into(),
+        };
+        if attempt >= max_retries || !is_transient(&error) {
+            return Err(error);
+        }
+        let delay = base_delay * 2u32.saturating_pow(attempt) + jitter(base_delay);
+        attempt += 1;
+        warn!("Transient RPC error, retry {}/{} in {:?}: {}", attempt, max_retries, delay, error);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+fn is_transient(error: &anyhow::Error) -> bool {
+    let transport_error = if let Some(ContractError::TransportError(e)) = error.downcast_ref::<ContractError>() {
+        e
+    } else if let Some(e) = error.downcast_ref::<TransportError>() {
+        e
+    } else {
+        return false;
+    };
+    match transport_error {
+        RpcError::Transport(_) => true,
+        RpcError::ErrorResp(payload) => payload.is_retry_err(),
+        _ => false,
+    }
+}
+
+// up to half the base delay, so retries from concurrent callers spread out
+fn jitter(base_delay: Duration) -> Duration {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+    base_delay.mul_f64((nanos % 1000) as f64 / 2000.0)
+}
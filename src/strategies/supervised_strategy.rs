@@ -0,0 +1,84 @@
+use anyhow::Result;
+use artemis_core::types::Strategy;
+use async_trait::async_trait;
+use futures::FutureExt;
+use std::panic::AssertUnwindSafe;
+use tracing::{error, info};
+
+use crate::{shutdown, watchdog};
+
+/// What to do when the supervised strategy panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnStrategyPanic {
+    /// Rebuild the strategy and re-seed it from the persisted state cache.
+    Restart,
+    /// Exit the process so an orchestrator can restart it.
+    Exit,
+}
+
+/// Builds a fresh strategy, used to replace one that panicked.
+pub type StrategyFactory<E, A> = Box<dyn Fn() -> Result<Box<dyn Strategy<E, A>>> + Send + Sync>;
+
+/// A strategy that wraps another strategy and catches panics in its event
+/// handling. Without it a panic ends the engine's strategy task while the
+/// collectors and executors keep running with nothing to feed them.
+pub struct SupervisedStrategy<E, A> {
+    inner: Box<dyn Strategy<E, A>>,
+    factory: StrategyFactory<E, A>,
+    on_panic: OnStrategyPanic,
+    restarts: u64,
+}
+
+impl<E, A> SupervisedStrategy<E, A> {
+    /// Supervise `inner`, replacing it with one from `factory` when it panics.
+    pub fn new(inner: Box<dyn Strategy<E, A>>, factory: StrategyFactory<E, A>, on_panic: OnStrategyPanic) -> Self {
+        Self { inner, factory, on_panic, restarts: 0 }
+    }
+}
+
+/// Implementation of the [Strategy](Strategy) trait for the [SupervisedStrategy](SupervisedStrategy).
+#[async_trait]
+impl<E: Send + Sync + 'static, A: Send + Sync + 'static> Strategy<E, A> for SupervisedStrategy<E, A> {
+    async fn sync_state(&mut self) -> Result<()> {
+        self.inner.sync_state().await
+    }
+
+    async fn process_event(&mut self, event: E) -> Vec<A> {
+        // collected events are ignored once shutting down
+        if shutdown::requested() {
+            return Vec::new();
+        }
+        watchdog::record_event();
+        match AssertUnwindSafe(self.inner.process_event(event)).catch_unwind().await {
+            Ok(actions) => actions,
+            Err(_) => {
+                match self.on_panic {
+                    OnStrategyPanic::Exit => {
+                        error!("strategy panicked, exiting (--on-strategy-panic exit)");
+                        std::process::exit(1);
+                    }
+                    OnStrategyPanic::Restart => {
+                        self.restarts += 1;
+                        error!(
+                            "strategy panicked, restarting from persisted state (restart #{})",
+                            self.restarts
+                        );
+                        self.inner = match (self.factory)() {
+                            Ok(inner) => inner,
+                            Err(e) => {
+                                error!("strategy restart failed to build it, exiting: {}", e);
+                                std::process::exit(1);
+                            }
+                        };
+                        if let Err(e) = self.inner.sync_state().await {
+                            error!("strategy restart failed to sync state, exiting: {}", e);
+                            std::process::exit(1);
+                        }
+                        info!("strategy restarted");
+                    }
+                }
+                Vec::new()
+            }
+        }
+    }
+}
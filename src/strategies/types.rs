@@ -1,5 +1,15 @@
+use crate::collectors::block_collector::{BlockEvent, NewBlock, Reorg};
+use crate::collectors::control_collector::ControlCommand;
+use crate::collectors::margin_call_collector::MarginCall;
+use crate::collectors::oracle_collector::PriceUpdate;
+use crate::collectors::pool_collector::NewPool;
 use crate::collectors::time_collector::NewTick;
-use artemis_core::executors::mempool_executor::SubmitTxToMempool;
+use crate::executors::protect_executor::{LiquidationOutcome, SubmitLiquidation};
+use crate::executors::refuel_executor::Refuel;
+use super::deployments::DeploymentAddresses;
+use super::flashloan::FlashloanConfig;
+use alloy::primitives::{Address, U256};
+use alloy::rpc::types::{Log, Transaction};
 // use alloy::{
 //     contract as alloy_contract,
 // };
@@ -8,16 +18,120 @@ use artemis_core::executors::mempool_executor::SubmitTxToMempool;
 #[derive(Debug, Clone)]
 pub enum Event {
     NewTick(NewTick),
+    NewBlock(NewBlock),
+    Reorg(Reorg),
+    PendingTx(Transaction),
+    PriceUpdate(PriceUpdate),
+    Log(Log),
+    LiquidationOutcome(LiquidationOutcome),
+    NewPool(NewPool),
+    MarginCall(MarginCall),
+    Control(ControlCommand),
+}
+
+impl From<BlockEvent> for Event {
+    fn from(event: BlockEvent) -> Self {
+        match event {
+            BlockEvent::NewBlock(block) => Event::NewBlock(block),
+            BlockEvent::Reorg(reorg) => Event::Reorg(reorg),
+        }
+    }
 }
 
 /// Core Action enum for the current strategy.
 #[derive(Debug, Clone)]
 pub enum Action<N : alloy::providers::Network> {
-    SubmitTx(SubmitTxToMempool<N>),
+    SubmitTx(SubmitLiquidation<N>),
+    Refuel(Refuel),
+}
+
+/// A task the strategy runs every few ticks, and once at startup unless deferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PeriodicTask {
+    /// Refresh every pool, every update_all_pools_secs.
+    PoolRefresh,
+    /// Check every position instead of only active ones, every calc_all_positions_secs.
+    PositionScan,
+    /// Lower the pools' activity levels, every activity_level_clean_secs.
+    ActivityDecay,
 }
 
 /// Configuration for variables we need to pass to the strategy.
 #[derive(Debug, Clone)]
 pub struct Config {
     pub chain_id: u64,
+    /// Ignore positions whose last event is older than this block (0 disables).
+    pub skip_positions_inactive_since: u64,
+    /// Liquidation attempts of any one position before its account is skipped permanently (0 disables).
+    pub max_account_attempts: u64,
+    /// Clear the persisted permanently skipped accounts on startup.
+    pub clear_skipped_accounts: bool,
+    /// Allowed deviation of the computed debt from the on-chain debt, in bps.
+    pub repay_sanity_tolerance_bps: u64,
+    /// Only log liquidations instead of emitting actions.
+    pub observe_only: bool,
+    /// Minimum blocks between two full pool refreshes.
+    pub min_pool_refresh_blocks: u64,
+    /// Token all profits are valued in, the base token most pools quote in if unset.
+    pub accounting_token: Option<Address>,
+    /// Wrapped native token whose pool with the profit token prices gas, gas costs
+    /// aren't compared to profits without it.
+    pub native_token: Option<Address>,
+    /// Positions within this margin above the threshold are scanned every tick, in bps (0 disables).
+    pub fast_watch_margin_bps: u64,
+    /// Raise a margin call for positions within this margin above the threshold, in bps (0 disables).
+    pub margin_call_buffer_bps: u64,
+    /// File listing accounts to always scan and liquidate first, reloaded on change.
+    pub priority_accounts_file: Option<String>,
+    /// Only scan and liquidate positions of these borrowers, all positions when empty.
+    pub target_borrowers: Vec<Address>,
+    /// Re-fetch logs around chunk boundaries to fill holes left by the provider.
+    pub verify_log_boundaries: bool,
+    /// Initial blocks per eth_getLogs call of the position log backfill.
+    pub backfill_chunk_size: u64,
+    /// Emit a refuel when the liquidator's native balance drops below this (0 disables).
+    pub min_native_balance_wei: U256,
+    /// Native amount requested per refuel.
+    pub refuel_amount_wei: U256,
+    /// MINIMUM_LIQUIDITY pools are held to when the deployment has no PoolUtils to read it from.
+    pub minimum_liquidity: Option<U256>,
+    /// Between full scans only positions below this margin level are scanned, in percent (0 disables).
+    pub monitor_margin_level_threshold: u128,
+    /// Dormant positions scanned per tick between full scans, cycling through all of them (0 disables).
+    pub positions_per_tick: usize,
+    /// Share of expected profit the executor may spend on gas, in percent (0 caps at the full profit).
+    pub bid_percentage: u64,
+    /// JSON file with the deployment's contract addresses.
+    pub deployed_addresses_file: String,
+    /// The deployment's contract addresses from the `--deployments-file` registry, read instead of `deployed_addresses_file`.
+    pub deployment_addresses: Option<DeploymentAddresses>,
+    /// File the strategy state is cached in between runs.
+    pub state_cache_file: String,
+    /// Retries of pool and position reads on transient RPC errors.
+    pub rpc_max_retries: u32,
+    /// Stop emitting liquidations once a proxied deployment contract is upgraded.
+    pub pause_on_upgrade: bool,
+    /// Position logs arrive as [Event::Log] from a log collector instead of being fetched each tick.
+    pub logs_from_collector: bool,
+    /// Skip positions whose liquidation reverted in the protocol for this long (0 disables).
+    pub blacklist_ttl_secs: u64,
+    /// Position reads in flight at once while checking a scan's liquidations.
+    pub scan_concurrency: usize,
+    /// Skip liquidation batches whose expected profit net of estimated gas is below this,
+    /// both in 18-decimal units of the profit token (0 disables).
+    pub min_profit: u128,
+    /// Multicall3 contract batching the scan's reads, individual calls when unset or not deployed.
+    pub multicall_address: Option<Address>,
+    /// Calls per Multicall3 aggregate3.
+    pub multicall_batch_size: usize,
+    /// Stop liquidating once realized profit reaches this, in 18-decimal units of the accounting token (0 disables).
+    pub profit_target: u128,
+    /// Failed submissions in a row that trip the circuit breaker (0 disables).
+    pub breaker_threshold: u64,
+    /// Re-arm the tripped circuit breaker after this long (0 holds until restart).
+    pub breaker_cooldown_secs: u64,
+    /// Periodic tasks that wait a full interval after startup instead of running at once.
+    pub defer_on_startup: Vec<PeriodicTask>,
+    /// Fund liquidations with a flashloan through a helper contract instead of the liquidator's balance.
+    pub flashloan: Option<FlashloanConfig>,
 }
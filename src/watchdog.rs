@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::alerts;
+
+static LAST_EVENT_SECS: AtomicU64 = AtomicU64::new(0);
+static EVENTS_PROCESSED: AtomicU64 = AtomicU64::new(0);
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("Invalid timestamp").as_secs()
+}
+
+/// Mark an event as processed by a strategy.
+pub fn record_event() {
+    LAST_EVENT_SECS.store(now_secs(), Ordering::Relaxed);
+    EVENTS_PROCESSED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Start the idle clock at now unless an event was already processed, so a
+/// process that hasn't produced its first event yet isn't reported as stalled.
+pub fn arm() {
+    LAST_EVENT_SECS.compare_exchange(0, now_secs(), Ordering::Relaxed, Ordering::Relaxed).ok();
+}
+
+/// Seconds since the last processed event.
+pub fn idle_secs() -> u64 {
+    now_secs().saturating_sub(LAST_EVENT_SECS.load(Ordering::Relaxed))
+}
+
+/// Number of events processed so far.
+pub fn events_processed() -> u64 {
+    EVENTS_PROCESSED.load(Ordering::Relaxed)
+}
+
+/// Log a heartbeat every check and warn once no event was processed for
+/// `timeout`, exiting with a nonzero code when `exit_on_stall` is set so a
+/// supervisor restarts the wedged process.
+pub async fn run(timeout: Duration, exit_on_stall: bool) {
+    // give the engine until the first timeout to produce an event
+    arm();
+    let check_interval = std::cmp::max(timeout / 4, Duration::from_secs(1));
+    let mut stalled = false;
+    loop {
+        tokio::time::sleep(check_interval).await;
+        let idle = idle_secs();
+        info!("heartbeat: {} events processed, last one {}s ago", events_processed(), idle);
+        if idle < timeout.as_secs() {
+            if stalled {
+                info!("events are being processed again");
+                stalled = false;
+            }
+            continue;
+        }
+        if exit_on_stall {
+            error!("no event processed for {}s, exiting", idle);
+            alerts::send(format!("Watchdog: no event processed for {}s, exiting", idle));
+            // give the alert a moment to go out
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            std::process::exit(1);
+        }
+        if !stalled {
+            warn!("no event processed for {}s, the collectors may have stalled", idle);
+            alerts::send(format!("Watchdog: no event processed for {}s, the collectors may have stalled", idle));
+            stalled = true;
+        }
+    }
+}